@@ -3,10 +3,47 @@ use colored::Colorize;
 use engine::engine::Database;
 use engine::sql::QueryResult;
 use engine::sql::parser::parse_sql;
+use std::path::Path;
 
 fn main() {
-    let mut db = Database::open("./data");
-    let mut rl = rustyline::DefaultEditor::new().unwrap();
+    // `cli check [data_dir]` runs the integrity checker, `cli convert <data_dir> <file>`
+    // the converter and `cli upgrade [data_dir]` the file format upgrade instead of the
+    // REPL, which opens `cli [data_dir | file]`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("check") => {
+            let data_dir = args.get(1).map(String::as_str).unwrap_or("./data");
+            std::process::exit(check(data_dir));
+        }
+        Some("upgrade") => {
+            let data_dir = args.get(1).map(String::as_str).unwrap_or("./data");
+            std::process::exit(upgrade(data_dir));
+        }
+        Some("convert") => match (args.get(1), args.get(2)) {
+            (Some(data_dir), Some(file)) => std::process::exit(convert(data_dir, file)),
+            _ => {
+                eprintln!("{} cli convert <data_dir> <file>", "Usage:".red().bold());
+                std::process::exit(2);
+            }
+        },
+        _ => {}
+    }
+
+    let path = args.first().map(String::as_str).unwrap_or("./data");
+    let mut db = match Database::open(path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("{} {}", "Failed to open database:".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+    let mut rl = match rustyline::DefaultEditor::new() {
+        Ok(rl) => rl,
+        Err(e) => {
+            eprintln!("{} {}", "Failed to start the line editor:".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
 
     println!(
         "{}",
@@ -28,11 +65,11 @@ fn main() {
                         for cmd in commands {
                             match db.execute(cmd) {
                                 Ok(msg) => print_result(msg),
-                                Err(e) => println!("{} {}", "Error:".red().bold(), e),
+                                Err(e) => print_result(e.into()),
                             }
                         }
                     }
-                    Err(e) => println!("{} {}", "SQL Error:".red().bold(), e),
+                    Err(e) => print_result(e.into()),
                 }
             }
             Err(_) => break,
@@ -40,15 +77,115 @@ fn main() {
     }
 }
 
+/// Prints every integrity problem in `data_dir` and returns the exit code: 0 if
+/// there were none, 1 otherwise.
+fn check(data_dir: &str) -> i32 {
+    // Opening would create a missing database, which would then pass the check
+    let path = Path::new(data_dir);
+    let missing = if !path.exists() {
+        Some(format!("{} does not exist", data_dir))
+    } else if path.is_dir()
+        && !path.join("scardb_master.db").is_file()
+        && !path.join("catalog.json").is_file()
+    {
+        Some(format!("{} has no catalog", data_dir))
+    } else {
+        None
+    };
+    if let Some(message) = missing {
+        eprintln!("{} {}", "Not a database:".red().bold(), message);
+        return 1;
+    }
+
+    let mut db = match Database::open(data_dir) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("{} {}", "Failed to open database:".red().bold(), e);
+            return 1;
+        }
+    };
+    match db.integrity_check() {
+        Ok(problems) if problems.is_empty() => {
+            println!("{}", "ok".green());
+            0
+        }
+        Ok(problems) => {
+            for problem in &problems {
+                println!("{}", problem.red());
+            }
+            println!("{} problems found.", problems.len());
+            1
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Integrity check failed:".red().bold(), e);
+            1
+        }
+    }
+}
+
+/// Copies the database in `data_dir` into the single-file database `file` and
+/// returns the exit code.
+fn convert(data_dir: &str, file: &str) -> i32 {
+    match Database::convert_to_single_file(data_dir, file) {
+        Ok(()) => {
+            println!(
+                "{}",
+                format!("Converted {} into {}.", data_dir, file).green()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Conversion failed:".red().bold(), e);
+            1
+        }
+    }
+}
+
+/// Rewrites the table files in `data_dir` that predate the file header into the
+/// current format and returns the exit code.
+fn upgrade(data_dir: &str) -> i32 {
+    match Database::upgrade(data_dir) {
+        Ok(tables) if tables.is_empty() => {
+            println!("{}", "Nothing to upgrade.".green());
+            0
+        }
+        Ok(tables) => {
+            println!(
+                "{}",
+                format!("Upgraded tables {}.", tables.join(", ")).green()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Upgrade failed:".red().bold(), e);
+            1
+        }
+    }
+}
+
 fn print_result(result: QueryResult) {
     match result {
-        QueryResult::Message(msg) => {
-            // Color success messages green
-            if msg.contains("success") || msg.contains("created") || msg.contains("inserted") {
-                println!("{}", msg.green());
-            } else {
-                println!("{}", msg.cyan());
+        QueryResult::Error { sqlstate, message } => {
+            println!(
+                "{} {}",
+                format!("Error [{}]:", sqlstate).red().bold(),
+                message
+            )
+        }
+        QueryResult::RowsAffected {
+            count,
+            last_insert_id,
+        } => {
+            let mut msg = format!("{} rows affected.", count);
+            if let Some(id) = last_insert_id {
+                msg.push_str(&format!(" Last insert id: {}", id));
             }
+            println!("{}", msg.green());
+        }
+        QueryResult::DdlOk { message, .. } => println!("{}", message.green()),
+        QueryResult::Empty => {}
+        QueryResult::Data(resp) if resp.rows.is_empty() => {
+            println!("{} ({})", "No rows found.".cyan(), resp.columns.join(", "));
         }
         QueryResult::Data(resp) => {
             let table = resp
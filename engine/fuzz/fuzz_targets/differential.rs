@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // One directory per process, so parallel jobs (-jobs=N) don't share files
+    let data_dir = std::env::temp_dir().join(format!("engine-fuzz-{}", std::process::id()));
+    engine::fuzzing::check_differential(data, &data_dir.to_string_lossy());
+});
@@ -1,12 +1,24 @@
 pub mod schema;
+pub mod system;
 
 use serde::{Deserialize, Serialize};
 
 use crate::catalog::schema::Schema;
+use crate::error::{CatalogKind, Error, Result};
+use crate::storage::Table;
+use crate::storage::pager::Pager;
+use crate::storage::record::Row;
+use crate::storage::single_file::{self, SharedFile};
 use std::collections::HashMap;
-use std::fs;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
 
-#[derive(Serialize, Deserialize, Default)]
+/// AUTOINCREMENT ids reserved per catalog write. The catalog records the end of the
+/// reserved range, so only one insert in this many has to save it.
+pub const SEQUENCE_BATCH: i32 = 32;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct CatalogData {
     pub tables: HashMap<String, Schema>,
     pub sequences: HashMap<String, i32>,
@@ -15,49 +27,287 @@ pub struct CatalogData {
 pub struct Catalog {
     pub tables: HashMap<String, Schema>,
     pub sequences: HashMap<String, i32>,
-    path: String,
+    // Last id of each sequence recorded in the saved catalog. Ids up to it may be
+    // handed out without saving; after a crash they are skipped, never reused.
+    reserved: HashMap<String, i32>,
+    // The read-only system tables: `scardb_master`, where the catalog is saved, and
+    // the `information_schema` tables, rebuilt from the catalog when queried
+    system: HashMap<String, Schema>,
+    store: Store,
+}
+
+/// Where the catalog is saved.
+enum Store {
+    // The `scardb_master` table file in this data directory
+    Directory(String),
+    // The `scardb_master` segment of a single-file database, written on its next commit
+    SingleFile(SharedFile),
 }
 
 impl Catalog {
-    pub fn load_or_create(path: &str) -> Self {
-        if let Ok(data) = fs::read_to_string(path) {
-            let data: CatalogData = serde_json::from_str(&data).unwrap_or_default();
+    fn new(data: CatalogData, store: Store) -> Self {
+        Self {
+            tables: data.tables,
+            reserved: data.sequences.clone(),
+            sequences: data.sequences,
+            system: system::schemas(),
+            store,
+        }
+    }
 
-            return Self {
-                tables: data.tables,
-                sequences: data.sequences,
-                path: path.to_string(),
-            };
+    /// Opens the catalog of the database in `data_dir`, creating an empty one if there
+    /// is none. A catalog still in the `catalog.json` of older versions is moved into
+    /// `scardb_master`, and the JSON file removed once the table is saved.
+    pub fn open_directory(data_dir: &str) -> Result<Self> {
+        let migrate = !Path::new(&master_path(data_dir)).exists();
+        let data = Self::read_directory(data_dir)?;
+        if migrate {
+            check_migration(&data)?;
         }
-        Self {
-            tables: HashMap::new(),
-            path: path.to_string(),
-            sequences: HashMap::new(),
+        let catalog = Self::new(data, Store::Directory(data_dir.to_string()));
+        if migrate {
+            catalog.save()?;
+            let json_path = json_path(data_dir);
+            if Path::new(&json_path).exists() {
+                fs::remove_file(&json_path)?;
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// The catalog of the database in `data_dir`, read without writing anything: from
+    /// `scardb_master`, else from the `catalog.json` of older versions, else empty.
+    /// A catalog that can't be read is a corruption error, not an empty database.
+    pub fn read_directory(data_dir: &str) -> Result<CatalogData> {
+        let path = master_path(data_dir);
+        if Path::new(&path).exists() {
+            return read_master(Pager::open_read_only(&path)?, &path);
+        }
+
+        let path = json_path(data_dir);
+        match fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data)
+                .map_err(|e| Error::Corruption(format!("Catalog {} can't be read: {}", path, e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CatalogData::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The catalog kept in the `scardb_master` segment of a single-file database.
+    /// One still in the directory of the file, as older versions kept it, is moved
+    /// into the segment; both changes reach the file with the next commit.
+    pub fn in_single_file(file: &SharedFile) -> Result<Self> {
+        let (saved, path, data) = {
+            let single = single_file::lock(file);
+            let path = format!("{}:{}", single.path(), system::MASTER);
+            (
+                single.has_segment(system::MASTER),
+                path,
+                single.catalog().clone(),
+            )
+        };
+        if saved {
+            let data = read_master(Pager::segment(file, system::MASTER)?, &path)?;
+            return Ok(Self::new(data, Store::SingleFile(file.clone())));
+        }
+
+        check_migration(&data)?;
+        let catalog = Self::new(data, Store::SingleFile(file.clone()));
+        catalog.save()?;
+        single_file::lock(file).set_catalog(CatalogData::default());
+        Ok(catalog)
+    }
+
+    /// The rows of system table `name`: those `scardb_master` holds, read from its
+    /// storage, or those of an `information_schema` table, built from the catalog.
+    pub fn system_rows(&self, name: &str) -> Result<Vec<Row>> {
+        if name != system::MASTER {
+            return Ok(system::rows(self, name));
         }
+        let pager = match &self.store {
+            Store::Directory(data_dir) => Pager::open_read_only(&master_path(data_dir))?,
+            Store::SingleFile(file) => Pager::segment(file, system::MASTER)?,
+        };
+        Table::new(pager, system::master_schema()).scan_rows()
+    }
+
+    /// The schema of a table, or of a system table, by name.
+    pub fn schema(&self, name: &str) -> Option<&Schema> {
+        self.tables.get(name).or_else(|| self.system.get(name))
     }
 
-    pub fn add_table(&mut self, schema: Schema) {
+    pub fn add_table(&mut self, schema: Schema) -> Result<()> {
         let name = schema.table_name.clone();
         self.tables.insert(name.clone(), schema);
         self.sequences.entry(name).or_insert(0);
-        self.save();
+        self.save()
+    }
+
+    pub fn update_table(&mut self, schema: Schema) -> Result<()> {
+        self.tables.insert(schema.table_name.clone(), schema);
+        self.save()
+    }
+
+    pub fn rename_table(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        if let Some(mut schema) = self.tables.remove(old_name) {
+            schema.table_name = new_name.to_string();
+            self.tables.insert(new_name.to_string(), schema);
+        }
+        if let Some(seq) = self.sequences.remove(old_name) {
+            self.sequences.insert(new_name.to_string(), seq);
+        }
+        if let Some(reserved) = self.reserved.remove(old_name) {
+            self.reserved.insert(new_name.to_string(), reserved);
+        }
+        self.save()
     }
 
-    pub fn get_next_id(&mut self, table_name: &str) -> i32 {
+    /// Hands out the next AUTOINCREMENT id of `table_name`. The catalog is only
+    /// saved when the ids reserved by the last save run out.
+    pub fn get_next_id(&mut self, table_name: &str) -> Result<i32> {
         let current_id = self.sequences.get(table_name).cloned().unwrap_or(0);
         let next_id = current_id + 1;
         self.sequences.insert(table_name.to_string(), next_id);
-        self.save();
-        next_id
+        if next_id > self.reserved_id(table_name) {
+            self.reserved.insert(
+                table_name.to_string(),
+                next_id.saturating_add(SEQUENCE_BATCH - 1),
+            );
+            self.save()?;
+        }
+        Ok(next_id)
+    }
+
+    /// Moves the sequence of `table_name` past `value`, so an id set by hand
+    /// is never handed out again.
+    pub fn advance_sequence(&mut self, table_name: &str, value: i32) -> Result<()> {
+        let current_id = self.sequences.get(table_name).cloned().unwrap_or(0);
+        if value > current_id {
+            self.sequences.insert(table_name.to_string(), value);
+            if value > self.reserved_id(table_name) {
+                self.reserved.insert(table_name.to_string(), value);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the sequence of `table_name` over from 1. Only safe once the table is empty.
+    pub fn reset_sequence(&mut self, table_name: &str) -> Result<()> {
+        self.sequences.insert(table_name.to_string(), 0);
+        self.reserved.remove(table_name);
+        self.save()
     }
 
-    pub fn save(&self) {
+    fn reserved_id(&self, table_name: &str) -> i32 {
+        self.reserved.get(table_name).cloned().unwrap_or(0)
+    }
+
+    /// Saves the catalog, recording the end of each sequence's reserved ids.
+    pub fn save(&self) -> Result<()> {
+        let sequences = self
+            .sequences
+            .iter()
+            .map(|(name, &id)| (name.clone(), id.max(self.reserved_id(name))))
+            .collect();
+        self.write(sequences)
+    }
+
+    fn write(&self, sequences: HashMap<String, i32>) -> Result<()> {
         let data_to_save = CatalogData {
             tables: self.tables.clone(),
-            sequences: self.sequences.clone(),
+            sequences,
         };
-        let json =
-            serde_json::to_string_pretty(&data_to_save).expect("Failed to serialize catalog");
-        fs::write(&self.path, json).expect("Unable to save catalog file");
+        let rows = system::master_rows(&data_to_save)?;
+        match &self.store {
+            Store::Directory(data_dir) => {
+                // Written in full to a new file that replaces the old one, so a crash
+                // leaves either the old catalog or the new one
+                let path = master_path(data_dir);
+                let tmp_path = format!("{}.tmp", path);
+                if Path::new(&tmp_path).exists() {
+                    fs::remove_file(&tmp_path)?;
+                }
+                let mut table = write_master(Pager::open(&tmp_path)?, rows)?;
+                table.pager.flush()?;
+                drop(table);
+                fs::rename(&tmp_path, &path)?;
+                File::open(data_dir)?.sync_all()?;
+            }
+            Store::SingleFile(file) => {
+                // A segment no table has, renamed over the old one once it is whole
+                let mut tmp_name = format!("{}.tmp", system::MASTER);
+                while self.tables.contains_key(&tmp_name) {
+                    tmp_name.push_str(".tmp");
+                }
+                single_file::lock(file).remove_segment(&tmp_name);
+                write_master(Pager::segment(file, &tmp_name)?, rows)?;
+                single_file::lock(file).rename_segment(&tmp_name, system::MASTER);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Catalog {
+    /// Gives back the ids reserved but not handed out, so a clean shutdown leaves
+    /// no gaps in the sequences. Best-effort: saved ranges are safe to leave as they are.
+    fn drop(&mut self) {
+        if self
+            .sequences
+            .iter()
+            .any(|(name, &id)| id < self.reserved_id(name))
+        {
+            let _ = self.write(self.sequences.clone());
+        }
+    }
+}
+
+fn master_path(data_dir: &str) -> String {
+    format!("{}/{}.db", data_dir, system::MASTER)
+}
+
+// Where older versions saved the catalog of a directory database
+fn json_path(data_dir: &str) -> String {
+    format!("{}/catalog.json", data_dir)
+}
+
+/// Refuses to move a catalog into `scardb_master` when one of its own tables has
+/// that name, as saving the catalog would replace the table's rows.
+fn check_migration(data: &CatalogData) -> Result<()> {
+    if data.tables.contains_key(system::MASTER) {
+        return Err(Error::catalog(
+            CatalogKind::DuplicateTable,
+            format!(
+                "Table {} is needed to store the catalog; rename it with an older version first",
+                system::MASTER
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the catalog from the `scardb_master` storage that `pager` opened at `path`.
+fn read_master(pager: Pager, path: &str) -> Result<CatalogData> {
+    let schema = system::master_schema();
+    if pager.header().schema_fingerprint != schema.fingerprint() {
+        return Err(Error::Corruption(format!(
+            "{} was written for a different layout of table {}",
+            path,
+            system::MASTER
+        )));
+    }
+    let rows = Table::new(pager, schema).scan_rows()?;
+    system::catalog_data(rows, path)
+}
+
+/// Fills the empty `scardb_master` storage that `pager` opened with `rows`.
+fn write_master(mut pager: Pager, rows: Vec<Row>) -> Result<Table> {
+    let schema = system::master_schema();
+    pager.set_schema_fingerprint(schema.fingerprint())?;
+    let mut table = Table::new(pager, schema);
+    for row in rows {
+        table.insert_row(row)?;
     }
+    Ok(table)
 }
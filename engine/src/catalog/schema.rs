@@ -1,5 +1,11 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use crate::error::{CatalogKind, Error, Result};
+use crate::sql::expr::Expr;
+use crate::storage::record::Field;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DataType {
     Integer,
@@ -13,12 +19,43 @@ pub struct Column {
     pub data_type: DataType,
     pub is_primary: bool,
     pub is_autoincrement: bool,
+    // Nullable columns carry an extra flag byte on disk
+    #[serde(default)]
+    pub is_nullable: bool,
+    // Value used for omitted columns and to back-fill rows on ADD COLUMN
+    #[serde(default)]
+    pub default: Option<Field>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Schema {
     pub table_name: String,
     pub columns: Vec<Column>,
+    // Column groups covered by a UNIQUE constraint
+    #[serde(default)]
+    pub unique: Vec<Vec<String>>,
+    #[serde(default)]
+    pub checks: Vec<Expr>,
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub foreign_table: String,
+    pub referred_columns: Vec<String>,
+    pub on_delete: ReferentialAction,
+    #[serde(default)]
+    pub on_update: ReferentialAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ReferentialAction {
+    #[default]
+    Restrict,
+    Cascade,
+    SetNull,
 }
 
 impl DataType {
@@ -29,6 +66,80 @@ impl DataType {
             DataType::Text(len) => *len,
         }
     }
+
+    pub fn accepts(&self, field: &Field) -> bool {
+        matches!(
+            (self, field),
+            (DataType::Integer, Field::Integer(_))
+                | (DataType::Boolean, Field::Boolean(_))
+                | (DataType::Text(_), Field::Text(_))
+        )
+    }
+
+    /// Returns an error if `field` is text longer than this type stores. Text is kept in
+    /// a fixed number of bytes, so a longer value would be cut short on disk.
+    pub fn check_length(&self, field: &Field, column_name: &str) -> Result<()> {
+        if let (DataType::Text(max_len), Field::Text(text)) = (self, field)
+            && text.len() > *max_len
+        {
+            return Err(Error::StringTooLong(format!(
+                "Value for column '{}' is {} bytes long, but {} holds at most {}",
+                column_name,
+                text.len(),
+                self,
+                max_len
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns true if every value of this type can be stored in `target` without loss.
+    pub fn widens_to(&self, target: &DataType) -> bool {
+        match (self, target) {
+            (DataType::Integer, DataType::Integer) => true,
+            (DataType::Boolean, DataType::Boolean) => true,
+            (DataType::Boolean, DataType::Integer) => true,
+            (DataType::Text(from), DataType::Text(to)) => to >= from,
+            // "-2147483648" is the longest possible rendering of an i32
+            (DataType::Integer, DataType::Text(len)) => *len >= 11,
+            (DataType::Boolean, DataType::Text(len)) => *len >= 5,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ReferentialAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReferentialAction::Restrict => write!(f, "RESTRICT"),
+            ReferentialAction::Cascade => write!(f, "CASCADE"),
+            ReferentialAction::SetNull => write!(f, "SET NULL"),
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    /// The type as written in SQL. `TEXT` is shown as the `VARCHAR(255)` it stands for.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataType::Integer => write!(f, "INT"),
+            DataType::Boolean => write!(f, "BOOLEAN"),
+            DataType::Text(len) => write!(f, "VARCHAR({})", len),
+        }
+    }
+}
+
+impl Column {
+    pub fn byte_size(&self) -> usize {
+        self.data_type.byte_size() + usize::from(self.is_nullable)
+    }
+
+    pub fn accepts(&self, field: &Field) -> bool {
+        match field {
+            Field::Null => self.is_nullable,
+            _ => self.data_type.accepts(field),
+        }
+    }
 }
 
 impl Schema {
@@ -36,9 +147,162 @@ impl Schema {
         let mut total_bytes = 0;
 
         for column in &self.columns {
-            total_bytes += column.data_type.byte_size();
+            total_bytes += column.byte_size();
         }
 
         total_bytes
     }
+
+    /// A hash of the on-disk row layout, stored in the table file's header so a
+    /// file isn't read with the wrong schema. Names and constraints don't affect
+    /// the layout, so renaming a column keeps the fingerprint. Never 0, which
+    /// marks a file with no fingerprint yet.
+    pub fn fingerprint(&self) -> u64 {
+        // 64-bit FNV-1a, which unlike `DefaultHasher` is the same in every build
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for column in &self.columns {
+            let (tag, size) = match column.data_type {
+                DataType::Integer => (1u8, 0),
+                DataType::Boolean => (2, 0),
+                DataType::Text(len) => (3, len as u64),
+            };
+            let bytes = [tag, column.is_nullable as u8]
+                .into_iter()
+                .chain(size.to_le_bytes());
+            for byte in bytes {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+        hash.max(1)
+    }
+
+    /// Finds a column by name. `table.column` also matches a column of this table,
+    /// and in the merged schema of a join, whose columns are named `table.column`,
+    /// a bare column name matches the first table that has it.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        if let Some(i) = self.columns.iter().position(|c| c.name == name) {
+            return Some(i);
+        }
+        if let Some((table, column)) = name.split_once('.')
+            && table == self.table_name
+        {
+            return self.columns.iter().position(|c| c.name == column);
+        }
+        self.columns.iter().position(|c| {
+            c.name
+                .split_once('.')
+                .is_some_and(|(_, column)| column == name)
+        })
+    }
+
+    pub fn column_indices(&self, names: &[String]) -> Result<Vec<usize>> {
+        names
+            .iter()
+            .map(|name| {
+                self.column_index(name).ok_or_else(|| {
+                    Error::catalog(
+                        CatalogKind::UndefinedColumn,
+                        format!("Column {} not found in table {}", name, self.table_name),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+
+    pub fn primary_key_columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .filter(|c| c.is_primary)
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// Positions of the primary key columns, in schema order. A composite key
+    /// is encoded and compared in this order.
+    pub fn primary_key_indices(&self) -> Vec<usize> {
+        self.columns
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.is_primary)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// True if `columns` is exactly the primary key or one of the UNIQUE groups,
+    /// i.e. a set of columns a foreign key may reference.
+    pub fn is_unique_key(&self, columns: &[String]) -> bool {
+        let pk = self.primary_key_columns();
+        (!pk.is_empty() && pk == columns) || self.unique.iter().any(|u| u == columns)
+    }
+
+    /// The CREATE TABLE statement that makes this table, as SHOW CREATE TABLE prints it.
+    /// Parsing it back gives the same schema.
+    pub fn create_statement(&self) -> String {
+        let primary_key = self.primary_key_columns();
+        let mut lines = Vec::new();
+        for column in &self.columns {
+            let mut line = format!("{} {}", column.name, column.data_type);
+            if column.is_primary && primary_key.len() == 1 {
+                line.push_str(" PRIMARY KEY");
+            } else if !column.is_nullable && !column.is_primary {
+                line.push_str(" NOT NULL");
+            }
+            if column.is_autoincrement {
+                line.push_str(" AUTOINCREMENT");
+            }
+            if let Some(default) = &column.default {
+                line.push_str(&format!(" DEFAULT {}", Expr::Literal(default.clone())));
+            }
+            lines.push(line);
+        }
+
+        if primary_key.len() > 1 {
+            lines.push(format!("PRIMARY KEY ({})", primary_key.join(", ")));
+        }
+        for group in &self.unique {
+            lines.push(format!("UNIQUE ({})", group.join(", ")));
+        }
+        for check in &self.checks {
+            lines.push(format!("CHECK ({})", check));
+        }
+        for fk in &self.foreign_keys {
+            let mut line = format!(
+                "FOREIGN KEY ({}) REFERENCES {} ({})",
+                fk.columns.join(", "),
+                fk.foreign_table,
+                fk.referred_columns.join(", ")
+            );
+            if fk.on_delete != ReferentialAction::Restrict {
+                line.push_str(&format!(" ON DELETE {}", fk.on_delete));
+            }
+            if fk.on_update != ReferentialAction::Restrict {
+                line.push_str(&format!(" ON UPDATE {}", fk.on_update));
+            }
+            lines.push(line);
+        }
+
+        format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            self.table_name,
+            lines.join(",\n    ")
+        )
+    }
+
+    /// True if any constraint of this table mentions `column`.
+    pub fn constrains_column(&self, column: &str) -> bool {
+        self.unique.iter().flatten().any(|c| c == column)
+            || self
+                .checks
+                .iter()
+                .any(|check| check.columns().contains(&column))
+            || self
+                .foreign_keys
+                .iter()
+                .any(|fk| fk.columns.iter().any(|c| c == column))
+    }
 }
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::catalog::schema::{Column, DataType, Schema};
+use crate::catalog::{Catalog, CatalogData};
+use crate::error::{Error, Result};
+use crate::sql::expr::Expr;
+use crate::storage::record::{Field, Row};
+
+/// One row per table.
+pub const TABLES: &str = "information_schema.tables";
+/// One row per column of every table, in column order.
+pub const COLUMNS: &str = "information_schema.columns";
+/// The catalog itself, stored like any table: the definition of each table as JSON,
+/// cut into parts that fit a row.
+pub const MASTER: &str = "scardb_master";
+
+// Longest text in a system table; longer values are cut short
+const TEXT_LENGTH: usize = 255;
+// Bytes of a table definition held by one row of `scardb_master`
+const PART_LENGTH: usize = 1024;
+
+pub fn is_system_table(name: &str) -> bool {
+    name == TABLES || name == COLUMNS || name == MASTER
+}
+
+/// The schemas of the system tables, by name.
+pub fn schemas() -> HashMap<String, Schema> {
+    [
+        schema(
+            TABLES,
+            &[
+                ("table_name", DataType::Text(TEXT_LENGTH), false),
+                ("table_type", DataType::Text(16), false),
+                ("column_count", DataType::Integer, false),
+                ("autoincrement_value", DataType::Integer, true),
+            ],
+        ),
+        schema(
+            COLUMNS,
+            &[
+                ("table_name", DataType::Text(TEXT_LENGTH), false),
+                ("column_name", DataType::Text(TEXT_LENGTH), false),
+                ("ordinal_position", DataType::Integer, false),
+                ("data_type", DataType::Text(16), false),
+                ("is_nullable", DataType::Text(3), false),
+                ("column_default", DataType::Text(TEXT_LENGTH), true),
+                ("is_primary_key", DataType::Boolean, false),
+                ("is_unique", DataType::Boolean, false),
+                ("is_autoincrement", DataType::Boolean, false),
+            ],
+        ),
+        master_schema(),
+    ]
+    .into_iter()
+    .map(|schema| (schema.table_name.clone(), schema))
+    .collect()
+}
+
+/// The schema of `scardb_master`. Only the definitions are read back; the table
+/// names are there to make the table easier to query.
+pub fn master_schema() -> Schema {
+    schema(
+        MASTER,
+        &[
+            ("table_name", DataType::Text(TEXT_LENGTH), false),
+            ("part", DataType::Integer, false),
+            ("sequence", DataType::Integer, true),
+            ("definition", DataType::Text(PART_LENGTH), false),
+        ],
+    )
+}
+
+/// The rows `scardb_master` holds for `data`, ordered by table name and then part.
+/// The first part of each table also holds the sequence of the table, if it has one.
+pub fn master_rows(data: &CatalogData) -> Result<Vec<Row>> {
+    let mut tables: Vec<&Schema> = data.tables.values().collect();
+    tables.sort_by(|a, b| a.table_name.cmp(&b.table_name));
+
+    let mut rows = Vec::new();
+    for schema in tables {
+        let json = serde_json::to_string(schema).map_err(io::Error::other)?;
+        let mut rest = json.as_str();
+        let mut part = 0;
+        while !rest.is_empty() {
+            let mut end = rest.len().min(PART_LENGTH);
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            let (definition, tail) = rest.split_at(end);
+            let sequence = match data.sequences.get(&schema.table_name) {
+                Some(&value) if part == 0 => Field::Integer(value),
+                _ => Field::Null,
+            };
+            rows.push(Row {
+                fields: vec![
+                    text(&schema.table_name),
+                    Field::Integer(part),
+                    sequence,
+                    Field::Text(definition.to_string()),
+                ],
+            });
+            rest = tail;
+            part += 1;
+        }
+    }
+    Ok(rows)
+}
+
+/// Puts the catalog back together from the rows of `scardb_master` at `path`, in the
+/// order `master_rows` wrote them. Missing or unreadable parts are a corruption error.
+pub fn catalog_data(rows: Vec<Row>, path: &str) -> Result<CatalogData> {
+    let corrupt =
+        |detail: String| Error::Corruption(format!("Catalog {} can't be read: {}", path, detail));
+    let mut data = CatalogData::default();
+    // Each table's definition so far, with its sequence, until its next part
+    let mut tables: Vec<(String, Option<i32>)> = Vec::new();
+    let mut next_part = 0;
+
+    for row in rows {
+        let [_, Field::Integer(part), sequence, Field::Text(definition)] = row.fields.as_slice()
+        else {
+            return Err(corrupt("a row has the wrong fields".to_string()));
+        };
+        match (*part, tables.last_mut()) {
+            (0, _) => tables.push((
+                definition.clone(),
+                match sequence {
+                    Field::Integer(value) => Some(*value),
+                    _ => None,
+                },
+            )),
+            (part, Some((text, _))) if part == next_part => text.push_str(definition),
+            (part, _) => {
+                return Err(corrupt(format!(
+                    "part {} of a definition comes without the part before it",
+                    part
+                )));
+            }
+        }
+        next_part = part + 1;
+    }
+
+    for (definition, sequence) in tables {
+        let schema: Schema =
+            serde_json::from_str(&definition).map_err(|e| corrupt(e.to_string()))?;
+        if let Some(value) = sequence {
+            data.sequences.insert(schema.table_name.clone(), value);
+        }
+        data.tables.insert(schema.table_name.clone(), schema);
+    }
+    Ok(data)
+}
+
+/// The rows system table `name` should hold for `catalog`, ordered by table name
+/// and then column position.
+pub fn rows(catalog: &Catalog, name: &str) -> Vec<Row> {
+    let mut tables: Vec<&Schema> = catalog.tables.values().collect();
+    tables.sort_by(|a, b| a.table_name.cmp(&b.table_name));
+
+    let mut rows = Vec::new();
+    for schema in tables {
+        if name == TABLES {
+            let has_sequence = schema.columns.iter().any(|c| c.is_autoincrement);
+            let sequence = catalog.sequences.get(&schema.table_name).copied();
+            rows.push(Row {
+                fields: vec![
+                    text(&schema.table_name),
+                    text("BASE TABLE"),
+                    Field::Integer(schema.columns.len() as i32),
+                    match sequence {
+                        Some(value) if has_sequence => Field::Integer(value),
+                        _ => Field::Null,
+                    },
+                ],
+            });
+            continue;
+        }
+
+        rows.extend(column_rows(schema));
+    }
+    rows
+}
+
+/// The rows `information_schema.columns` holds for the columns of one table.
+pub fn column_rows(schema: &Schema) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for (i, column) in schema.columns.iter().enumerate() {
+        let is_unique = schema
+            .unique
+            .iter()
+            .any(|group| group.len() == 1 && group[0] == column.name);
+        rows.push(Row {
+            fields: vec![
+                text(&schema.table_name),
+                text(&column.name),
+                Field::Integer(i as i32 + 1),
+                text(&column.data_type.to_string()),
+                text(if column.is_nullable { "YES" } else { "NO" }),
+                match &column.default {
+                    Some(default) => text(&Expr::Literal(default.clone()).to_string()),
+                    None => Field::Null,
+                },
+                Field::Boolean(column.is_primary),
+                Field::Boolean(is_unique),
+                Field::Boolean(column.is_autoincrement),
+            ],
+        });
+    }
+    rows
+}
+
+fn schema(name: &str, columns: &[(&str, DataType, bool)]) -> Schema {
+    Schema {
+        table_name: name.to_string(),
+        columns: columns
+            .iter()
+            .map(|(column, data_type, is_nullable)| Column {
+                name: column.to_string(),
+                data_type: data_type.clone(),
+                is_primary: false,
+                is_autoincrement: false,
+                is_nullable: *is_nullable,
+                default: None,
+            })
+            .collect(),
+        unique: Vec::new(),
+        checks: Vec::new(),
+        foreign_keys: Vec::new(),
+    }
+}
+
+/// A text field cut to fit the system tables without splitting a character.
+fn text(value: &str) -> Field {
+    let mut end = value.len().min(TEXT_LENGTH);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    Field::Text(value[..end].to_string())
+}
@@ -0,0 +1,531 @@
+use std::collections::BTreeMap;
+
+use crate::catalog::schema::{ForeignKey, ReferentialAction, Schema};
+use crate::engine::Database;
+use crate::error::{CatalogKind, ConstraintKind, Error, Result};
+use crate::storage::pager::Page;
+use crate::storage::record::{Field, Row};
+
+/// Checks types, NOT NULL and every CHECK constraint of `schema` against `row`.
+pub(crate) fn validate_row(schema: &Schema, row: &Row) -> Result<()> {
+    for (column, provided) in schema.columns.iter().zip(&row.fields) {
+        if *provided == Field::Null && !column.is_nullable {
+            return Err(Error::constraint(
+                ConstraintKind::NotNull,
+                format!("Column '{}' cannot be NULL", column.name),
+            ));
+        }
+        if !column.accepts(provided) {
+            return Err(Error::Type(format!(
+                "Type mismatch for column '{}': expected {:?}, found {:?}",
+                column.name, column.data_type, provided
+            )));
+        }
+        column.data_type.check_length(provided, &column.name)?;
+    }
+
+    for check in &schema.checks {
+        if !check.is_satisfied_by(row, schema)? {
+            return Err(Error::constraint(
+                ConstraintKind::Check,
+                format!("CHECK constraint {} violated", check),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that at least one row of `schema` fits in a page.
+pub(crate) fn check_row_size(schema: &Schema) -> Result<()> {
+    if Page::max_slots(schema.row_size()) == 0 {
+        return Err(Error::catalog(
+            CatalogKind::InvalidDefinition,
+            format!(
+                "Rows of table {} are {} bytes, which doesn't fit in a page",
+                schema.table_name,
+                schema.row_size()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn format_values(values: &[&Field]) -> String {
+    values
+        .iter()
+        .map(|v| match v {
+            Field::Integer(i) => i.to_string(),
+            Field::Boolean(b) => b.to_string(),
+            Field::Text(t) => format!("'{}'", t),
+            Field::Null => "NULL".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Everything a DELETE touches once foreign key actions are followed, keyed by
+/// table and then by (page_idx, slot_idx).
+#[derive(Default)]
+struct DeletePlan {
+    deletes: BTreeMap<String, BTreeMap<(usize, usize), Row>>,
+    set_null: BTreeMap<String, BTreeMap<(usize, usize), Row>>,
+}
+
+impl DeletePlan {
+    fn is_deleted(&self, table_name: &str, location: (usize, usize)) -> bool {
+        self.deletes
+            .get(table_name)
+            .is_some_and(|rows| rows.contains_key(&location))
+    }
+}
+
+/// The new version of every row an UPDATE touches once foreign key actions are
+/// followed, keyed by table and then by (page_idx, slot_idx).
+#[derive(Default)]
+struct UpdatePlan {
+    updates: BTreeMap<String, BTreeMap<(usize, usize), Row>>,
+}
+
+impl Database {
+    /// Checks that the constraints of a new table are well formed. Foreign keys that
+    /// don't name their referred columns are pointed at the parent's primary key.
+    pub(crate) fn validate_constraints(&self, schema: &mut Schema) -> Result<()> {
+        check_row_size(schema)?;
+
+        for columns in &schema.unique {
+            schema.column_indices(columns)?;
+        }
+
+        for check in &schema.checks {
+            for column in check.columns() {
+                if schema.column_index(column).is_none() {
+                    return Err(Error::catalog(
+                        CatalogKind::UndefinedColumn,
+                        format!("CHECK constraint uses unknown column {}", column),
+                    ));
+                }
+            }
+        }
+
+        let snapshot = schema.clone();
+        for fk in &mut schema.foreign_keys {
+            let parent = if fk.foreign_table == snapshot.table_name {
+                &snapshot
+            } else {
+                self.catalog.tables.get(&fk.foreign_table).ok_or_else(|| {
+                    Error::catalog(
+                        CatalogKind::UndefinedTable,
+                        format!("Referenced table {} not found", fk.foreign_table),
+                    )
+                })?
+            };
+
+            if fk.referred_columns.is_empty() {
+                fk.referred_columns = parent.primary_key_columns();
+            }
+            if fk.columns.len() != fk.referred_columns.len() {
+                return Err(Error::catalog(
+                    CatalogKind::InvalidDefinition,
+                    format!(
+                        "Foreign key ({}) and referenced columns ({}) differ in length",
+                        fk.columns.join(", "),
+                        fk.referred_columns.join(", ")
+                    ),
+                ));
+            }
+            if !parent.is_unique_key(&fk.referred_columns) {
+                return Err(Error::catalog(
+                    CatalogKind::InvalidDefinition,
+                    format!(
+                        "Foreign key must reference the primary key or a UNIQUE column set of {}",
+                        fk.foreign_table
+                    ),
+                ));
+            }
+
+            let child_idx = snapshot.column_indices(&fk.columns)?;
+            let parent_idx = parent.column_indices(&fk.referred_columns)?;
+            for (&c, &p) in child_idx.iter().zip(&parent_idx) {
+                let child_col = &snapshot.columns[c];
+                let parent_col = &parent.columns[p];
+                if std::mem::discriminant(&child_col.data_type)
+                    != std::mem::discriminant(&parent_col.data_type)
+                {
+                    return Err(Error::catalog(
+                        CatalogKind::InvalidDefinition,
+                        format!(
+                            "Foreign key column {} ({:?}) doesn't match {}.{} ({:?})",
+                            child_col.name,
+                            child_col.data_type,
+                            fk.foreign_table,
+                            parent_col.name,
+                            parent_col.data_type
+                        ),
+                    ));
+                }
+                for (event, action) in [("DELETE", fk.on_delete), ("UPDATE", fk.on_update)] {
+                    if action == ReferentialAction::SetNull && !child_col.is_nullable {
+                        return Err(Error::catalog(
+                            CatalogKind::InvalidDefinition,
+                            format!(
+                                "ON {} SET NULL needs column {} to be nullable",
+                                event, child_col.name
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every foreign key that references `table_name`, with the table it's declared on.
+    pub(crate) fn referencing_tables(&self, table_name: &str) -> Vec<(String, ForeignKey)> {
+        let mut references = Vec::new();
+        for (name, schema) in &self.catalog.tables {
+            for fk in &schema.foreign_keys {
+                if fk.foreign_table == table_name {
+                    references.push((name.clone(), fk.clone()));
+                }
+            }
+        }
+        // HashMap order isn't stable, keep error messages and cascades deterministic
+        references.sort_by(|a, b| a.0.cmp(&b.0));
+        references
+    }
+
+    pub(crate) fn is_referenced_column(&self, table_name: &str, column: &str) -> bool {
+        self.referencing_tables(table_name)
+            .iter()
+            .any(|(_, fk)| fk.referred_columns.iter().any(|c| c == column))
+    }
+
+    /// Checks that every foreign key of `row` without NULLs points at an existing parent row.
+    pub(crate) fn check_references(&self, schema: &Schema, row: &Row) -> Result<()> {
+        for fk in &schema.foreign_keys {
+            let child_idx = schema.column_indices(&fk.columns)?;
+            let values: Vec<&Field> = child_idx.iter().map(|&i| &row.fields[i]).collect();
+            if values.contains(&&Field::Null) {
+                continue;
+            }
+
+            let parent = self.open_table(&fk.foreign_table)?;
+            let parent_idx = parent.schema.column_indices(&fk.referred_columns)?;
+
+            // A self-referencing row may point at itself
+            let points_at_itself = fk.foreign_table == schema.table_name
+                && parent_idx
+                    .iter()
+                    .map(|&i| &row.fields[i])
+                    .eq(values.iter().copied());
+
+            if !points_at_itself && parent.find_by_key(&parent_idx, &values).is_none() {
+                return Err(Error::constraint(
+                    ConstraintKind::ForeignKey,
+                    format!(
+                        "Foreign key violation: ({}) = ({}) has no matching row in {}",
+                        fk.columns.join(", "),
+                        format_values(&values),
+                        fk.foreign_table
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn find_referencing_rows(
+        &self,
+        child_name: &str,
+        fk: &ForeignKey,
+        values: &[&Field],
+    ) -> Result<Vec<(usize, usize, Row)>> {
+        let mut child = self.open_table(child_name)?;
+        let child_idx = child.schema.column_indices(&fk.columns)?;
+
+        Ok(child
+            .scan_locations()?
+            .into_iter()
+            .filter(|(_, _, row)| {
+                child_idx
+                    .iter()
+                    .zip(values)
+                    .all(|(&i, value)| row.fields[i] == **value)
+            })
+            .collect())
+    }
+
+    /// Deletes `targets` from `table_name` and applies the ON DELETE action of every
+    /// foreign key that references them. Nothing is written if a RESTRICT key blocks
+    /// the delete. Returns the number of rows removed from `table_name` itself.
+    pub(crate) fn delete_rows(
+        &mut self,
+        table_name: &str,
+        targets: Vec<(usize, usize, Row)>,
+    ) -> Result<usize> {
+        let count = targets.len();
+        let mut plan = DeletePlan::default();
+        self.plan_delete(table_name, targets, &mut plan)?;
+
+        for (name, rows) in &plan.set_null {
+            let rows = rows
+                .iter()
+                .filter(|(location, _)| !plan.is_deleted(name, **location))
+                .map(|(location, row)| (*location, row.clone()))
+                .collect();
+            self.open_table(name)?.update_rows(&rows)?;
+        }
+
+        for (name, rows) in &plan.deletes {
+            let mut table = self.open_table(name)?;
+            for &(p_idx, s_idx) in rows.keys() {
+                table.delete_row(p_idx, s_idx)?;
+            }
+        }
+
+        Ok(count)
+    }
+
+    fn plan_delete(
+        &self,
+        table_name: &str,
+        targets: Vec<(usize, usize, Row)>,
+        plan: &mut DeletePlan,
+    ) -> Result<()> {
+        // Mark every target first, so rows deleted together may reference each other
+        let planned = plan.deletes.entry(table_name.to_string()).or_default();
+        let mut new_rows = Vec::new();
+        for (p_idx, s_idx, row) in targets {
+            if planned.insert((p_idx, s_idx), row.clone()).is_none() {
+                new_rows.push(row);
+            }
+        }
+        if new_rows.is_empty() {
+            return Ok(());
+        }
+
+        let schema = self
+            .catalog
+            .tables
+            .get(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?;
+
+        for (child_name, fk) in self.referencing_tables(table_name) {
+            let parent_idx = schema.column_indices(&fk.referred_columns)?;
+
+            for row in &new_rows {
+                let values: Vec<&Field> = parent_idx.iter().map(|&i| &row.fields[i]).collect();
+                if values.contains(&&Field::Null) {
+                    continue;
+                }
+
+                let children: Vec<(usize, usize, Row)> = self
+                    .find_referencing_rows(&child_name, &fk, &values)?
+                    .into_iter()
+                    .filter(|(p_idx, s_idx, _)| !plan.is_deleted(&child_name, (*p_idx, *s_idx)))
+                    .collect();
+                if children.is_empty() {
+                    continue;
+                }
+
+                match fk.on_delete {
+                    ReferentialAction::Restrict => {
+                        return Err(Error::constraint(
+                            ConstraintKind::ForeignKey,
+                            format!(
+                                "Cannot delete from {}: key ({}) is still referenced by {}",
+                                table_name,
+                                format_values(&values),
+                                child_name
+                            ),
+                        ));
+                    }
+                    ReferentialAction::Cascade => self.plan_delete(&child_name, children, plan)?,
+                    ReferentialAction::SetNull => {
+                        let child_schema = self
+                            .catalog
+                            .tables
+                            .get(&child_name)
+                            .ok_or_else(|| Error::table_not_found(&child_name))?;
+                        let child_idx = child_schema.column_indices(&fk.columns)?;
+                        let updates = plan.set_null.entry(child_name.clone()).or_default();
+
+                        for (p_idx, s_idx, child_row) in children {
+                            let row = updates.entry((p_idx, s_idx)).or_insert(child_row);
+                            for &i in &child_idx {
+                                row.fields[i] = Field::Null;
+                            }
+                            validate_row(child_schema, row)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the new versions of `changes` (location, old row, new row) to `table_name`
+    /// and applies the ON UPDATE action of every foreign key whose referenced values
+    /// change. Nothing is written if a RESTRICT key blocks the update or the new rows
+    /// would break a primary key or UNIQUE constraint.
+    pub(crate) fn update_rows(
+        &mut self,
+        table_name: &str,
+        changes: Vec<(usize, usize, Row, Row)>,
+    ) -> Result<usize> {
+        let count = changes.len();
+        let mut plan = UpdatePlan::default();
+        self.plan_update(table_name, changes, &mut plan)?;
+
+        // Every table's keys are checked before any table is written
+        for (name, rows) in &plan.updates {
+            self.open_table(name)?.check_updates(rows)?;
+        }
+        for (name, rows) in &plan.updates {
+            self.open_table(name)?.update_rows(rows)?;
+        }
+
+        Ok(count)
+    }
+
+    fn plan_update(
+        &self,
+        table_name: &str,
+        changes: Vec<(usize, usize, Row, Row)>,
+        plan: &mut UpdatePlan,
+    ) -> Result<()> {
+        let planned = plan.updates.entry(table_name.to_string()).or_default();
+        for (p_idx, s_idx, _, new_row) in &changes {
+            planned.insert((*p_idx, *s_idx), new_row.clone());
+        }
+
+        let schema = self
+            .catalog
+            .tables
+            .get(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?;
+
+        for (child_name, fk) in self.referencing_tables(table_name) {
+            let parent_idx = schema.column_indices(&fk.referred_columns)?;
+
+            for (_, _, old_row, new_row) in &changes {
+                let old_values: Vec<&Field> =
+                    parent_idx.iter().map(|&i| &old_row.fields[i]).collect();
+                let new_values: Vec<&Field> =
+                    parent_idx.iter().map(|&i| &new_row.fields[i]).collect();
+                if old_values == new_values || old_values.contains(&&Field::Null) {
+                    continue;
+                }
+
+                let child_schema = self
+                    .catalog
+                    .tables
+                    .get(&child_name)
+                    .ok_or_else(|| Error::table_not_found(&child_name))?;
+                let child_idx = child_schema.column_indices(&fk.columns)?;
+
+                let mut child_changes = Vec::new();
+                for (p_idx, s_idx, disk_row) in
+                    self.find_referencing_rows(&child_name, &fk, &old_values)?
+                {
+                    // Build on the planned version if this update already changed the row
+                    let current = plan
+                        .updates
+                        .get(&child_name)
+                        .and_then(|rows| rows.get(&(p_idx, s_idx)))
+                        .cloned()
+                        .unwrap_or(disk_row);
+                    if !child_idx
+                        .iter()
+                        .zip(&old_values)
+                        .all(|(&i, value)| current.fields[i] == **value)
+                    {
+                        continue;
+                    }
+
+                    let mut row = current.clone();
+                    match fk.on_update {
+                        ReferentialAction::Restrict => {
+                            return Err(Error::constraint(
+                                ConstraintKind::ForeignKey,
+                                format!(
+                                    "Cannot update {}: key ({}) is still referenced by {}",
+                                    table_name,
+                                    format_values(&old_values),
+                                    child_name
+                                ),
+                            ));
+                        }
+                        ReferentialAction::Cascade => {
+                            for (&i, value) in child_idx.iter().zip(&new_values) {
+                                row.fields[i] = (*value).clone();
+                            }
+                        }
+                        ReferentialAction::SetNull => {
+                            for &i in &child_idx {
+                                row.fields[i] = Field::Null;
+                            }
+                        }
+                    }
+                    validate_row(child_schema, &row)?;
+                    child_changes.push((p_idx, s_idx, current, row));
+                }
+
+                if !child_changes.is_empty() {
+                    self.plan_update(&child_name, child_changes, plan)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follows a column rename into every constraint that mentions it, including
+    /// foreign keys declared on other tables.
+    pub(crate) fn rename_constraint_column(
+        &mut self,
+        table_name: &str,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<()> {
+        let rename = |columns: &mut Vec<String>| {
+            for column in columns.iter_mut() {
+                if column == old_name {
+                    *column = new_name.to_string();
+                }
+            }
+        };
+
+        for (name, schema) in self.catalog.tables.iter_mut() {
+            if name == table_name {
+                schema.unique.iter_mut().for_each(rename);
+                for check in &mut schema.checks {
+                    check.rename_column(old_name, new_name);
+                }
+                for fk in &mut schema.foreign_keys {
+                    rename(&mut fk.columns);
+                }
+            }
+            for fk in &mut schema.foreign_keys {
+                if fk.foreign_table == table_name {
+                    rename(&mut fk.referred_columns);
+                }
+            }
+        }
+        self.catalog.save()
+    }
+
+    pub(crate) fn rename_referenced_table(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        for schema in self.catalog.tables.values_mut() {
+            for fk in &mut schema.foreign_keys {
+                if fk.foreign_table == old_name {
+                    fk.foreign_table = new_name.to_string();
+                }
+            }
+        }
+        self.catalog.save()
+    }
+}
@@ -0,0 +1,68 @@
+use std::io;
+use std::path::Path;
+
+use crate::catalog::Catalog;
+use crate::engine::Database;
+use crate::error::{Error, Result};
+use crate::storage::Table;
+use crate::storage::pager::{self, Pager};
+use crate::upgrade::headerless_rows;
+
+impl Database {
+    /// Copies the database in `data_dir` into a new single-file database at `file_path`.
+    /// Pages are copied as they are, so rows keep their page and slot; the rows of files
+    /// from before the file header are read with their old layout and inserted instead.
+    /// The directory database is only read, never written; `file_path` must not exist yet.
+    pub fn convert_to_single_file(data_dir: &str, file_path: &str) -> Result<()> {
+        if !Path::new(data_dir).is_dir() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a database directory", data_dir),
+            )));
+        }
+        if Path::new(file_path).exists() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", file_path),
+            )));
+        }
+        let catalog = Catalog::read_directory(data_dir)?;
+        let mut target = Database::open_single_file(file_path)?;
+
+        let mut names: Vec<&String> = catalog.tables.keys().collect();
+        names.sort();
+        for name in names {
+            let schema = &catalog.tables[name];
+            let path = format!("{}/{}.db", data_dir, name);
+            let mut to = target.open_table_pager(name, schema)?;
+            // A table that was never written to has no file, and stays empty
+            if !Path::new(&path).exists() {
+                continue;
+            }
+            if let Some(pages) = pager::read_headerless(&path)? {
+                let mut table = Table::new(to, schema.clone());
+                for row in headerless_rows(&pages, schema)? {
+                    table.insert_row(row)?;
+                }
+                continue;
+            }
+
+            let mut from = Pager::open_read_only(&path)?;
+            let fingerprint = from.header().schema_fingerprint;
+            if fingerprint != 0 && fingerprint != schema.fingerprint() {
+                return Err(Error::Corruption(format!(
+                    "{} was written for a different layout of table {}",
+                    path, schema.table_name
+                )));
+            }
+            for page_index in 0..from.num_pages() {
+                to.write_page(page_index, &*from.read_page(page_index)?)?;
+            }
+        }
+
+        target.catalog.tables = catalog.tables;
+        target.catalog.sequences = catalog.sequences;
+        target.catalog.save()?;
+        target.flush(true)
+    }
+}
@@ -1,383 +1,1360 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
 
 use crate::catalog::Catalog;
-use crate::catalog::schema::DataType;
-use crate::index::PrimaryIndex;
-use crate::sql::{Command, QueryResponse, QueryResult};
+use crate::catalog::schema::{Column, Schema};
+use crate::catalog::system;
+use crate::constraints::{check_row_size, validate_row};
+use crate::error::{CatalogKind, ConstraintKind, Error, Result};
+use crate::query::{AccessPath, find_rows};
+use crate::sql::{
+    AlterTableOp, Command, ConflictAction, OnConflict, QueryResponse, QueryResult, SelectItem,
+};
 use crate::storage::Table;
-use crate::storage::pager::{HEADER_SIZE, PAGE_SIZE, Pager};
+use crate::storage::buffer_pool::{self, BufferPool, DEFAULT_CAPACITY, PoolStats, SharedPool};
+use crate::storage::pager::Pager;
 use crate::storage::record::{Field, Row};
+use crate::storage::single_file::{self, SharedFile, SingleFile};
 
 pub struct Database {
     pub catalog: Catalog,
+    // The directory of the database, or its file if it is a single-file one
     pub data_dir: String,
+    // Page cache shared by every table
+    pool: SharedPool,
+    // Set if every table and the catalog live in one file instead of a directory
+    single_file: Option<SharedFile>,
+    synchronous: Synchronous,
+    // Open tables with their indexes loaded, kept between statements.
+    // Emptied by DDL that changes or removes a table's file or schema.
+    tables: RefCell<HashMap<String, Table>>,
 }
 
-impl Database {
-    pub fn open(data_dir: &str) -> Self {
-        let catalog_path = format!("{}/catalog.json", data_dir);
+/// A table taken out of the registry by `open_table`. It goes back when dropped.
+pub(crate) struct TableHandle<'a> {
+    registry: &'a RefCell<HashMap<String, Table>>,
+    table: Option<Table>,
+}
+
+impl Deref for TableHandle<'_> {
+    type Target = Table;
+
+    fn deref(&self) -> &Table {
+        self.table.as_ref().expect("table is present until drop")
+    }
+}
+
+impl DerefMut for TableHandle<'_> {
+    fn deref_mut(&mut self) -> &mut Table {
+        self.table.as_mut().expect("table is present until drop")
+    }
+}
+
+impl Drop for TableHandle<'_> {
+    fn drop(&mut self) {
+        let Some(table) = self.table.take() else {
+            return;
+        };
+        let mut registry = self.registry.borrow_mut();
+        let name = table.schema.table_name.clone();
+        // Another handle to the same table was opened meanwhile and already put back.
+        // Either copy's indexes may have missed the other's writes, so keep neither.
+        if registry.remove(&name).is_none() {
+            registry.insert(name, table);
+        }
+    }
+}
+
+/// How hard a statement tries to get its changes onto disk before it returns,
+/// as in SQLite's `PRAGMA synchronous`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Synchronous {
+    // Changed pages stay in the buffer pool until evicted or the database is closed.
+    // A crash of the process may lose recent statements.
+    Off,
+    // Changed pages are handed to the OS after each statement, but not synced.
+    // A crash of the process is safe; a power loss may lose recent statements.
+    Normal,
+    // Changed pages are written and synced after each statement
+    Full,
+}
 
-        if let Err(e) = fs::create_dir_all(data_dir) {
-            panic!("Failed to create data directory {}: {}", data_dir, e);
+impl Synchronous {
+    /// Parses a setting by name or by SQLite's number for it.
+    pub fn parse(value: &str) -> Option<Synchronous> {
+        match value.to_uppercase().as_str() {
+            "OFF" | "0" => Some(Synchronous::Off),
+            "NORMAL" | "1" => Some(Synchronous::Normal),
+            "FULL" | "2" => Some(Synchronous::Full),
+            _ => None,
         }
+    }
+}
 
-        Self {
-            catalog: Catalog::load_or_create(&catalog_path),
+impl fmt::Display for Synchronous {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Synchronous::Off => write!(f, "OFF"),
+            Synchronous::Normal => write!(f, "NORMAL"),
+            Synchronous::Full => write!(f, "FULL"),
+        }
+    }
+}
+
+impl Database {
+    /// Opens the database in `data_dir`, or the single-file database at that path
+    /// if it names a file.
+    pub fn open(data_dir: &str) -> Result<Self> {
+        if Path::new(data_dir).is_file() {
+            return Self::open_single_file(data_dir);
+        }
+        Self::open_with_pool_size(data_dir, DEFAULT_CAPACITY)
+    }
+
+    /// Opens the single-file database at `path`, creating it if it doesn't exist.
+    pub fn open_single_file(path: &str) -> Result<Self> {
+        let pool = BufferPool::shared(DEFAULT_CAPACITY);
+        let file = SingleFile::open(path, pool.clone())?;
+
+        Ok(Self {
+            catalog: Catalog::in_single_file(&file)?,
+            data_dir: path.to_string(),
+            pool,
+            single_file: Some(file),
+            synchronous: Synchronous::Full,
+            tables: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Opens a database whose buffer pool holds `pages` pages.
+    pub fn open_with_pool_size(data_dir: &str, pages: usize) -> Result<Self> {
+        fs::create_dir_all(data_dir)?;
+
+        Ok(Self {
+            catalog: Catalog::open_directory(data_dir)?,
             data_dir: data_dir.to_string(),
+            pool: BufferPool::shared(pages),
+            single_file: None,
+            synchronous: Synchronous::Full,
+            tables: RefCell::new(HashMap::new()),
+        })
+    }
+
+    pub fn synchronous(&self) -> Synchronous {
+        self.synchronous
+    }
+
+    pub fn set_synchronous(&mut self, synchronous: Synchronous) {
+        self.synchronous = synchronous;
+    }
+
+    /// The single-file database this is, if it is one.
+    pub fn single_file(&self) -> Option<&SharedFile> {
+        self.single_file.as_ref()
+    }
+
+    /// Writes every dirty page in the buffer pool to its file, and for a single-file
+    /// database the directory if it changed, syncing each file written if `sync` is set.
+    pub(crate) fn flush(&self, sync: bool) -> Result<()> {
+        match &self.single_file {
+            Some(file) => single_file::lock(file).commit(sync),
+            None => buffer_pool::lock(&self.pool).flush_all(sync),
         }
     }
 
-    pub fn execute(&mut self, command: Command) -> Result<QueryResult, String> {
+    /// Hit, miss, eviction and write-back counts of the buffer pool.
+    pub fn buffer_pool_stats(&self) -> PoolStats {
+        buffer_pool::lock(&self.pool).stats()
+    }
+
+    /// Executes one statement, then flushes the pages it changed as the
+    /// `synchronous` setting asks.
+    pub fn execute(&mut self, command: Command) -> Result<QueryResult> {
+        let result = self.execute_command(command);
+        // Flush even after an error, as the statement may have written part of its rows
+        let flushed = match self.synchronous {
+            Synchronous::Off => Ok(()),
+            Synchronous::Normal => self.flush(false),
+            Synchronous::Full => self.flush(true),
+        };
+        let result = result?;
+        flushed?;
+        Ok(result)
+    }
+
+    fn execute_command(&mut self, command: Command) -> Result<QueryResult> {
+        // The system tables are written by the catalog alone, so statements may only read them
+        if let Some(name) = command
+            .written_tables()
+            .into_iter()
+            .find(|name| system::is_system_table(name))
+        {
+            return Err(Error::catalog(
+                CatalogKind::WrongObjectType,
+                format!("{} is a read-only system table", name),
+            ));
+        }
+
         match command {
-            Command::CreateTable { name, columns } => {
-                let schema = crate::catalog::schema::Schema {
+            Command::CreateTable {
+                name,
+                columns,
+                unique,
+                checks,
+                foreign_keys,
+                if_not_exists,
+            } => {
+                let mut schema = Schema {
                     table_name: name.clone(),
                     columns,
+                    unique,
+                    checks,
+                    foreign_keys,
                 };
 
                 let table = self.catalog.tables.get(&name);
 
-                if let Some(_) = table {
-                    return Err(format!("Table {} already exists", &name));
+                if table.is_some() && if_not_exists {
+                    return Ok(QueryResult::DdlOk {
+                        message: format!("Table {} already exists, skipped.", name),
+                        object: name,
+                    });
+                }
+                if table.is_some() {
+                    return Err(Error::catalog(
+                        CatalogKind::DuplicateTable,
+                        format!("Table {} already exists", &name),
+                    ));
                 }
 
-                self.catalog.add_table(schema);
-                Ok(QueryResult::Message(
-                    format!("Table {} created.", name).into(),
-                ))
+                self.validate_constraints(&mut schema)?;
+
+                // Create the file with its header. One left behind by a table of the same
+                // name that the catalog lost track of is replaced, not adopted.
+                self.remove_table_storage(&name)?;
+                self.open_table_pager(&name, &schema)?;
+
+                self.catalog.add_table(schema)?;
+                Ok(QueryResult::DdlOk {
+                    message: format!("Table {} created.", name),
+                    object: name,
+                })
             }
 
-            Command::Insert { table_name, row } => {
-                // 1. Get schema from catalog
-                let schema = self
-                    .catalog
-                    .tables
-                    .get(&table_name)
-                    .ok_or_else(|| format!("Table {} not found", table_name))?;
-
-                // 2. Open the table
-                let path = format!("{}/{}.db", self.data_dir, table_name);
-                let pager = Pager::open(&path).map_err(|e| e.to_string())?;
-                let mut table = Table {
-                    pager,
-                    schema: schema.clone(),
-                    index: PrimaryIndex::new(),
+            Command::Insert {
+                table_name,
+                columns,
+                rows,
+                on_conflict,
+                returning,
+            } => {
+                self.check_returning(&table_name, returning.as_deref())?;
+                // Rows skipped by ON CONFLICT DO NOTHING aren't counted or returned
+                let mut inserted = Vec::new();
+                for row in rows {
+                    inserted.extend(self.insert_fields(
+                        &table_name,
+                        &columns,
+                        row.fields,
+                        on_conflict.as_ref(),
+                    )?);
+                }
+
+                let last_insert_id = self.last_insert_id(&table_name, &inserted);
+                self.dml_result(&table_name, returning, inserted, last_insert_id)
+            }
+
+            Command::InsertSelect {
+                table_name,
+                columns,
+                query,
+                on_conflict,
+                returning,
+            } => {
+                self.check_returning(&table_name, returning.as_deref())?;
+                // Run the query to completion first, so a table can be filled from itself
+                let rows = match self.execute(*query)? {
+                    QueryResult::Data(resp) => resp.rows,
+                    _ => return Err(Error::parse("INSERT ... SELECT needs a query")),
                 };
 
-                // 3. Warm up index (So PK violation check works)
-                table.load_index().map_err(|e| e.to_string())?;
-                let prepared_row = self.validate_and_prepare_row(&table_name, row.fields)?;
+                let mut inserted = Vec::new();
+                for fields in rows {
+                    inserted.extend(self.insert_fields(
+                        &table_name,
+                        &columns,
+                        fields,
+                        on_conflict.as_ref(),
+                    )?);
+                }
 
-                // 4. Perform insert
-                table
-                    .insert_row(prepared_row.clone())
-                    .map_err(|e| e.to_string())?;
-                Ok(QueryResult::Message(
-                    format!("Inserted 1 row : {:?}", prepared_row).to_string(),
-                ))
+                let last_insert_id = self.last_insert_id(&table_name, &inserted);
+                self.dml_result(&table_name, returning, inserted, last_insert_id)
             }
 
-            Command::DropTable { table_name } => {
-                // 1. Remove from Catalog
-                if self.catalog.tables.remove(&table_name).is_none() {
-                    return Err(format!("Table {} not found", table_name));
-                }
-                self.catalog.sequences.remove(&table_name);
-                self.catalog.save();
+            Command::DropTable {
+                table_names,
+                if_exists,
+            } => self.drop_tables(&table_names, if_exists),
 
-                // 2. Delete the physical file
-                let path = format!("{}/{}.db", self.data_dir, table_name);
-                if std::path::Path::new(&path).exists() {
-                    std::fs::remove_file(path).map_err(|e| e.to_string())?;
-                }
+            Command::Truncate {
+                table_names,
+                restart_identity,
+            } => self.truncate_tables(&table_names, restart_identity),
 
-                Ok(QueryResult::Message(format!(
-                    "Table {} dropped.",
-                    table_name
-                )))
+            Command::AlterTable {
+                table_name,
+                operation,
+            } => {
+                let result = self.alter_table(&table_name, operation);
+                // Cached handles may have the old layout, name or file of this table, or
+                // the old foreign keys of the tables referencing it
+                self.tables.get_mut().clear();
+                result
             }
 
-            Command::Delete { table_name, filter } => {
+            Command::Pragma { name, value } => self.pragma(&name, value.as_deref()),
+
+            Command::ShowTables => {
+                let mut names: Vec<&String> = self.catalog.tables.keys().collect();
+                names.sort();
+                Ok(QueryResult::Data(QueryResponse {
+                    columns: vec!["table_name".to_string()],
+                    rows: names
+                        .into_iter()
+                        .map(|name| vec![Field::Text(name.clone())])
+                        .collect(),
+                }))
+            }
+
+            Command::Describe { table_name } => self.describe(&table_name),
+
+            Command::ShowCreateTable { table_name } => {
+                let schema = self
+                    .catalog
+                    .schema(&table_name)
+                    .ok_or_else(|| Error::table_not_found(&table_name))?;
+                Ok(QueryResult::Data(QueryResponse {
+                    columns: vec!["table_name".to_string(), "create_statement".to_string()],
+                    rows: vec![vec![
+                        Field::Text(table_name.clone()),
+                        Field::Text(schema.create_statement()),
+                    ]],
+                }))
+            }
+
+            Command::Vacuum { table_name } => {
+                let result = self.vacuum(table_name.as_deref());
+                // Rows have moved, so the cached indexes point at the wrong slots
+                self.tables.get_mut().clear();
+                result
+            }
+
+            Command::Delete {
+                table_name,
+                filter,
+                returning,
+            } => {
+                self.check_returning(&table_name, returning.as_deref())?;
                 let schema = self
                     .catalog
                     .tables
                     .get(&table_name)
-                    .ok_or("Table not found")?;
-                let path = format!("{}/{}.db", self.data_dir, table_name);
-                let pager = Pager::open(&path).map_err(|e| e.to_string())?;
-                let mut table = Table {
-                    pager,
-                    schema: schema.clone(),
-                    index: crate::index::PrimaryIndex::new(),
-                };
-                table.load_index().map_err(|e| e.to_string())?;
-
-                let mut deleted_count = 0;
-                let mut targets = Vec::new();
-
-                // TODO: Insert Index Optimization Logic
-                for p_idx in 0..table.pager.num_pages() {
-                    let page = table.pager.read_page(p_idx).map_err(|e| e.to_string())?;
-                    for s_idx in 0..(PAGE_SIZE - HEADER_SIZE) / schema.row_size() {
-                        if page.is_slot_full(s_idx) {
-                            let row = table.get_row(p_idx, s_idx).map_err(|e| e.to_string())?;
-                            if filter
-                                .as_ref()
-                                .map_or(true, |f| Row::row_matches_filter(&row, f, &schema))
-                            {
-                                targets.push((p_idx, s_idx, row));
-                            }
-                        }
-                    }
-                }
+                    .ok_or_else(|| Error::table_not_found(&table_name))?;
+                let mut table = self.open_table(&table_name)?;
 
-                // 2. Perform deletion
-                for (p_idx, s_idx, row) in targets {
-                    table.delete_row(p_idx, s_idx).map_err(|e| e.to_string())?;
+                let path = AccessPath::plan(schema, filter.as_ref());
+                let targets = find_rows(&mut table, &path, filter.as_ref())?;
 
-                    // IMPORTANT: Remove from Index
-                    if let Some(pk_idx) = schema.columns.iter().position(|c| c.is_primary) {
-                        let pk_val = format!("{:?}", row.fields[pk_idx]);
-                        table.index.map.remove(&pk_val);
-                    }
-                    deleted_count += 1;
-                }
+                // 2. Perform deletion, along with any ON DELETE actions of foreign keys
+                drop(table);
+                let deleted: Vec<Row> = targets.iter().map(|(_, _, row)| row.clone()).collect();
+                self.delete_rows(&table_name, targets)?;
 
-                Ok(QueryResult::Message(format!(
-                    "Deleted {} rows.",
-                    deleted_count
-                )))
+                self.dml_result(&table_name, returning, deleted, None)
             }
 
             Command::Update {
                 table_name,
                 assignments,
                 filter,
+                returning,
             } => {
+                self.check_returning(&table_name, returning.as_deref())?;
                 let schema = self
                     .catalog
                     .tables
                     .get(&table_name)
-                    .ok_or("Table not found")?;
-                let path = format!("{}/{}.db", self.data_dir, table_name);
-                let pager = Pager::open(&path).map_err(|e| e.to_string())?;
-                let mut table = Table {
-                    pager,
-                    schema: schema.clone(),
-                    index: crate::index::PrimaryIndex::new(),
-                };
-                table.load_index().map_err(|e| e.to_string())?;
-
-                let mut updated_count = 0;
+                    .ok_or_else(|| Error::table_not_found(&table_name))?
+                    .clone();
+                let mut table = self.open_table(&table_name)?;
 
                 // 1: Find which rows to update
-                let mut targets = Vec::new(); // Stores (page_idx, slot_idx, Row)
-
-                // TODO: Add Index Optimization Logic here if filter is PK = Val]
-                for p_idx in 0..table.pager.num_pages() {
-                    let page = table.pager.read_page(p_idx).map_err(|e| e.to_string())?;
-                    for s_idx in 0..(PAGE_SIZE - HEADER_SIZE) / schema.row_size() {
-                        if page.is_slot_full(s_idx) {
-                            let row = table.get_row(p_idx, s_idx).map_err(|e| e.to_string())?;
-                            if filter
-                                .as_ref()
-                                .map_or(true, |f| Row::row_matches_filter(&row, f, &schema))
-                            {
-                                targets.push((p_idx, s_idx, row));
-                            }
-                        }
-                    }
-                }
+                let path = AccessPath::plan(&schema, filter.as_ref());
+                let targets = find_rows(&mut table, &path, filter.as_ref())?;
+                drop(table);
 
-                // 2: Apply Updates and Write Back
-                for (p_idx, s_idx, mut row) in targets {
-                    for (col_name, new_val) in &assignments {
+                // 2: Build and validate every new row before anything is written
+                let mut changes = Vec::new();
+                for (p_idx, s_idx, old_row) in targets {
+                    let mut row = old_row.clone();
+                    // Every expression sees the row as it was before the update
+                    for (col_name, expr) in &assignments {
                         let col_idx = schema
-                            .columns
-                            .iter()
-                            .position(|c| &c.name == col_name)
-                            .ok_or(format!("Column {} not found", col_name))?;
+                            .column_index(col_name)
+                            .ok_or_else(|| Error::column_not_found(col_name))?;
+                        row.fields[col_idx] = expr.eval(&old_row, &schema)?;
+                    }
 
-                        // Check if user is trying to update a Primary Key prevent this
-                        if schema.columns[col_idx].is_primary {
-                            return Err("Updating Primary Key is not allowed".to_string());
-                        }
+                    validate_row(&schema, &row)?;
+                    self.check_references(&schema, &row)?;
+                    changes.push((p_idx, s_idx, old_row, row));
+                }
 
-                        row.fields[col_idx] = new_val.clone();
+                // Ids set by hand must not be handed out again by AUTOINCREMENT
+                if let Some(auto_idx) = schema.columns.iter().position(|c| c.is_autoincrement) {
+                    for (_, _, _, row) in &changes {
+                        if let Field::Integer(id) = row.fields[auto_idx] {
+                            self.catalog.advance_sequence(&table_name, id)?;
+                        }
                     }
-
-                    table
-                        .update_row(p_idx, s_idx, row)
-                        .map_err(|e| e.to_string())?;
-                    updated_count += 1;
                 }
 
-                Ok(QueryResult::Message(format!(
-                    "Updated {} rows.",
-                    updated_count
-                )))
+                // 3: Write back, along with any ON UPDATE actions of foreign keys
+                let updated: Vec<Row> = changes.iter().map(|(_, _, _, row)| row.clone()).collect();
+                self.update_rows(&table_name, changes)?;
+
+                self.dml_result(&table_name, returning, updated, None)
             }
 
             Command::Select {
                 table_name,
+                projection,
                 join,
                 filter,
             } => {
                 let schema = self
                     .catalog
-                    .tables
-                    .get(&table_name)
-                    .ok_or_else(|| format!("Table {} not found", table_name))?;
-                let path = format!("{}/{}.db", self.data_dir, table_name);
-                let pager = Pager::open(&path).map_err(|e| e.to_string())?;
-                let mut table = Table {
-                    pager,
-                    schema: schema.clone(),
-                    index: crate::index::PrimaryIndex::new(),
-                };
-                table.load_index().map_err(|e| e.to_string())?;
-
-                let mut final_rows = Vec::new();
-                let mut used_index = false;
-                let mut merged_columns;
-
-                // Check for optimization (fast path with index)
-                if let (None, Some(f)) = (&join, &filter) {
-                    let pk_col = schema.columns.iter().find(|c| c.is_primary);
-
-                    if let Some(pk) = pk_col {
-                        if f.column_name == pk.name
-                            && matches!(f.operator, crate::sql::Operator::Eq)
-                        {
-                            // Convert filter value to string key for index lookup
-                            let key = match &f.value {
-                                Field::Integer(v) => v.to_string(),
-                                Field::Text(v) => v.clone(),
-                                Field::Boolean(v) => v.to_string(),
-                            };
-
-                            // Look up in B-Tree
-                            if let Some((p_idx, s_idx)) = table.index.map.get(&key) {
-                                let row =
-                                    table.get_row(*p_idx, *s_idx).map_err(|e| e.to_string())?;
-                                final_rows.push(row);
-                            }
-                            used_index = true;
-                            merged_columns = schema.columns.clone();
-                        } else {
-                            merged_columns = schema.columns.clone();
-                        }
-                    } else {
-                        merged_columns = schema.columns.clone();
-                    }
-                } else {
-                    merged_columns = schema.columns.clone();
-                }
+                    .schema(&table_name)
+                    .ok_or_else(|| Error::table_not_found(&table_name))?;
 
-                // Slow path (fallback if not optimized)
-                if !used_index {
-                    let mut rows = table.scan_rows().map_err(|e| e.to_string())?;
+                let final_rows;
+                let merged_columns;
+                // What `*` is expanded to in the header
+                let wildcard: Vec<String>;
 
-                    // Apply filter if present
-                    if let Some(f) = filter {
-                        rows.retain(|r| Row::row_matches_filter(r, &f, &schema));
-                    }
+                if let Some(join_info) = join {
+                    let rows = self.scan_table(&table_name)?;
 
-                    // Handle join if present
-                    if let Some(join_info) = join {
-                        // Get right table schema and rows
-                        let right_schema = self
-                            .catalog
-                            .tables
-                            .get(&join_info.right_table)
-                            .ok_or_else(|| format!("Table {} not found", join_info.right_table))?;
-                        let right_path = format!("{}/{}.db", self.data_dir, join_info.right_table);
-                        let right_pager = Pager::open(&right_path).map_err(|e| e.to_string())?;
-                        let mut right_table = Table {
-                            pager: right_pager,
-                            schema: right_schema.clone(),
-                            index: crate::index::PrimaryIndex::new(),
-                        };
-                        let right_rows = right_table.scan_rows().map_err(|e| e.to_string())?;
+                    // Get right table schema and rows
+                    let right_schema = self
+                        .catalog
+                        .schema(&join_info.right_table)
+                        .ok_or_else(|| Error::table_not_found(&join_info.right_table))?;
+                    let right_rows = self.scan_table(&join_info.right_table)?;
 
-                        // Find column indexes
-                        let left_col_idx = schema
-                            .columns
-                            .iter()
-                            .position(|c| c.name == join_info.left_column)
-                            .ok_or_else(|| {
+                    // Find column indexes
+                    let left_col_idx = schema
+                        .columns
+                        .iter()
+                        .position(|c| c.name == join_info.left_column)
+                        .ok_or_else(|| {
+                            Error::catalog(
+                                CatalogKind::UndefinedColumn,
                                 format!(
                                     "Column {} not found in table {}",
                                     join_info.left_column, table_name
-                                )
-                            })?;
+                                ),
+                            )
+                        })?;
 
-                        let right_col_idx = right_schema
-                            .columns
-                            .iter()
-                            .position(|c| c.name == join_info.right_column)
-                            .ok_or_else(|| {
+                    let right_col_idx = right_schema
+                        .columns
+                        .iter()
+                        .position(|c| c.name == join_info.right_column)
+                        .ok_or_else(|| {
+                            Error::catalog(
+                                CatalogKind::UndefinedColumn,
                                 format!(
                                     "Column {} not found in table {}",
                                     join_info.right_column, join_info.right_table
-                                )
-                            })?;
-
-                        // Perform join
-                        for row_a in &rows {
-                            for row_b in &right_rows {
-                                if row_a.fields[left_col_idx] == row_b.fields[right_col_idx] {
-                                    // Merge rows
-                                    let mut merged_fields = row_a.fields.clone();
-                                    merged_fields.extend(row_b.fields.clone());
-                                    final_rows.push(Row {
-                                        fields: merged_fields,
-                                    });
+                                ),
+                            )
+                        })?;
+
+                    // The WHERE clause may refer to columns of either table, qualified
+                    // with the table name when both have a column of that name
+                    let mut merged_schema = schema.clone();
+                    merged_schema.columns.extend(right_schema.columns.clone());
+                    let tables = std::iter::repeat_n(&table_name, schema.columns.len())
+                        .chain(std::iter::repeat(&join_info.right_table));
+                    let in_both = |name: &str| {
+                        [schema, right_schema]
+                            .iter()
+                            .all(|s| s.columns.iter().any(|c| c.name == name))
+                    };
+                    // Only names both tables have are qualified in the header
+                    wildcard = merged_schema
+                        .columns
+                        .iter()
+                        .zip(tables.clone())
+                        .map(|(column, table)| match in_both(&column.name) {
+                            true => format!("{}.{}", table, column.name),
+                            false => column.name.clone(),
+                        })
+                        .collect();
+                    for (column, table) in merged_schema.columns.iter_mut().zip(tables) {
+                        column.name = format!("{}.{}", table, column.name);
+                    }
+
+                    // Perform join
+                    let mut joined = Vec::new();
+                    for row_a in &rows {
+                        for row_b in &right_rows {
+                            if row_a.fields[left_col_idx] == row_b.fields[right_col_idx] {
+                                // Merge rows
+                                let mut merged_fields = row_a.fields.clone();
+                                merged_fields.extend(row_b.fields.clone());
+                                let row = Row {
+                                    fields: merged_fields,
+                                };
+                                let keep = match &filter {
+                                    Some(f) => f.matches(&row, &merged_schema)?,
+                                    None => true,
+                                };
+                                if keep {
+                                    joined.push(row);
                                 }
                             }
                         }
+                    }
 
-                        merged_columns = schema.columns.clone();
-                        merged_columns.extend(right_schema.columns.clone());
-                    } else {
-                        final_rows = rows;
-                        merged_columns = schema.columns.clone();
+                    final_rows = joined;
+                    merged_columns = merged_schema.columns;
+                } else if system::is_system_table(&table_name) {
+                    // Read through the catalog for every query, so there are no indexes to use
+                    let mut rows = Vec::new();
+                    for row in self.catalog.system_rows(&table_name)? {
+                        let keep = match &filter {
+                            Some(f) => f.matches(&row, schema)?,
+                            None => true,
+                        };
+                        if keep {
+                            rows.push(row);
+                        }
                     }
+                    final_rows = rows;
+                    merged_columns = schema.columns.clone();
+                    wildcard = schema.column_names();
+                } else {
+                    let mut table = self.open_table(&table_name)?;
+                    let path = AccessPath::plan(schema, filter.as_ref());
+                    final_rows = find_rows(&mut table, &path, filter.as_ref())?
+                        .into_iter()
+                        .map(|(_, _, row)| row)
+                        .collect();
+                    merged_columns = schema.columns.clone();
+                    wildcard = schema.column_names();
                 }
 
-                if final_rows.is_empty() {
-                    return Ok(QueryResult::Message("No rows found.".to_string()));
-                }
+                let merged_schema = Schema {
+                    table_name: table_name.clone(),
+                    columns: merged_columns,
+                    unique: Vec::new(),
+                    checks: Vec::new(),
+                    foreign_keys: Vec::new(),
+                };
+                Ok(QueryResult::Data(project(
+                    &merged_schema,
+                    &wildcard,
+                    &projection,
+                    final_rows,
+                )?))
+            }
+        }
+    }
 
-                if used_index {
-                    println!("(Optimization used: Primary Key Index Lookup)");
+    fn pragma(&mut self, name: &str, value: Option<&str>) -> Result<QueryResult> {
+        match name.to_lowercase().as_str() {
+            "synchronous" => {
+                if let Some(value) = value {
+                    self.synchronous = Synchronous::parse(value).ok_or_else(|| {
+                        Error::Data(format!(
+                            "Invalid synchronous setting {}: expected OFF, NORMAL or FULL",
+                            value
+                        ))
+                    })?;
+                }
+                Ok(QueryResult::Data(QueryResponse {
+                    columns: vec!["synchronous".to_string()],
+                    rows: vec![vec![Field::Text(self.synchronous.to_string())]],
+                }))
+            }
+            "integrity_check" => {
+                let mut problems = self.integrity_check()?;
+                if problems.is_empty() {
+                    problems.push("ok".to_string());
                 }
-
                 Ok(QueryResult::Data(QueryResponse {
-                    columns: merged_columns.iter().map(|c| c.name.clone()).collect(),
-                    rows: final_rows.into_iter().map(|r| r.fields).collect(),
+                    columns: vec!["integrity_check".to_string()],
+                    rows: problems.into_iter().map(|p| vec![Field::Text(p)]).collect(),
                 }))
             }
+            _ => Err(Error::Unsupported(format!("Unknown PRAGMA {}", name))),
+        }
+    }
+
+    /// Rewrites a table, or every table, into a new file holding its rows in as few pages
+    /// as they need. The old file is only replaced once the new one is complete.
+    fn vacuum(&mut self, table_name: Option<&str>) -> Result<QueryResult> {
+        let mut names: Vec<String> = match table_name {
+            Some(name) if self.catalog.tables.contains_key(name) => vec![name.to_string()],
+            Some(name) => return Err(Error::table_not_found(name)),
+            None => self.catalog.tables.keys().cloned().collect(),
+        };
+        names.sort();
+
+        let mut rows = Vec::new();
+        for name in names {
+            let schema = self
+                .catalog
+                .tables
+                .get(&name)
+                .ok_or_else(|| Error::table_not_found(&name))?
+                .clone();
+            let pages_before = self.open_pager(&name)?.num_pages();
+            self.rewrite_table(&schema, schema.clone(), |row| row)?;
+            let pages_after = self.open_pager(&name)?.num_pages();
+
+            rows.push(vec![
+                Field::Text(name),
+                Field::Integer(pages_before as i32),
+                Field::Integer(pages_after as i32),
+            ]);
+        }
+
+        Ok(QueryResult::Data(QueryResponse {
+            columns: vec![
+                "table".to_string(),
+                "pages_before".to_string(),
+                "pages_after".to_string(),
+            ],
+            rows,
+        }))
+    }
+
+    /// Drops every table in `names`, skipping missing ones if `if_exists` is set. Nothing
+    /// is dropped unless all of them can be, and tables may reference each other.
+    fn drop_tables(&mut self, names: &[String], if_exists: bool) -> Result<QueryResult> {
+        let mut dropped: Vec<&String> = Vec::new();
+        let mut skipped = Vec::new();
+        for name in names {
+            if !self.catalog.tables.contains_key(name) {
+                if !if_exists {
+                    return Err(Error::table_not_found(name));
+                }
+                skipped.push(name);
+            } else if !dropped.contains(&name) {
+                dropped.push(name);
+            }
+        }
+        for &name in &dropped {
+            if let Some((child, _)) = self
+                .referencing_tables(name)
+                .into_iter()
+                .find(|(child, _)| !dropped.contains(&child))
+            {
+                return Err(Error::catalog(
+                    CatalogKind::DependentObjects,
+                    format!(
+                        "Cannot drop table {}: it is referenced by a foreign key on {}",
+                        name, child
+                    ),
+                ));
+            }
+        }
+
+        // 1. Remove from Catalog
+        for &name in &dropped {
+            self.tables.get_mut().remove(name);
+            self.catalog.tables.remove(name);
+            self.catalog.sequences.remove(name);
+        }
+        self.catalog.save()?;
+
+        // 2. Delete the physical files
+        for &name in &dropped {
+            self.remove_table_storage(name)?;
+        }
+
+        let mut messages = Vec::new();
+        match dropped.as_slice() {
+            [] => {}
+            [name] => messages.push(format!("Table {} dropped.", name)),
+            names => {
+                let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+                messages.push(format!("Tables {} dropped.", names.join(", ")));
+            }
         }
+        for name in &skipped {
+            messages.push(format!("Table {} does not exist, skipped.", name));
+        }
+        let objects: Vec<&str> = dropped
+            .iter()
+            .chain(&skipped)
+            .map(|name| name.as_str())
+            .collect();
+        Ok(QueryResult::DdlOk {
+            object: objects.join(", "),
+            message: messages.join(" "),
+        })
+    }
+
+    /// Empties every table in `names` by replacing its storage with an empty one and
+    /// forgetting its indexes, without reading a row. A table referenced by a foreign key
+    /// can only be truncated together with the tables referencing it.
+    fn truncate_tables(&mut self, names: &[String], restart_identity: bool) -> Result<QueryResult> {
+        let mut schemas = Vec::new();
+        for name in names {
+            let schema = self
+                .catalog
+                .tables
+                .get(name)
+                .ok_or_else(|| Error::table_not_found(name))?;
+            if let Some((child, _)) = self
+                .referencing_tables(name)
+                .into_iter()
+                .find(|(child, _)| !names.contains(child))
+            {
+                return Err(Error::catalog(
+                    CatalogKind::DependentObjects,
+                    format!(
+                        "Cannot truncate table {}: it is referenced by a foreign key on {}",
+                        name, child
+                    ),
+                ));
+            }
+            schemas.push(schema.clone());
+        }
+
+        for schema in &schemas {
+            let name = &schema.table_name;
+            self.tables.get_mut().remove(name);
+            self.remove_table_storage(name)?;
+            self.open_table_pager(name, schema)?;
+            if restart_identity {
+                self.catalog.reset_sequence(name)?;
+            }
+        }
+
+        let message = match names {
+            [name] => format!("Table {} truncated.", name),
+            names => format!("Tables {} truncated.", names.join(", ")),
+        };
+        Ok(QueryResult::DdlOk {
+            object: names.join(", "),
+            message,
+        })
+    }
+
+    /// One row per column of a table: what information_schema.columns has for it,
+    /// less the columns that are the same for every row or follow from the order.
+    fn describe(&self, table_name: &str) -> Result<QueryResult> {
+        let schema = self
+            .catalog
+            .schema(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?;
+        let keep = |name: &str| name != "table_name" && name != "ordinal_position";
+        let columns: Vec<&str> = self
+            .catalog
+            .schema(system::COLUMNS)
+            .map(|s| s.columns.iter().map(|c| c.name.as_str()).collect())
+            .unwrap_or_default();
+
+        Ok(QueryResult::Data(QueryResponse {
+            columns: columns
+                .iter()
+                .filter(|name| keep(name))
+                .map(|name| name.to_string())
+                .collect(),
+            rows: system::column_rows(schema)
+                .into_iter()
+                .map(|row| {
+                    row.fields
+                        .into_iter()
+                        .zip(&columns)
+                        .filter(|(_, name)| keep(name))
+                        .map(|(field, _)| field)
+                        .collect()
+                })
+                .collect(),
+        }))
+    }
+
+    fn alter_table(&mut self, table_name: &str, operation: AlterTableOp) -> Result<QueryResult> {
+        let schema = self
+            .catalog
+            .tables
+            .get(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?
+            .clone();
+
+        match operation {
+            AlterTableOp::AddColumn { column } => {
+                if schema.column_index(&column.name).is_some() {
+                    return Err(Error::catalog(
+                        CatalogKind::DuplicateColumn,
+                        format!(
+                            "Column {} already exists in table {}",
+                            column.name, table_name
+                        ),
+                    ));
+                }
+                if column.is_primary || column.is_autoincrement {
+                    return Err(Error::Unsupported(
+                        "Cannot add a PRIMARY KEY or AUTOINCREMENT column to an existing table"
+                            .to_string(),
+                    ));
+                }
+                // Existing rows need a value for the new column
+                let default = match &column.default {
+                    Some(value) => value.clone(),
+                    None if column.is_nullable => Field::Null,
+                    None => {
+                        return Err(Error::constraint(
+                            ConstraintKind::NotNull,
+                            format!(
+                                "Column {} is NOT NULL, so it needs a DEFAULT value to be added",
+                                column.name
+                            ),
+                        ));
+                    }
+                };
+
+                let column_name = column.name.clone();
+                let mut new_schema = schema.clone();
+                new_schema.columns.push(column);
+                check_row_size(&new_schema)?;
+                self.rewrite_table(&schema, new_schema, |mut row| {
+                    row.fields.push(default.clone());
+                    row
+                })?;
+
+                Ok(QueryResult::DdlOk {
+                    object: table_name.to_string(),
+                    message: format!("Column {} added to table {}.", column_name, table_name),
+                })
+            }
+
+            AlterTableOp::DropColumn { column_name } => {
+                let col_idx = schema
+                    .column_index(&column_name)
+                    .ok_or_else(|| Error::column_not_found(&column_name))?;
+
+                if schema.columns[col_idx].is_primary {
+                    return Err(Error::catalog(
+                        CatalogKind::InvalidDefinition,
+                        "Dropping a Primary Key column is not allowed",
+                    ));
+                }
+                if schema.constrains_column(&column_name)
+                    || self.is_referenced_column(table_name, &column_name)
+                {
+                    return Err(Error::catalog(
+                        CatalogKind::DependentObjects,
+                        format!(
+                            "Cannot drop column {}: it is used by a constraint",
+                            column_name
+                        ),
+                    ));
+                }
+                if schema.columns.len() == 1 {
+                    return Err(Error::catalog(
+                        CatalogKind::InvalidDefinition,
+                        format!(
+                            "Cannot drop {}: table {} must keep at least one column",
+                            column_name, table_name
+                        ),
+                    ));
+                }
+
+                let mut new_schema = schema.clone();
+                new_schema.columns.remove(col_idx);
+                self.rewrite_table(&schema, new_schema, |mut row| {
+                    row.fields.remove(col_idx);
+                    row
+                })?;
+
+                Ok(QueryResult::DdlOk {
+                    object: table_name.to_string(),
+                    message: format!("Column {} dropped from table {}.", column_name, table_name),
+                })
+            }
+
+            AlterTableOp::RenameColumn { old_name, new_name } => {
+                let col_idx = schema
+                    .column_index(&old_name)
+                    .ok_or_else(|| Error::column_not_found(&old_name))?;
+                if schema.column_index(&new_name).is_some() {
+                    return Err(Error::catalog(
+                        CatalogKind::DuplicateColumn,
+                        format!("Column {} already exists in table {}", new_name, table_name),
+                    ));
+                }
+
+                // The on-disk layout doesn't depend on column names
+                let mut new_schema = schema;
+                new_schema.columns[col_idx].name = new_name.clone();
+                self.catalog.update_table(new_schema)?;
+                self.rename_constraint_column(table_name, &old_name, &new_name)?;
+
+                Ok(QueryResult::DdlOk {
+                    object: table_name.to_string(),
+                    message: format!("Column {} renamed to {}.", old_name, new_name),
+                })
+            }
+
+            AlterTableOp::RenameTable { new_name } => {
+                if self.catalog.tables.contains_key(&new_name) {
+                    return Err(Error::catalog(
+                        CatalogKind::DuplicateTable,
+                        format!("Table {} already exists", new_name),
+                    ));
+                }
+
+                self.rename_table_storage(table_name, &new_name)?;
+                self.catalog.rename_table(table_name, &new_name)?;
+                self.rename_referenced_table(table_name, &new_name)?;
+
+                Ok(QueryResult::DdlOk {
+                    object: new_name.clone(),
+                    message: format!("Table {} renamed to {}.", table_name, new_name),
+                })
+            }
+
+            AlterTableOp::AlterColumnType {
+                column_name,
+                data_type,
+            } => {
+                let col_idx = schema
+                    .column_index(&column_name)
+                    .ok_or_else(|| Error::column_not_found(&column_name))?;
+                let current = &schema.columns[col_idx].data_type;
+
+                let changes_kind =
+                    std::mem::discriminant(current) != std::mem::discriminant(&data_type);
+                if changes_kind
+                    && (schema.constrains_column(&column_name)
+                        || self.is_referenced_column(table_name, &column_name))
+                {
+                    return Err(Error::catalog(
+                        CatalogKind::DependentObjects,
+                        format!(
+                            "Cannot change the type of column {}: it is used by a constraint",
+                            column_name
+                        ),
+                    ));
+                }
+                if !current.widens_to(&data_type) {
+                    return Err(Error::Type(format!(
+                        "Cannot change column {} from {:?} to {:?}: only widening conversions are supported",
+                        column_name, current, data_type
+                    )));
+                }
+
+                let mut new_schema = schema.clone();
+                new_schema.columns[col_idx].data_type = data_type.clone();
+                new_schema.columns[col_idx].default = new_schema.columns[col_idx]
+                    .default
+                    .take()
+                    .map(|d| d.widen_to(&data_type));
+                self.rewrite_table(&schema, new_schema, |mut row| {
+                    let field = std::mem::replace(&mut row.fields[col_idx], Field::Integer(0));
+                    row.fields[col_idx] = field.widen_to(&data_type);
+                    row
+                })?;
+
+                Ok(QueryResult::DdlOk {
+                    object: table_name.to_string(),
+                    message: format!("Column {} changed to {:?}.", column_name, data_type),
+                })
+            }
+        }
+    }
+
+    /// Copies every row of a table into a fresh file laid out for `new_schema`,
+    /// swaps it in place of the old file and then updates the catalog.
+    fn rewrite_table(
+        &mut self,
+        old_schema: &Schema,
+        new_schema: Schema,
+        transform: impl Fn(Row) -> Row,
+    ) -> Result<()> {
+        let name = &old_schema.table_name;
+        // A name no table has, so the copy can't take over the storage of another table
+        let mut tmp_name = format!("{}.tmp", name);
+        while self.catalog.schema(&tmp_name).is_some() {
+            tmp_name.push_str(".tmp");
+        }
+        self.remove_table_storage(&tmp_name)?;
+
+        let mut old_table =
+            Table::new(self.open_table_pager(name, old_schema)?, old_schema.clone());
+        let mut new_table = Table::new(
+            self.open_table_pager(&tmp_name, &new_schema)?,
+            new_schema.clone(),
+        );
+
+        for row in old_table.scan_rows()? {
+            new_table.insert_row(transform(row))?;
+        }
+        new_table.pager.flush()?;
+
+        // Close both handles before replacing the file
+        drop(old_table);
+        drop(new_table);
+        self.rename_table_storage(&tmp_name, name)?;
+
+        self.catalog.update_table(new_schema)
+    }
+
+    /// Opens the storage called `name`: a file in the data directory, or a segment
+    /// of the single-file database. It is created if it doesn't exist.
+    fn open_pager(&self, name: &str) -> Result<Pager> {
+        match &self.single_file {
+            Some(file) => Pager::segment(file, name),
+            None => Pager::with_pool(&self.table_path(name), self.pool.clone()),
+        }
+    }
+
+    /// Opens the storage called `name` for a table with `schema`, checking that its rows
+    /// were written for that schema. New storage is stamped with the schema's fingerprint.
+    pub(crate) fn open_table_pager(&self, name: &str, schema: &Schema) -> Result<Pager> {
+        let mut pager = self.open_pager(name)?;
+        let fingerprint = schema.fingerprint();
+        match pager.header().schema_fingerprint {
+            0 => pager.set_schema_fingerprint(fingerprint)?,
+            stored if stored == fingerprint => {}
+            _ => {
+                return Err(Error::Corruption(format!(
+                    "{} was written for a different layout of table {}",
+                    self.table_path(name),
+                    schema.table_name
+                )));
+            }
+        }
+        Ok(pager)
+    }
+
+    /// Deletes the storage called `name` and forgets its cached pages, if it exists.
+    fn remove_table_storage(&self, name: &str) -> Result<()> {
+        if let Some(file) = &self.single_file {
+            single_file::lock(file).remove_segment(name);
+            return Ok(());
+        }
+        let path = self.table_path(name);
+        buffer_pool::lock(&self.pool).discard_file(&path);
+        if Path::new(&path).exists() {
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the storage called `from` to `to`, replacing whatever `to` held.
+    fn rename_table_storage(&self, from: &str, to: &str) -> Result<()> {
+        if let Some(file) = &self.single_file {
+            single_file::lock(file).rename_segment(from, to);
+            return Ok(());
+        }
+        let (from, to) = (self.table_path(from), self.table_path(to));
+        {
+            let mut pool = buffer_pool::lock(&self.pool);
+            pool.flush_file(&from, true)?;
+            pool.discard_file(&from);
+            pool.discard_file(&to);
+        }
+        if Path::new(&from).exists() {
+            fs::rename(&from, &to)?;
+        }
+        Ok(())
+    }
+
+    /// Where the rows of a table are kept: its file, or its segment of the single-file
+    /// database, written as `file:segment`.
+    pub(crate) fn table_path(&self, table_name: &str) -> String {
+        match &self.single_file {
+            Some(_) => format!("{}:{}", self.data_dir, table_name),
+            None => format!("{}/{}.db", self.data_dir, table_name),
+        }
+    }
+
+    /// Takes a table out of the registry, opening its file and loading its primary and
+    /// unique indexes if it isn't there yet.
+    pub(crate) fn open_table(&self, table_name: &str) -> Result<TableHandle<'_>> {
+        let schema = self
+            .catalog
+            .schema(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?;
+
+        let cached = self.tables.borrow_mut().remove(table_name);
+        let table = match cached {
+            Some(table) => table,
+            None => {
+                let pager = self.open_table_pager(table_name, schema)?;
+                let mut table = Table::new(pager, schema.clone());
+                table.load_index()?;
+                table
+            }
+        };
+        Ok(TableHandle {
+            registry: &self.tables,
+            table: Some(table),
+        })
+    }
+
+    /// Every row of table `name`. The rows of a system table come from the catalog,
+    /// never from a table in the registry.
+    fn scan_table(&self, name: &str) -> Result<Vec<Row>> {
+        if system::is_system_table(name) {
+            return self.catalog.system_rows(name);
+        }
+        self.open_table(name)?.scan_rows()
+    }
+
+    /// Number of tables whose handles and indexes are cached between statements.
+    pub fn open_table_count(&self) -> usize {
+        self.tables.borrow().len()
+    }
+
+    /// Rejects a RETURNING list naming unknown columns before anything is written.
+    fn check_returning(&self, table_name: &str, returning: Option<&[SelectItem]>) -> Result<()> {
+        let schema = self
+            .catalog
+            .tables
+            .get(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?;
+        for item in returning.unwrap_or_default() {
+            if let SelectItem::Expr { expr, .. } = item {
+                for column in expr.columns() {
+                    if schema.column_index(column).is_none() {
+                        return Err(Error::column_not_found(column));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The result of an INSERT, UPDATE or DELETE: the RETURNING list evaluated over
+    /// the affected `rows` if there is one, otherwise their count.
+    fn dml_result(
+        &self,
+        table_name: &str,
+        returning: Option<Vec<SelectItem>>,
+        rows: Vec<Row>,
+        last_insert_id: Option<i32>,
+    ) -> Result<QueryResult> {
+        match returning {
+            Some(returning) => {
+                let schema = self
+                    .catalog
+                    .tables
+                    .get(table_name)
+                    .ok_or_else(|| Error::table_not_found(table_name))?;
+                Ok(QueryResult::Data(project(
+                    schema,
+                    &schema.column_names(),
+                    &returning,
+                    rows,
+                )?))
+            }
+            None => Ok(QueryResult::RowsAffected {
+                count: rows.len(),
+                last_insert_id,
+            }),
+        }
+    }
+
+    /// The AUTOINCREMENT value of the last row in `rows`, if the table has such a column.
+    fn last_insert_id(&self, table_name: &str, rows: &[Row]) -> Option<i32> {
+        let schema = self.catalog.tables.get(table_name)?;
+        let auto_idx = schema.columns.iter().position(|c| c.is_autoincrement)?;
+        match rows.last()?.fields[auto_idx] {
+            Field::Integer(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Fills in defaults, checks every constraint and writes one row. Returns the row
+    /// as stored.
+    fn insert_fields(
+        &mut self,
+        table_name: &str,
+        columns: &[String],
+        fields: Vec<Field>,
+        on_conflict: Option<&OnConflict>,
+    ) -> Result<Option<Row>> {
+        // 1. Get schema from catalog
+        let schema = self
+            .catalog
+            .tables
+            .get(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?
+            .clone();
+
+        // 2. Fill in defaults and check the row against the constraints
+        let prepared_row = self.validate_and_prepare_row(table_name, columns, fields)?;
+
+        // 3. Open the table with its indexes warm (So PK / UNIQUE checks work)
+        let mut table = self.open_table(table_name)?;
+
+        // 4. Resolve ON CONFLICT through the index covering the target
+        if let Some(on_conflict) = on_conflict {
+            let existing = if on_conflict.target.is_empty() {
+                table.find_conflict(&prepared_row)
+            } else {
+                if !schema.is_unique_key(&on_conflict.target) {
+                    return Err(Error::catalog(
+                        CatalogKind::InvalidDefinition,
+                        format!(
+                            "ON CONFLICT ({}) doesn't match a PRIMARY KEY or UNIQUE constraint",
+                            on_conflict.target.join(", ")
+                        ),
+                    ));
+                }
+                let target_idx = schema.column_indices(&on_conflict.target)?;
+                let values: Vec<&Field> = target_idx
+                    .iter()
+                    .map(|&i| &prepared_row.fields[i])
+                    .collect();
+                // NULLs never conflict
+                if values.contains(&&Field::Null) {
+                    None
+                } else {
+                    table.find_by_key(&target_idx, &values)
+                }
+            };
+
+            if let Some((p_idx, s_idx)) = existing {
+                let ConflictAction::DoUpdate {
+                    assignments,
+                    filter,
+                } = &on_conflict.action
+                else {
+                    return Ok(None);
+                };
+
+                let old_row = table.get_row(p_idx, s_idx)?;
+                drop(table);
+
+                // Expressions see the existing row followed by the proposed one as excluded.*
+                let mut merged_schema = schema.clone();
+                merged_schema
+                    .columns
+                    .extend(schema.columns.iter().map(|c| Column {
+                        name: format!("excluded.{}", c.name),
+                        ..c.clone()
+                    }));
+                let mut merged_row = old_row.clone();
+                merged_row.fields.extend(prepared_row.fields);
+
+                if let Some(filter) = filter
+                    && !filter.matches(&merged_row, &merged_schema)?
+                {
+                    return Ok(None);
+                }
+
+                let mut row = old_row.clone();
+                for (col_name, expr) in assignments {
+                    let col_idx = schema
+                        .column_index(col_name)
+                        .ok_or_else(|| Error::column_not_found(col_name))?;
+                    row.fields[col_idx] = expr.eval(&merged_row, &merged_schema)?;
+                }
+
+                validate_row(&schema, &row)?;
+                self.check_references(&schema, &row)?;
+                self.update_rows(table_name, vec![(p_idx, s_idx, old_row, row.clone())])?;
+                return Ok(Some(row));
+            }
+        }
+        // Put the table back first, a foreign key may refer to the table itself
+        drop(table);
+        self.check_references(&schema, &prepared_row)?;
+
+        // 5. Perform insert
+        self.open_table(table_name)?
+            .insert_row(prepared_row.clone())?;
+        Ok(Some(prepared_row))
     }
 
     fn validate_and_prepare_row(
         &mut self,
         table_name: &str,
+        columns: &[String],
         provided_fields: Vec<Field>,
-    ) -> Result<Row, String> {
+    ) -> Result<Row> {
         let schema = self
             .catalog
             .tables
             .get(table_name)
-            .ok_or_else(|| format!("Table {} not found", table_name))?;
+            .ok_or_else(|| Error::table_not_found(table_name))?;
 
         let mut final_fields = provided_fields;
 
+        // 0. Put named columns in schema order, filling the omitted ones from their defaults
+        if !columns.is_empty() {
+            if columns.len() != final_fields.len() {
+                return Err(Error::parse(format!(
+                    "{} columns were named, but {} values were provided",
+                    columns.len(),
+                    final_fields.len()
+                )));
+            }
+
+            let mut slots: Vec<Option<Field>> = vec![None; schema.columns.len()];
+            for (name, value) in columns.iter().zip(final_fields) {
+                let col_idx = schema
+                    .column_index(name)
+                    .ok_or_else(|| Error::column_not_found(name))?;
+                if slots[col_idx].is_some() {
+                    return Err(Error::catalog(
+                        CatalogKind::DuplicateColumn,
+                        format!("Column {} specified more than once", name),
+                    ));
+                }
+                slots[col_idx] = Some(value);
+            }
+
+            final_fields = Vec::with_capacity(slots.len());
+            for (column, slot) in schema.columns.iter().zip(slots) {
+                match slot.or_else(|| column.default.clone()) {
+                    Some(value) => final_fields.push(value),
+                    None if column.is_nullable => final_fields.push(Field::Null),
+                    // Placeholder, overwritten by the autoincrement step below
+                    None if column.is_autoincrement => final_fields.push(Field::Integer(0)),
+                    None => {
+                        return Err(Error::constraint(
+                            ConstraintKind::NotNull,
+                            format!("No value provided for column {}", column.name),
+                        ));
+                    }
+                }
+            }
+        }
+
         // 1. Handle Autoincrement Logic
         if let Some(auto_idx) = schema.columns.iter().position(|c| c.is_autoincrement) {
-            let next_id = self.catalog.get_next_id(table_name);
-            let schema = self.catalog.tables.get(table_name).unwrap();
+            let next_id = self.catalog.get_next_id(table_name)?;
+            let schema = self
+                .catalog
+                .tables
+                .get(table_name)
+                .ok_or_else(|| Error::table_not_found(table_name))?;
 
             let auto_field = Field::Integer(next_id);
 
@@ -388,47 +1365,73 @@ impl Database {
                 // Scenario: User omitted the ID, we insert it at the correct position
                 final_fields.insert(auto_idx, auto_field);
             } else {
-                return Err(format!(
+                return Err(Error::parse(format!(
                     "Column count mismatch: expected {} or {} (with autoincrement), found {}",
                     schema.columns.len(),
                     schema.columns.len() - 1,
                     final_fields.len()
-                ));
+                )));
             }
         }
 
-        let schema = self.catalog.tables.get(table_name).unwrap();
+        let schema = self
+            .catalog
+            .tables
+            .get(table_name)
+            .ok_or_else(|| Error::table_not_found(table_name))?;
         // 2. Final Length Check (for tables without autoincrement)
         if final_fields.len() != schema.columns.len() {
-            return Err(format!(
+            return Err(Error::parse(format!(
                 "Table {} expects {} columns, but {} were provided",
                 table_name,
                 schema.columns.len(),
                 final_fields.len()
-            ));
+            )));
         }
 
-        // 3. Type Validation
-        for (i, column) in schema.columns.iter().enumerate() {
-            let provided = &final_fields[i];
+        // 3. Type, NOT NULL and CHECK Validation
+        let row = Row {
+            fields: final_fields,
+        };
+        validate_row(schema, &row)?;
 
-            let is_valid = match (&column.data_type, provided) {
-                (DataType::Integer, Field::Integer(_)) => true,
-                (DataType::Boolean, Field::Boolean(_)) => true,
-                (DataType::Text(_), Field::Text(_)) => true,
-                _ => false,
-            };
+        Ok(row)
+    }
+}
 
-            if !is_valid {
-                return Err(format!(
-                    "Type mismatch for column '{}': expected {:?}, found {:?}",
-                    column.name, column.data_type, provided
-                ));
-            }
+/// Evaluates the SELECT list against every row. `*` expands to all columns of `schema`,
+/// named as in `wildcard`; other items are named after their alias or their expression text.
+fn project(
+    schema: &Schema,
+    wildcard: &[String],
+    projection: &[SelectItem],
+    rows: Vec<Row>,
+) -> Result<QueryResponse> {
+    let mut columns = Vec::new();
+    for item in projection {
+        match item {
+            SelectItem::Wildcard => columns.extend(wildcard.iter().cloned()),
+            SelectItem::Expr {
+                alias: Some(alias), ..
+            } => columns.push(alias.clone()),
+            SelectItem::Expr { expr, alias: None } => columns.push(expr.to_string()),
         }
+    }
 
-        Ok(Row {
-            fields: final_fields,
-        })
+    let mut projected = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut fields = Vec::with_capacity(columns.len());
+        for item in projection {
+            match item {
+                SelectItem::Wildcard => fields.extend(row.fields.iter().cloned()),
+                SelectItem::Expr { expr, .. } => fields.push(expr.eval(&row, schema)?),
+            }
+        }
+        projected.push(fields);
     }
+
+    Ok(QueryResponse {
+        columns,
+        rows: projected,
+    })
 }
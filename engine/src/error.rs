@@ -0,0 +1,168 @@
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong while parsing or executing a statement. Each
+/// category maps to a SQLSTATE code, so clients can tell a syntax error from a
+/// constraint violation or an I/O failure without parsing the message.
+#[derive(Debug)]
+pub enum Error {
+    // The SQL text couldn't be parsed; `position` points at the offending token when known
+    Parse {
+        message: String,
+        position: Option<Position>,
+    },
+    // Valid SQL this engine doesn't implement
+    Unsupported(String),
+    Catalog {
+        kind: CatalogKind,
+        message: String,
+    },
+    Constraint {
+        kind: ConstraintKind,
+        message: String,
+    },
+    // A value of the wrong type for a column, operator or function
+    Type(String),
+    // A bad value at runtime, e.g. division by zero or integer overflow
+    Data(String),
+    // Text longer than the column it is stored in
+    StringTooLong(String),
+    Io(io::Error),
+    // On-disk data that doesn't decode
+    Corruption(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CatalogKind {
+    UndefinedTable,
+    UndefinedColumn,
+    DuplicateTable,
+    DuplicateColumn,
+    // The object is still used by another one, e.g. a table referenced by a foreign key
+    DependentObjects,
+    InvalidDefinition,
+    // The object isn't of the kind the statement needs, e.g. writing to a system view
+    WrongObjectType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstraintKind {
+    NotNull,
+    PrimaryKey,
+    Unique,
+    ForeignKey,
+    Check,
+}
+
+/// Line and column of a parse error, both starting at 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: u64,
+    pub column: u64,
+}
+
+impl Error {
+    pub fn parse(message: impl Into<String>) -> Self {
+        Error::Parse {
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    pub fn catalog(kind: CatalogKind, message: impl Into<String>) -> Self {
+        Error::Catalog {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn constraint(kind: ConstraintKind, message: impl Into<String>) -> Self {
+        Error::Constraint {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn table_not_found(name: &str) -> Self {
+        Error::catalog(
+            CatalogKind::UndefinedTable,
+            format!("Table {} not found", name),
+        )
+    }
+
+    pub fn column_not_found(name: &str) -> Self {
+        Error::catalog(
+            CatalogKind::UndefinedColumn,
+            format!("Column {} not found", name),
+        )
+    }
+
+    /// The five-character SQLSTATE code for this error.
+    pub fn sqlstate(&self) -> &'static str {
+        match self {
+            Error::Parse { .. } => "42601",
+            Error::Unsupported(_) => "0A000",
+            Error::Catalog { kind, .. } => match kind {
+                CatalogKind::UndefinedTable => "42P01",
+                CatalogKind::UndefinedColumn => "42703",
+                CatalogKind::DuplicateTable => "42P07",
+                CatalogKind::DuplicateColumn => "42701",
+                CatalogKind::DependentObjects => "2BP01",
+                CatalogKind::InvalidDefinition => "42P16",
+                CatalogKind::WrongObjectType => "42809",
+            },
+            Error::Constraint { kind, .. } => match kind {
+                ConstraintKind::NotNull => "23502",
+                ConstraintKind::PrimaryKey | ConstraintKind::Unique => "23505",
+                ConstraintKind::ForeignKey => "23503",
+                ConstraintKind::Check => "23514",
+            },
+            Error::Type(_) => "42804",
+            Error::Data(_) => "22000",
+            Error::StringTooLong(_) => "22001",
+            Error::Io(_) => "58030",
+            Error::Corruption(_) => "XX001",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse {
+                message,
+                position: Some(position),
+            } => write!(
+                f,
+                "{} (line {}, column {})",
+                message, position.line, position.column
+            ),
+            Error::Parse { message, .. }
+            | Error::Unsupported(message)
+            | Error::Catalog { message, .. }
+            | Error::Constraint { message, .. }
+            | Error::Type(message)
+            | Error::Data(message)
+            | Error::StringTooLong(message)
+            | Error::Corruption(message) => f.write_str(message),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,299 @@
+//! Checks driven by the `cargo fuzz` targets in `engine/fuzz`. Each one takes
+//! raw fuzzer bytes and panics when it finds a bug, so the same functions also
+//! replay saved crash inputs in `tests/fuzz_regression_tests.rs`.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs;
+
+use crate::catalog::schema::{Column, DataType, Schema};
+use crate::engine::Database;
+use crate::error::Result;
+use crate::sql::QueryResult;
+use crate::sql::parser::parse_sql;
+use crate::storage::record::{Field, Row};
+
+/// Reads fuzzer bytes as a stream of small numbers, yielding zeros once exhausted.
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn byte(&mut self) -> u8 {
+        let b = self.data.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        b
+    }
+
+    fn below(&mut self, n: u8) -> u8 {
+        self.byte() % n
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+}
+
+/// Parsing arbitrary text must return commands or an error, never panic.
+pub fn check_parse(data: &[u8]) {
+    if let Ok(sql) = std::str::from_utf8(data) {
+        let _ = parse_sql(sql);
+    }
+}
+
+/// Decodes a schema from the first bytes of `data` and a row from the rest.
+/// Decoding must not panic, must fail on short input, and integers, booleans
+/// and NULLs must survive a round trip through `serialize`.
+pub fn check_row_codec(data: &[u8]) {
+    let mut input = Input::new(data);
+    let count = input.below(8) + 1;
+    let columns = (0..count)
+        .map(|i| {
+            let spec = input.byte();
+            let data_type = match spec % 3 {
+                0 => DataType::Integer,
+                1 => DataType::Boolean,
+                _ => DataType::Text((spec as usize >> 2) % 64 + 1),
+            };
+            Column {
+                name: format!("c{}", i),
+                data_type,
+                is_primary: false,
+                is_autoincrement: false,
+                is_nullable: spec & 0x80 != 0,
+                default: None,
+            }
+        })
+        .collect();
+    let schema = Schema {
+        table_name: "fuzz".to_string(),
+        columns,
+        unique: Vec::new(),
+        checks: Vec::new(),
+        foreign_keys: Vec::new(),
+    };
+
+    let bytes = input.rest();
+    let row = match Row::deserialize(bytes, &schema) {
+        Ok(row) => row,
+        Err(_) => {
+            assert!(bytes.len() < schema.row_size());
+            return;
+        }
+    };
+    assert_eq!(row.fields.len(), schema.columns.len());
+
+    let again = Row::deserialize(&row.serialize(&schema), &schema).expect("round trip");
+    for (before, after) in row.fields.iter().zip(&again.fields) {
+        if !matches!(before, Field::Text(_)) {
+            assert_eq!(before, after);
+        }
+    }
+}
+
+/// Values of `t` in the reference model, keyed by id: (v, s). The wide, always
+/// NULL `pad` column spreads the table over several pages.
+type Model = BTreeMap<i32, (Option<i32>, Option<String>)>;
+
+const TEXT_LEN: usize = 8;
+
+/// Runs a random sequence of statements against a database in `data_dir` and
+/// against an in-memory model of the same table, panicking when they disagree.
+pub fn check_differential(data: &[u8], data_dir: &str) {
+    let _ = fs::remove_dir_all(data_dir);
+    let mut db = Database::open(data_dir).expect("open");
+    let mut model = Model::new();
+    let mut input = Input::new(data);
+
+    exec(
+        &mut db,
+        &format!(
+            "CREATE TABLE t (id INT PRIMARY KEY, v INT, s VARCHAR({}), pad VARCHAR(200))",
+            TEXT_LEN
+        ),
+    )
+    .expect("create");
+
+    while !input.is_empty() {
+        let k = input.below(64) as i32;
+        match input.below(8) {
+            0 => {
+                let v = optional_int(&mut input);
+                let s = optional_text(&mut input);
+                let sql = format!(
+                    "INSERT INTO t (id, v, s) VALUES ({}, {}, {})",
+                    k,
+                    sql_int(v),
+                    sql_text(s.as_deref())
+                );
+                let result = exec(&mut db, &sql);
+                match model.entry(k) {
+                    Entry::Vacant(entry) if fits(&s) => {
+                        assert_eq!(affected(result, &sql), 1);
+                        entry.insert((v, s));
+                    }
+                    _ => assert!(result.is_err(), "{} should fail", sql),
+                }
+            }
+            1 => {
+                let d = input.below(7) as i32 - 3;
+                let sql = format!("UPDATE t SET v = v + {} WHERE id = {}", d, k);
+                let expected = match model.get_mut(&k) {
+                    Some((v, _)) => {
+                        *v = v.map(|v| v + d);
+                        1
+                    }
+                    None => 0,
+                };
+                assert_eq!(affected(exec(&mut db, &sql), &sql), expected);
+            }
+            2 => {
+                let x = input.below(7) as i32 - 3;
+                let s = optional_text(&mut input);
+                let sql = format!(
+                    "UPDATE t SET s = {} WHERE v > {}",
+                    sql_text(s.as_deref()),
+                    x
+                );
+                let matching = model
+                    .values()
+                    .filter(|(v, _)| v.is_some_and(|v| v > x))
+                    .count();
+                let result = exec(&mut db, &sql);
+                if matching > 0 && !fits(&s) {
+                    assert!(result.is_err(), "{} should fail", sql);
+                } else {
+                    for (v, old) in model.values_mut() {
+                        if v.is_some_and(|v| v > x) {
+                            *old = s.clone();
+                        }
+                    }
+                    assert_eq!(affected(result, &sql), matching);
+                }
+            }
+            3 => {
+                let sql = format!("DELETE FROM t WHERE id = {}", k);
+                let expected = usize::from(model.remove(&k).is_some());
+                assert_eq!(affected(exec(&mut db, &sql), &sql), expected);
+            }
+            4 => {
+                let x = input.below(7) as i32 - 3;
+                let sql = format!("DELETE FROM t WHERE v IS NULL OR v < {}", x);
+                let before = model.len();
+                model.retain(|_, (v, _)| v.is_some_and(|v| v >= x));
+                assert_eq!(affected(exec(&mut db, &sql), &sql), before - model.len());
+            }
+            5 => {
+                let sql = format!("SELECT * FROM t WHERE id = {}", k);
+                let expected: Vec<_> = model_rows(&model)
+                    .into_iter()
+                    .filter(|row| row[0] == Field::Integer(k))
+                    .collect();
+                assert_eq!(rows(exec(&mut db, &sql), &sql), expected);
+            }
+            6 => {
+                let d = input.below(7) as i32 - 3;
+                let sql = format!("UPDATE t SET id = id + {} WHERE id = {}", d, k);
+                let result = exec(&mut db, &sql);
+                match model.get(&k).cloned() {
+                    None => assert_eq!(affected(result, &sql), 0),
+                    Some(_) if d != 0 && model.contains_key(&(k + d)) => {
+                        assert!(result.is_err(), "{} should fail", sql)
+                    }
+                    Some(values) => {
+                        assert_eq!(affected(result, &sql), 1);
+                        model.remove(&k);
+                        model.insert(k + d, values);
+                    }
+                }
+            }
+            _ => {
+                drop(db);
+                db = Database::open(data_dir).expect("reopen");
+            }
+        }
+
+        let mut actual = rows(exec(&mut db, "SELECT * FROM t"), "SELECT * FROM t");
+        actual.sort_by_key(|row| match row[0] {
+            Field::Integer(id) => id,
+            _ => i32::MIN,
+        });
+        assert_eq!(actual, model_rows(&model));
+    }
+
+    let _ = fs::remove_dir_all(data_dir);
+}
+
+fn exec(db: &mut Database, sql: &str) -> Result<QueryResult> {
+    let mut result = Ok(QueryResult::Empty);
+    for command in parse_sql(sql)? {
+        result = Ok(db.execute(command)?);
+    }
+    result
+}
+
+fn affected(result: Result<QueryResult>, sql: &str) -> usize {
+    match result {
+        Ok(QueryResult::RowsAffected { count, .. }) => count,
+        Ok(_) => panic!("Expected a row count from: {}", sql),
+        Err(e) => panic!("{} failed: {}", sql, e),
+    }
+}
+
+fn rows(result: Result<QueryResult>, sql: &str) -> Vec<Vec<Field>> {
+    match result {
+        Ok(QueryResult::Data(response)) => response.rows,
+        Ok(_) => panic!("Expected rows from: {}", sql),
+        Err(e) => panic!("{} failed: {}", sql, e),
+    }
+}
+
+fn model_rows(model: &Model) -> Vec<Vec<Field>> {
+    model
+        .iter()
+        .map(|(id, (v, s))| {
+            vec![
+                Field::Integer(*id),
+                v.map_or(Field::Null, Field::Integer),
+                s.clone().map_or(Field::Null, Field::Text),
+                Field::Null,
+            ]
+        })
+        .collect()
+}
+
+fn optional_int(input: &mut Input) -> Option<i32> {
+    match input.below(8) {
+        0 => None,
+        n => Some(n as i32 - 4),
+    }
+}
+
+fn optional_text(input: &mut Input) -> Option<String> {
+    match input.below(14) {
+        0 => None,
+        n => Some((0..n - 1).map(|i| (b'a' + (i + n) % 4) as char).collect()),
+    }
+}
+
+/// Longer text is refused rather than cut to fit the column.
+fn fits(s: &Option<String>) -> bool {
+    s.as_ref().is_none_or(|s| s.len() <= TEXT_LEN)
+}
+
+fn sql_int(v: Option<i32>) -> String {
+    v.map_or("NULL".to_string(), |v| v.to_string())
+}
+
+fn sql_text(s: Option<&str>) -> String {
+    s.map_or("NULL".to_string(), |s| format!("'{}'", s))
+}
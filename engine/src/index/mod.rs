@@ -1,5 +1,8 @@
 use std::collections::BTreeMap;
 
+use crate::error::{ConstraintKind, Error, Result};
+use crate::storage::record::{Field, Row};
+
 #[derive(Debug)]
 pub struct PrimaryIndex {
     // Key: The value of the Primary Key column (as a String or custom Enum)
@@ -7,6 +10,12 @@ pub struct PrimaryIndex {
     pub map: BTreeMap<String, (usize, usize)>,
 }
 
+impl Default for PrimaryIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PrimaryIndex {
     pub fn new() -> Self {
         Self {
@@ -14,11 +23,90 @@ impl PrimaryIndex {
         }
     }
 
-    pub fn insert(&mut self, key: String, page_idx: usize, slot_idx: usize) -> Result<(), String> {
+    pub fn insert(&mut self, key: String, page_idx: usize, slot_idx: usize) -> Result<()> {
         if self.map.contains_key(&key) {
-            return Err(format!("Duplicate key violation: '{}' already exists", key));
+            return Err(Error::constraint(
+                ConstraintKind::PrimaryKey,
+                format!(
+                    "Duplicate key violation: '{}' already exists",
+                    decode_key(&key).join(", ")
+                ),
+            ));
         }
         self.map.insert(key, (page_idx, slot_idx));
         Ok(())
     }
 }
+
+/// Index backing a UNIQUE constraint over one or more columns.
+#[derive(Debug)]
+pub struct UniqueIndex {
+    // Positions of the indexed columns in the schema
+    pub columns: Vec<usize>,
+    pub map: BTreeMap<String, (usize, usize)>,
+}
+
+impl UniqueIndex {
+    pub fn new(columns: Vec<usize>) -> Self {
+        Self {
+            columns,
+            map: BTreeMap::new(),
+        }
+    }
+
+    /// The index key for `row`, or None if any indexed column is NULL.
+    /// NULLs are never equal to each other, so such rows don't take part in the constraint.
+    pub fn key_for(&self, row: &Row) -> Option<String> {
+        let fields: Vec<&Field> = self.columns.iter().map(|&i| &row.fields[i]).collect();
+        if fields.contains(&&Field::Null) {
+            return None;
+        }
+        Some(encode_key(&fields))
+    }
+}
+
+/// Separates the values of a composite key.
+pub const KEY_SEPARATOR: char = '\u{1f}';
+// Marks a separator or escape character that is part of a value
+const KEY_ESCAPE: char = '\\';
+
+/// Turns one or more field values into the string key stored in an index.
+/// Values are joined with `KEY_SEPARATOR`, and separators and escapes inside a
+/// value are escaped, so ("a", "bc") and ("ab", "c") stay apart however the
+/// values are spelled, and a key starts with the key of its leading values
+/// followed by a separator.
+pub fn encode_key(fields: &[&Field]) -> String {
+    let mut key = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            key.push(KEY_SEPARATOR);
+        }
+        let value = match field {
+            Field::Integer(v) => v.to_string(),
+            Field::Text(v) => v.clone(),
+            Field::Boolean(v) => v.to_string(),
+            Field::Null => String::new(),
+        };
+        for c in value.chars() {
+            if c == KEY_SEPARATOR || c == KEY_ESCAPE {
+                key.push(KEY_ESCAPE);
+            }
+            key.push(c);
+        }
+    }
+    key
+}
+
+/// Splits a key made by `encode_key` back into its values, as text.
+pub fn decode_key(key: &str) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut chars = key.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            KEY_ESCAPE => values.last_mut().unwrap().extend(chars.next()),
+            KEY_SEPARATOR => values.push(String::new()),
+            c => values.last_mut().unwrap().push(c),
+        }
+    }
+    values
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::catalog::schema::Schema;
+use crate::catalog::system;
+use crate::engine::Database;
+use crate::error::Result;
+use crate::index::decode_key;
+use crate::storage::Table;
+use crate::storage::buffer_pool;
+use crate::storage::pager::{BITMAP_SIZE, Page, Pager};
+use crate::storage::record::Row;
+use crate::storage::single_file;
+
+impl Database {
+    /// Checks that the catalog, the table files and the rows in them agree, and
+    /// returns one line per problem found. No problems means the database is sound.
+    pub fn integrity_check(&mut self) -> Result<Vec<String>> {
+        // Pages are read back from disk below, so nothing may be left in the pool
+        self.flush(false)?;
+        if let Some(file) = self.single_file() {
+            // Every table shares the pool of the file; empty it so pages come from disk
+            let single = single_file::lock(file);
+            buffer_pool::lock(&single.pool()).discard_file(single.path());
+        }
+
+        let mut problems = Vec::new();
+        let mut names: Vec<&String> = self.catalog.tables.keys().collect();
+        names.sort();
+        for name in names {
+            self.check_table(&self.catalog.tables[name], &mut problems)?;
+        }
+        self.check_table(&system::master_schema(), &mut problems)?;
+
+        let owned = |name: &str| name == system::MASTER || self.catalog.tables.contains_key(name);
+        if let Some(file) = self.single_file() {
+            problems.extend(single_file::lock(file).check(owned));
+            return Ok(problems);
+        }
+
+        let mut orphans = Vec::new();
+        for entry in fs::read_dir(&self.data_dir)? {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(table_name) = file_name.strip_suffix(".db")
+                && !owned(table_name)
+            {
+                orphans.push(format!(
+                    "File {} doesn't belong to any table in the catalog",
+                    file_name
+                ));
+            }
+        }
+        orphans.sort();
+        problems.extend(orphans);
+
+        Ok(problems)
+    }
+
+    fn check_table(&self, schema: &Schema, problems: &mut Vec<String>) -> Result<()> {
+        let name = &schema.table_name;
+        let path = self.table_path(name);
+        let opened = match self.single_file() {
+            Some(file) if !single_file::lock(file).has_segment(name) => {
+                problems.push(format!("Table {}: segment {} is missing", name, path));
+                return Ok(());
+            }
+            Some(file) => Pager::segment(file, name),
+            None if !Path::new(&path).exists() => {
+                problems.push(format!("Table {}: file {} is missing", name, path));
+                return Ok(());
+            }
+            // A pager with a pool of its own, so every page comes from disk and has its
+            // checksum checked, that never writes to the file it checks
+            None => Pager::open_read_only(&path),
+        };
+        let pager = match opened {
+            Ok(pager) => pager,
+            Err(e) => {
+                problems.push(format!("Table {}: {}", name, e));
+                return Ok(());
+            }
+        };
+        if pager.file_length == 0 && self.single_file().is_none() {
+            problems.push(format!("Table {}: file {} has no header page", name, path));
+            return Ok(());
+        }
+        if pager.header().schema_fingerprint != schema.fingerprint() {
+            problems.push(format!(
+                "Table {}: file {} was written for a different layout of the table",
+                name, path
+            ));
+            return Ok(());
+        }
+
+        let mut table = Table::new(pager, schema.clone());
+        let row_size = schema.row_size();
+        let max_slots = Page::max_slots(row_size);
+        let mut primary: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut unique: Vec<HashMap<String, (usize, usize)>> =
+            vec![HashMap::new(); table.unique_indexes.len()];
+
+        for p_idx in 0..table.pager.num_pages() {
+            let page = match table.pager.read_page(p_idx) {
+                Ok(page) => page,
+                Err(e) => {
+                    problems.push(format!("Table {}: {}", name, e));
+                    continue;
+                }
+            };
+
+            let stray = (max_slots..BITMAP_SIZE * 8)
+                .filter(|&s| page.is_slot_full(s))
+                .count();
+            if stray > 0 {
+                problems.push(format!(
+                    "Table {}: page {} marks {} slots past the last of its {} slots as used",
+                    name, p_idx, stray, max_slots
+                ));
+            }
+
+            for s_idx in (0..max_slots).filter(|&s| page.is_slot_full(s)) {
+                let offset = page.get_row_offset(s_idx, row_size);
+                let bytes = &page.data[offset..offset + row_size];
+                if let Some(column) = Row::invalid_text_column(bytes, schema) {
+                    problems.push(format!(
+                        "Table {}: row at page {}, slot {} has invalid UTF-8 in column {}",
+                        name, p_idx, s_idx, schema.columns[column].name
+                    ));
+                }
+                let row = match Row::deserialize(bytes, schema) {
+                    Ok(row) => row,
+                    Err(e) => {
+                        problems.push(format!("Table {}: {}", name, e));
+                        continue;
+                    }
+                };
+
+                if let Some(key) = table.primary_key_for(&row)
+                    && let Some((p, s)) = primary.insert(key.clone(), (p_idx, s_idx))
+                {
+                    problems.push(format!(
+                        "Table {}: duplicate primary key {} at page {}, slot {} and page {}, slot {}",
+                        name,
+                        display_key(&key),
+                        p,
+                        s,
+                        p_idx,
+                        s_idx
+                    ));
+                }
+                for (index, seen) in table.unique_indexes.iter().zip(&mut unique) {
+                    if let Some(key) = index.key_for(&row)
+                        && let Some((p, s)) = seen.insert(key.clone(), (p_idx, s_idx))
+                    {
+                        problems.push(format!(
+                            "Table {}: duplicate UNIQUE key {} at page {}, slot {} and page {}, slot {}",
+                            name,
+                            display_key(&key),
+                            p,
+                            s,
+                            p_idx,
+                            s_idx
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An index key as the values it was built from, e.g. `(1, a)`.
+fn display_key(key: &str) -> String {
+    format!("({})", decode_key(key).join(", "))
+}
@@ -1,6 +1,14 @@
 pub mod catalog;
+mod constraints;
+mod convert;
 pub mod engine;
+pub mod error;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
 pub mod index;
+mod integrity;
+pub mod query;
 pub mod sql;
 pub mod storage;
 mod tests;
+mod upgrade;
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::catalog::schema::Schema;
+use crate::error::Result;
+use crate::sql::expr::{BinaryOp, Expr};
+use crate::storage::Table;
+use crate::storage::record::{Field, Row};
+
+/// How the rows matching a WHERE clause are located.
+#[derive(Debug, PartialEq)]
+pub enum AccessPath {
+    FullScan,
+    // A value for every primary key column: at most one row
+    PrimaryKeyLookup(Vec<Field>),
+    // Values for the leading primary key columns: a range of the index
+    PrimaryKeyPrefix(Vec<Field>),
+}
+
+impl AccessPath {
+    /// Picks the cheapest way to answer `filter` on a table with `schema`.
+    /// Equalities between a primary key column and a literal, joined by AND,
+    /// select an index lookup when they pin down a leading part of the key.
+    pub fn plan(schema: &Schema, filter: Option<&Expr>) -> AccessPath {
+        let mut equalities = HashMap::new();
+        if let Some(filter) = filter {
+            collect_equalities(filter, schema, &mut equalities);
+        }
+
+        let values: Vec<Field> = schema
+            .primary_key_indices()
+            .into_iter()
+            .map_while(|i| equalities.get(&i).cloned())
+            .collect();
+
+        if values.is_empty() {
+            AccessPath::FullScan
+        } else if values.len() == schema.primary_key_indices().len() {
+            AccessPath::PrimaryKeyLookup(values)
+        } else {
+            AccessPath::PrimaryKeyPrefix(values)
+        }
+    }
+}
+
+/// Finds the rows of `table` matching `filter`, with their (page_idx, slot_idx).
+/// The filter is always re-checked, so the access path only narrows the candidates.
+pub fn find_rows(
+    table: &mut Table,
+    path: &AccessPath,
+    filter: Option<&Expr>,
+) -> Result<Vec<(usize, usize, Row)>> {
+    let candidates = match path {
+        AccessPath::FullScan => table.scan_locations()?,
+        AccessPath::PrimaryKeyLookup(values) | AccessPath::PrimaryKeyPrefix(values) => {
+            let values: Vec<&Field> = values.iter().collect();
+            let mut rows = Vec::new();
+            for (p_idx, s_idx) in table.find_by_key_prefix(&values) {
+                let row = table.get_row(p_idx, s_idx)?;
+                rows.push((p_idx, s_idx, row));
+            }
+            rows
+        }
+    };
+
+    let mut matches = Vec::new();
+    for (p_idx, s_idx, row) in candidates {
+        let keep = match filter {
+            Some(filter) => filter.matches(&row, &table.schema)?,
+            None => true,
+        };
+        if keep {
+            matches.push((p_idx, s_idx, row));
+        }
+    }
+    Ok(matches)
+}
+
+/// Records every `column = literal` found among the AND-ed terms of `expr`, by column index.
+fn collect_equalities(expr: &Expr, schema: &Schema, out: &mut HashMap<usize, Field>) {
+    let Expr::BinaryOp { left, op, right } = expr else {
+        return;
+    };
+    match (op, left.as_ref(), right.as_ref()) {
+        (BinaryOp::And, _, _) => {
+            collect_equalities(left, schema, out);
+            collect_equalities(right, schema, out);
+        }
+        (BinaryOp::Eq, Expr::Column(name), Expr::Literal(value))
+        | (BinaryOp::Eq, Expr::Literal(value), Expr::Column(name)) => {
+            // A literal of the wrong type can't be looked up; leave it to the scan to report
+            if let Some(i) = schema.column_index(name)
+                && schema.columns[i].data_type.accepts(value)
+            {
+                out.insert(i, value.clone());
+            }
+        }
+        _ => {}
+    }
+}
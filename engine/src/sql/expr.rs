@@ -0,0 +1,366 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::schema::Schema;
+use crate::error::{Error, Result};
+use crate::storage::record::{Field, Row};
+
+/// A scalar expression evaluated against a single row: a WHERE clause, the body of
+/// a CHECK constraint, the right-hand side of an UPDATE assignment or a SELECT item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Expr {
+    Literal(Field),
+    Column(String),
+    BinaryOp {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Function {
+        function: Function,
+        args: Vec<Expr>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    // The || operator
+    Concat,
+}
+
+/// Built-in scalar functions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Function {
+    Upper,
+    Lower,
+    Length,
+    Abs,
+    Coalesce,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        match name.to_uppercase().as_str() {
+            "UPPER" => Some(Function::Upper),
+            "LOWER" => Some(Function::Lower),
+            "LENGTH" => Some(Function::Length),
+            "ABS" => Some(Function::Abs),
+            "COALESCE" => Some(Function::Coalesce),
+            _ => None,
+        }
+    }
+
+    /// Checks the number of arguments; COALESCE takes one or more, the rest exactly one.
+    pub fn check_arity(&self, count: usize) -> Result<()> {
+        let ok = match self {
+            Function::Coalesce => count >= 1,
+            _ => count == 1,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::Type(format!(
+                "Wrong number of arguments for {}: {}",
+                self, count
+            )))
+        }
+    }
+
+    /// Applies the function to already evaluated arguments. Apart from COALESCE,
+    /// a NULL argument gives a NULL result.
+    fn call(&self, args: Vec<Field>) -> Result<Field> {
+        self.check_arity(args.len())?;
+        if *self == Function::Coalesce {
+            return Ok(args
+                .into_iter()
+                .find(|arg| *arg != Field::Null)
+                .unwrap_or(Field::Null));
+        }
+
+        match (self, &args[0]) {
+            (_, Field::Null) => Ok(Field::Null),
+            (Function::Upper, Field::Text(s)) => Ok(Field::Text(s.to_uppercase())),
+            (Function::Lower, Field::Text(s)) => Ok(Field::Text(s.to_lowercase())),
+            (Function::Length, Field::Text(s)) => Ok(Field::Integer(s.chars().count() as i32)),
+            (Function::Abs, Field::Integer(i)) => i
+                .checked_abs()
+                .map(Field::Integer)
+                .ok_or_else(|| Error::Data("Integer overflow in ABS".to_string())),
+            (_, other) => Err(Error::Type(format!("{} doesn't accept {:?}", self, other))),
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression against `row`. Comparisons involving NULL yield NULL,
+    /// and AND / OR follow SQL's three-valued logic.
+    pub fn eval(&self, row: &Row, schema: &Schema) -> Result<Field> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Column(name) => {
+                let col_idx = schema
+                    .column_index(name)
+                    .ok_or_else(|| Error::column_not_found(name))?;
+                Ok(row.fields[col_idx].clone())
+            }
+            Expr::Not(inner) => match inner.eval(row, schema)? {
+                Field::Boolean(b) => Ok(Field::Boolean(!b)),
+                Field::Null => Ok(Field::Null),
+                other => Err(Error::Type(format!(
+                    "NOT expects a boolean, found {:?}",
+                    other
+                ))),
+            },
+            Expr::Negate(inner) => match inner.eval(row, schema)? {
+                Field::Integer(i) => i
+                    .checked_neg()
+                    .map(Field::Integer)
+                    .ok_or_else(|| Error::Data("Integer overflow".to_string())),
+                Field::Null => Ok(Field::Null),
+                other => Err(Error::Type(format!("Cannot negate {:?}", other))),
+            },
+            Expr::Function { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(row, schema))
+                    .collect::<Result<Vec<_>>>()?;
+                function.call(args)
+            }
+            Expr::IsNull { expr, negated } => {
+                let is_null = expr.eval(row, schema)? == Field::Null;
+                Ok(Field::Boolean(is_null != *negated))
+            }
+            Expr::BinaryOp { left, op, right } => {
+                let left = left.eval(row, schema)?;
+                let right = right.eval(row, schema)?;
+                match op {
+                    BinaryOp::And => logical(left, right, false),
+                    BinaryOp::Or => logical(left, right, true),
+                    BinaryOp::Plus
+                    | BinaryOp::Minus
+                    | BinaryOp::Multiply
+                    | BinaryOp::Divide
+                    | BinaryOp::Modulo => arithmetic(&left, *op, &right),
+                    BinaryOp::Concat => match (left, right) {
+                        (Field::Null, _) | (_, Field::Null) => Ok(Field::Null),
+                        (left, right) => Ok(Field::Text(format!(
+                            "{}{}",
+                            text_of(&left),
+                            text_of(&right)
+                        ))),
+                    },
+                    _ => compare(&left, *op, &right),
+                }
+            }
+        }
+    }
+
+    /// Returns true only if the expression evaluates to TRUE. This is the rule for WHERE
+    /// clauses, where rows for which the condition is NULL are left out.
+    pub fn matches(&self, row: &Row, schema: &Schema) -> Result<bool> {
+        Ok(self.eval(row, schema)? == Field::Boolean(true))
+    }
+
+    /// Returns true unless the expression evaluates to FALSE. This is the rule SQL
+    /// uses for CHECK constraints, where a NULL result passes.
+    pub fn is_satisfied_by(&self, row: &Row, schema: &Schema) -> Result<bool> {
+        Ok(self.eval(row, schema)? != Field::Boolean(false))
+    }
+
+    /// Names of all columns referenced by the expression.
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Expr::Literal(_) => Vec::new(),
+            Expr::Column(name) => vec![name.as_str()],
+            Expr::Not(inner) | Expr::Negate(inner) => inner.columns(),
+            Expr::IsNull { expr, .. } => expr.columns(),
+            Expr::Function { args, .. } => args.iter().flat_map(|arg| arg.columns()).collect(),
+            Expr::BinaryOp { left, right, .. } => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+        }
+    }
+
+    pub fn rename_column(&mut self, old_name: &str, new_name: &str) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Column(name) => {
+                if name == old_name {
+                    *name = new_name.to_string();
+                }
+            }
+            Expr::Not(inner) | Expr::Negate(inner) => inner.rename_column(old_name, new_name),
+            Expr::IsNull { expr, .. } => expr.rename_column(old_name, new_name),
+            Expr::Function { args, .. } => {
+                for arg in args {
+                    arg.rename_column(old_name, new_name);
+                }
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                left.rename_column(old_name, new_name);
+                right.rename_column(old_name, new_name);
+            }
+        }
+    }
+}
+
+fn logical(left: Field, right: Field, is_or: bool) -> Result<Field> {
+    let as_bool = |field: Field| match field {
+        Field::Boolean(b) => Ok(Some(b)),
+        Field::Null => Ok(None),
+        other => Err(Error::Type(format!(
+            "Expected a boolean, found {:?}",
+            other
+        ))),
+    };
+
+    // The short-circuit value (TRUE for OR, FALSE for AND) wins even over NULL
+    Ok(match (as_bool(left)?, as_bool(right)?) {
+        (Some(a), _) if a == is_or => Field::Boolean(is_or),
+        (_, Some(b)) if b == is_or => Field::Boolean(is_or),
+        (Some(_), Some(_)) => Field::Boolean(!is_or),
+        _ => Field::Null,
+    })
+}
+
+fn arithmetic(left: &Field, op: BinaryOp, right: &Field) -> Result<Field> {
+    let (a, b) = match (left, right) {
+        (Field::Null, _) | (_, Field::Null) => return Ok(Field::Null),
+        (Field::Integer(a), Field::Integer(b)) => (*a, *b),
+        _ => {
+            return Err(Error::Type(format!(
+                "Cannot apply {} to {:?} and {:?}",
+                op, left, right
+            )));
+        }
+    };
+
+    if b == 0 && matches!(op, BinaryOp::Divide | BinaryOp::Modulo) {
+        return Err(Error::Data("Division by zero".to_string()));
+    }
+    let result = match op {
+        BinaryOp::Plus => a.checked_add(b),
+        BinaryOp::Minus => a.checked_sub(b),
+        BinaryOp::Multiply => a.checked_mul(b),
+        BinaryOp::Divide => a.checked_div(b),
+        BinaryOp::Modulo => a.checked_rem(b),
+        _ => unreachable!("only arithmetic operators reach here"),
+    };
+    result
+        .map(Field::Integer)
+        .ok_or_else(|| Error::Data(format!("Integer overflow in {} {} {}", a, op, b)))
+}
+
+/// The text form of a value, as used by the || operator.
+fn text_of(field: &Field) -> String {
+    match field {
+        Field::Integer(v) => v.to_string(),
+        Field::Boolean(v) => v.to_string(),
+        Field::Text(v) => v.clone(),
+        Field::Null => String::new(),
+    }
+}
+
+fn compare(left: &Field, op: BinaryOp, right: &Field) -> Result<Field> {
+    let ordering = match (left, right) {
+        (Field::Null, _) | (_, Field::Null) => return Ok(Field::Null),
+        (Field::Integer(a), Field::Integer(b)) => a.cmp(b),
+        (Field::Text(a), Field::Text(b)) => a.cmp(b),
+        (Field::Boolean(a), Field::Boolean(b)) => a.cmp(b),
+        _ => {
+            return Err(Error::Type(format!(
+                "Cannot compare {:?} with {:?}",
+                left, right
+            )));
+        }
+    };
+
+    let result = match op {
+        BinaryOp::Eq => ordering == Ordering::Equal,
+        BinaryOp::NotEq => ordering != Ordering::Equal,
+        BinaryOp::Lt => ordering == Ordering::Less,
+        BinaryOp::LtEq => ordering != Ordering::Greater,
+        BinaryOp::Gt => ordering == Ordering::Greater,
+        BinaryOp::GtEq => ordering != Ordering::Less,
+        _ => unreachable!("only comparison operators reach here"),
+    };
+    Ok(Field::Boolean(result))
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Literal(Field::Integer(v)) => write!(f, "{}", v),
+            Expr::Literal(Field::Boolean(v)) => write!(f, "{}", v),
+            Expr::Literal(Field::Text(v)) => write!(f, "'{}'", v.replace('\'', "''")),
+            Expr::Literal(Field::Null) => write!(f, "NULL"),
+            Expr::Column(name) => write!(f, "{}", name),
+            Expr::Not(inner) => write!(f, "NOT {}", inner),
+            Expr::Negate(inner) => write!(f, "-{}", inner),
+            Expr::Function { function, args } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", function, args.join(", "))
+            }
+            Expr::IsNull { expr, negated } => {
+                write!(f, "{} IS {}NULL", expr, if *negated { "NOT " } else { "" })
+            }
+            Expr::BinaryOp { left, op, right } => write!(f, "({} {} {})", left, op, right),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Concat => "||",
+        })
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Function::Upper => "UPPER",
+            Function::Lower => "LOWER",
+            Function::Length => "LENGTH",
+            Function::Abs => "ABS",
+            Function::Coalesce => "COALESCE",
+        })
+    }
+}
@@ -1,8 +1,11 @@
+pub mod expr;
 pub mod parser;
 
 use serde::Serialize;
 
-use crate::catalog::schema::Column;
+use crate::catalog::schema::{Column, DataType, ForeignKey};
+use crate::error::Error;
+use crate::sql::expr::Expr;
 use crate::storage::record::{Field, Row};
 
 #[derive(Debug)]
@@ -10,44 +13,156 @@ pub enum Command {
     CreateTable {
         name: String,
         columns: Vec<Column>,
+        unique: Vec<Vec<String>>,
+        checks: Vec<Expr>,
+        foreign_keys: Vec<ForeignKey>,
+        // IF NOT EXISTS: an existing table of that name is left as it is
+        if_not_exists: bool,
     },
     Insert {
         table_name: String,
-        row: Row,
+        // Target columns, empty when the values are positional
+        columns: Vec<String>,
+        rows: Vec<Row>,
+        on_conflict: Option<OnConflict>,
+        // RETURNING list; the command then yields the written rows as Data
+        returning: Option<Vec<SelectItem>>,
+    },
+    // INSERT INTO ... SELECT, `query` is always a Select
+    InsertSelect {
+        table_name: String,
+        columns: Vec<String>,
+        query: Box<Command>,
+        on_conflict: Option<OnConflict>,
+        returning: Option<Vec<SelectItem>>,
     },
     Select {
         table_name: String,
-        filter: Option<Filter>,
+        projection: Vec<SelectItem>,
+        filter: Option<Expr>,
         join: Option<JoinClause>,
     },
     Update {
         table_name: String,
-        // Column name and the new value
-        assignments: Vec<(String, Field)>,
-        filter: Option<Filter>,
+        // Column name and the expression computing its new value from the old row
+        assignments: Vec<(String, Expr)>,
+        filter: Option<Expr>,
+        returning: Option<Vec<SelectItem>>,
     },
     Delete {
         table_name: String,
-        filter: Option<Filter>,
+        filter: Option<Expr>,
+        // Deleted rows are returned as they were before the delete
+        returning: Option<Vec<SelectItem>>,
     },
+    // DROP TABLE [IF EXISTS] a, b: every table is checked before any is dropped
     DropTable {
+        table_names: Vec<String>,
+        // Missing tables are skipped instead of failing the statement
+        if_exists: bool,
+    },
+    AlterTable {
+        table_name: String,
+        operation: AlterTableOp,
+    },
+    // PRAGMA name [= value]: reads, or with a value sets, a database setting
+    Pragma {
+        name: String,
+        value: Option<String>,
+    },
+    // VACUUM [table]: compacts one table, or every table, into as few pages as it needs
+    Vacuum {
+        table_name: Option<String>,
+    },
+    // TRUNCATE [TABLE] a, b [RESTART IDENTITY]: empties the tables without visiting rows
+    Truncate {
+        table_names: Vec<String>,
+        // Also restart the AUTOINCREMENT sequences from 1
+        restart_identity: bool,
+    },
+    // SHOW TABLES: the name of every table
+    ShowTables,
+    // DESCRIBE table, or SHOW COLUMNS FROM table: one row per column
+    Describe {
+        table_name: String,
+    },
+    // SHOW CREATE TABLE table: the DDL that recreates the table
+    ShowCreateTable {
         table_name: String,
     },
 }
 
+impl Command {
+    /// Tables the command creates, changes or removes, a renamed table's new name included.
+    pub fn written_tables(&self) -> Vec<&str> {
+        match self {
+            Command::CreateTable { name, .. } => vec![name],
+            Command::Insert { table_name, .. }
+            | Command::InsertSelect { table_name, .. }
+            | Command::Update { table_name, .. }
+            | Command::Delete { table_name, .. } => vec![table_name],
+            Command::DropTable { table_names, .. } | Command::Truncate { table_names, .. } => {
+                table_names.iter().map(String::as_str).collect()
+            }
+            Command::AlterTable {
+                table_name,
+                operation: AlterTableOp::RenameTable { new_name },
+            } => vec![table_name, new_name],
+            Command::AlterTable { table_name, .. } => vec![table_name],
+            Command::Vacuum { table_name } => table_name.iter().map(String::as_str).collect(),
+            Command::Select { .. }
+            | Command::Pragma { .. }
+            | Command::ShowTables
+            | Command::Describe { .. }
+            | Command::ShowCreateTable { .. } => Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug)]
-pub enum Operator {
-    Eq,
-    NotEq,
-    GreaterThan,
-    LessThan,
+pub enum AlterTableOp {
+    AddColumn {
+        column: Column,
+    },
+    DropColumn {
+        column_name: String,
+    },
+    RenameColumn {
+        old_name: String,
+        new_name: String,
+    },
+    RenameTable {
+        new_name: String,
+    },
+    // Only widening conversions (see DataType::widens_to) are accepted
+    AlterColumnType {
+        column_name: String,
+        data_type: DataType,
+    },
 }
 
+/// The ON CONFLICT clause of an INSERT.
 #[derive(Debug)]
-pub struct Filter {
-    pub column_name: String,
-    pub operator: Operator,
-    pub value: Field,
+pub struct OnConflict {
+    // Columns of the primary key or UNIQUE constraint to check, empty to check them all
+    pub target: Vec<String>,
+    pub action: ConflictAction,
+}
+
+#[derive(Debug)]
+pub enum ConflictAction {
+    DoNothing,
+    // Expressions see the existing row's columns, and the proposed row as `excluded.col`
+    DoUpdate {
+        assignments: Vec<(String, Expr)>,
+        filter: Option<Expr>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum SelectItem {
+    Wildcard,
+    Expr { expr: Expr, alias: Option<String> },
 }
 
 #[derive(Debug)]
@@ -65,6 +180,33 @@ pub struct QueryResponse {
 
 #[derive(Serialize)]
 pub enum QueryResult {
-    Message(String),     // For CREATE, INSERT, UPDATE, DELETE
-    Data(QueryResponse), // For SELECT and JOIN
+    // For SELECT, JOIN and DML with RETURNING; a query with no matches still has its columns
+    Data(QueryResponse),
+    // For INSERT, UPDATE and DELETE. `last_insert_id` is the AUTOINCREMENT value of the
+    // last row an INSERT wrote, if the table has one
+    RowsAffected {
+        count: usize,
+        last_insert_id: Option<i32>,
+    },
+    // For CREATE, DROP and ALTER; `object` is the table that was changed
+    DdlOk {
+        object: String,
+        message: String,
+    },
+    // For input that contains no statement
+    Empty,
+    // A failed statement as reported to clients, see `Error::sqlstate`
+    Error {
+        sqlstate: String,
+        message: String,
+    },
+}
+
+impl From<Error> for QueryResult {
+    fn from(e: Error) -> Self {
+        QueryResult::Error {
+            sqlstate: e.sqlstate().to_string(),
+            message: e.to_string(),
+        }
+    }
 }
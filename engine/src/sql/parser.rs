@@ -1,206 +1,667 @@
-use crate::catalog::schema::{Column, DataType};
-use crate::sql::{Command, Filter, JoinClause, Operator};
+use crate::catalog::schema::{Column, DataType, ForeignKey, ReferentialAction};
+use crate::error::{CatalogKind, Error, Position, Result};
+use crate::sql::expr;
+use crate::sql::{AlterTableOp, Command, ConflictAction, JoinClause, OnConflict, SelectItem};
+use crate::storage::pager::PAGE_SIZE;
 use crate::storage::record::{Field, Row};
 use sqlparser::ast::{
-    BinaryOperator, ColumnDef, DataType as SQLDataType, Expr, JoinConstraint, JoinOperator,
-    SetExpr, Statement, TableFactor,
+    AlterColumnOperation, AlterTableOperation, BinaryOperator, CharacterLength, ColumnDef,
+    ColumnOption, ConflictTarget, DataType as SQLDataType, Expr, FunctionArg, FunctionArgExpr,
+    Ident, JoinConstraint, JoinOperator, OnConflictAction, OnInsert as SQLOnInsert,
+    ReferentialAction as SQLReferentialAction, Select, SelectItem as SQLSelectItem, SetExpr,
+    ShowCreateObject, Statement, TableConstraint, TableFactor, UnaryOperator,
 };
 use sqlparser::dialect::GenericDialect;
-use sqlparser::parser::Parser;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::Token;
 
-pub fn parse_sql(sql: &str) -> Result<Vec<Command>, String> {
+pub fn parse_sql(sql: &str) -> Result<Vec<Command>> {
     let dialect = GenericDialect {};
-    let ast = Parser::parse_sql(&dialect, sql).map_err(|e| e.to_string())?;
+    let mut parser = Parser::new(&dialect)
+        .try_with_sql(sql)
+        .map_err(convert_parser_error)?;
 
     let mut commands = Vec::new();
 
-    for statement in ast {
-        match statement {
-            Statement::CreateTable { name, columns, .. } => {
-                let table_name = name.to_string();
-                let mut my_columns = Vec::new();
+    // The same loop as `Parser::parse_statements`, except that statements sqlparser
+    // doesn't handle the way we need are parsed here first
+    let mut expecting_delimiter = false;
+    loop {
+        while parser.consume_token(&Token::SemiColon) {
+            expecting_delimiter = false;
+        }
+        if parser.peek_token().token == Token::EOF {
+            break;
+        }
+        if expecting_delimiter {
+            return parser
+                .expected("end of statement", parser.peek_token())
+                .map_err(convert_parser_error);
+        }
 
-                for col in columns {
-                    my_columns.push(convert_column(col)?);
-                }
+        match parse_extension(&mut parser)? {
+            Some(command) => commands.push(command),
+            None => {
+                let statement = parser.parse_statement().map_err(convert_parser_error)?;
+                convert_statement(statement, &mut commands)?;
+            }
+        }
+        expecting_delimiter = true;
+    }
 
-                commands.push(Command::CreateTable {
-                    name: table_name,
-                    columns: my_columns,
-                });
+    Ok(commands)
+}
+
+/// Parses the statements sqlparser doesn't support: `PRAGMA name [= value]`, where
+/// unlike sqlparser the value may also be a bare word such as FULL, `VACUUM [table]`
+/// and `TRUNCATE [TABLE] a, b [RESTART IDENTITY | CONTINUE IDENTITY]`.
+fn parse_extension(parser: &mut Parser) -> Result<Option<Command>> {
+    let Token::Word(word) = parser.peek_token().token else {
+        return Ok(None);
+    };
+    match word.keyword {
+        Keyword::PRAGMA => {
+            parser.next_token();
+            let name = parser
+                .parse_object_name(false)
+                .map_err(convert_parser_error)?
+                .to_string();
+            let value = if parser.consume_token(&Token::Eq) {
+                Some(parse_pragma_value(parser)?)
+            } else if parser.consume_token(&Token::LParen) {
+                let value = parse_pragma_value(parser)?;
+                parser
+                    .expect_token(&Token::RParen)
+                    .map_err(convert_parser_error)?;
+                Some(value)
+            } else {
+                None
+            };
+            Ok(Some(Command::Pragma { name, value }))
+        }
+        Keyword::VACUUM => {
+            parser.next_token();
+            let table_name = match parser.peek_token().token {
+                Token::Word(_) => Some(
+                    parser
+                        .parse_object_name(false)
+                        .map_err(convert_parser_error)?
+                        .to_string(),
+                ),
+                _ => None,
+            };
+            Ok(Some(Command::Vacuum { table_name }))
+        }
+        Keyword::TRUNCATE => {
+            parser.next_token();
+            let _ = parser.parse_keyword(Keyword::TABLE);
+            let table_names = parser
+                .parse_comma_separated(|p| p.parse_object_name(false))
+                .map_err(convert_parser_error)?
+                .iter()
+                .map(|name| name.to_string())
+                .collect();
+            // CONTINUE IDENTITY, the default, keeps the sequences going
+            let restart_identity = parser.parse_keyword(Keyword::RESTART);
+            let continue_identity = matches!(
+                parser.peek_token().token,
+                Token::Word(word) if word.value.eq_ignore_ascii_case("CONTINUE")
+            );
+            if restart_identity || continue_identity {
+                if continue_identity {
+                    parser.next_token();
+                }
+                parser
+                    .expect_keyword(Keyword::IDENTITY)
+                    .map_err(convert_parser_error)?;
             }
+            Ok(Some(Command::Truncate {
+                table_names,
+                restart_identity,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
 
-            Statement::Delete {
-                selection, from, ..
-            } => {
-                for table in from {
-                    if let Some(selection) = &selection {
-                        let filter = parse_selection(&selection).unwrap();
-                        commands.push(Command::Delete {
-                            table_name: table.to_string(),
-                            filter: filter,
-                        });
+fn parse_pragma_value(parser: &mut Parser) -> Result<String> {
+    let token = parser.next_token();
+    match token.token {
+        Token::Word(word) => Ok(word.value),
+        Token::Number(n, _) => Ok(n),
+        Token::SingleQuotedString(s) => Ok(s),
+        _ => parser
+            .expected("a PRAGMA value", token)
+            .map_err(convert_parser_error),
+    }
+}
+
+/// Converts one parsed statement, appending the commands it stands for.
+fn convert_statement(statement: Statement, commands: &mut Vec<Command>) -> Result<()> {
+    match statement {
+        Statement::CreateTable {
+            name,
+            columns,
+            constraints,
+            if_not_exists,
+            ..
+        } => {
+            let table_name = name.to_string();
+            let mut my_columns = Vec::new();
+            let mut table_constraints = TableConstraints::default();
+
+            for col in columns {
+                my_columns.push(convert_column(col, &mut table_constraints)?);
+            }
+            // Two column-level keys are two keys, not one composite key
+            if my_columns.iter().filter(|c| c.is_primary).count() > 1 {
+                return Err(Error::catalog(
+                    CatalogKind::InvalidDefinition,
+                    format!("Table {} has more than one PRIMARY KEY", table_name),
+                ));
+            }
+            for constraint in constraints {
+                match constraint {
+                    // PRIMARY KEY (a, b) flags its columns, like the column-level form
+                    TableConstraint::Unique {
+                        columns,
+                        is_primary: true,
+                        ..
+                    } => {
+                        if my_columns.iter().any(|c: &Column| c.is_primary) {
+                            return Err(Error::catalog(
+                                CatalogKind::InvalidDefinition,
+                                format!("Table {} has more than one PRIMARY KEY", table_name),
+                            ));
+                        }
+                        for ident in columns {
+                            let column = my_columns
+                                .iter_mut()
+                                .find(|c| c.name == ident.value)
+                                .ok_or_else(|| {
+                                    Error::catalog(
+                                        CatalogKind::UndefinedColumn,
+                                        format!("PRIMARY KEY column {} not found", ident.value),
+                                    )
+                                })?;
+                            column.is_primary = true;
+                            column.is_nullable = false;
+                        }
                     }
+                    other => table_constraints.add(other)?,
                 }
             }
 
-            Statement::Drop {
-                object_type, names, ..
-            } => {
-                if matches!(object_type, sqlparser::ast::ObjectType::Table) {
-                    let table_name = names.first().unwrap().to_string();
-                    commands.push(Command::DropTable { table_name });
-                } else {
-                    return Err("Only DROP TABLE is supported".to_string());
-                }
+            commands.push(Command::CreateTable {
+                name: table_name,
+                columns: my_columns,
+                unique: table_constraints.unique,
+                checks: table_constraints.checks,
+                foreign_keys: table_constraints.foreign_keys,
+                if_not_exists,
+            });
+        }
+
+        Statement::Delete {
+            selection,
+            from,
+            returning,
+            ..
+        } => {
+            let returning = returning.as_deref().map(convert_select_items).transpose()?;
+            // Without a WHERE clause every row is deleted
+            let filter = selection.as_ref().map(convert_expr).transpose()?;
+            for table in from {
+                commands.push(Command::Delete {
+                    table_name: table.to_string(),
+                    filter: filter.clone(),
+                    returning: returning.clone(),
+                });
             }
+        }
 
-            Statement::Update {
-                table,
-                assignments,
-                selection,
-                ..
-            } => {
-                let table_name = table.to_string();
-
-                // 1. Map assignments (SET col = val)
-                let mut update_list = Vec::new();
-                for assignment in assignments {
-                    let col_name = assignment.id.first().unwrap().value.clone();
-                    let val = convert_expr_to_field(&assignment.value)?;
-                    update_list.push((col_name, val));
+        Statement::Drop {
+            object_type,
+            names,
+            if_exists,
+            ..
+        } => {
+            if matches!(object_type, sqlparser::ast::ObjectType::Table) {
+                if names.is_empty() {
+                    return Err(Error::parse("DROP TABLE needs a table name"));
                 }
+                commands.push(Command::DropTable {
+                    table_names: names.iter().map(|name| name.to_string()).collect(),
+                    if_exists,
+                });
+            } else {
+                return Err(Error::Unsupported(
+                    "Only DROP TABLE is supported".to_string(),
+                ));
+            }
+        }
+
+        Statement::Update {
+            table,
+            assignments,
+            selection,
+            returning,
+            ..
+        } => {
+            let table_name = table.to_string();
+
+            // 1. Map assignments (SET col = val)
+            let mut update_list = Vec::new();
+            for assignment in assignments {
+                let col_name = assignment
+                    .id
+                    .last()
+                    .ok_or_else(|| Error::parse("SET needs a column name"))?
+                    .value
+                    .clone();
+                let val = convert_expr(&assignment.value)?;
+                update_list.push((col_name, val));
+            }
+
+            // 2. Map WHERE clause
+            let filter = selection.as_ref().map(convert_expr).transpose()?;
+
+            commands.push(Command::Update {
+                table_name,
+                assignments: update_list,
+                filter,
+                returning: returning.as_deref().map(convert_select_items).transpose()?,
+            });
+        }
 
-                // 2. Map WHERE clause
-                let filter: Option<Filter> = match &selection {
-                    Some(sel) => parse_selection(sel)?,
-                    None => None,
+        Statement::AlterTable {
+            name, operations, ..
+        } => {
+            let table_name = name.to_string();
+
+            for operation in operations {
+                let operation = match operation {
+                    AlterTableOperation::AddColumn { column_def, .. } => {
+                        let mut column_constraints = TableConstraints::default();
+                        let column = convert_column(column_def, &mut column_constraints)?;
+                        if !column_constraints.is_empty() {
+                            return Err(Error::Unsupported(
+                                "Constraints on added columns are not supported".to_string(),
+                            ));
+                        }
+                        AlterTableOp::AddColumn { column }
+                    }
+                    AlterTableOperation::DropColumn { column_name, .. } => {
+                        AlterTableOp::DropColumn {
+                            column_name: column_name.value,
+                        }
+                    }
+                    AlterTableOperation::RenameColumn {
+                        old_column_name,
+                        new_column_name,
+                    } => AlterTableOp::RenameColumn {
+                        old_name: old_column_name.value,
+                        new_name: new_column_name.value,
+                    },
+                    AlterTableOperation::RenameTable { table_name } => AlterTableOp::RenameTable {
+                        new_name: table_name.to_string(),
+                    },
+                    AlterTableOperation::AlterColumn {
+                        column_name,
+                        op: AlterColumnOperation::SetDataType { data_type, .. },
+                    } => AlterTableOp::AlterColumnType {
+                        column_name: column_name.value,
+                        data_type: convert_data_type(&data_type)?,
+                    },
+                    other => {
+                        return Err(Error::Unsupported(format!(
+                            "Unsupported ALTER TABLE operation: {}",
+                            other
+                        )));
+                    }
                 };
 
-                commands.push(Command::Update {
-                    table_name,
-                    assignments: update_list,
-                    filter,
+                commands.push(Command::AlterTable {
+                    table_name: table_name.clone(),
+                    operation,
                 });
             }
+        }
 
-            Statement::Insert {
-                table_name, source, ..
-            } => {
-                let table = table_name.to_string();
+        Statement::Insert {
+            table_name,
+            columns,
+            source,
+            returning,
+            on,
+            ..
+        } => {
+            let table = table_name.to_string();
+            let columns: Vec<String> = columns.into_iter().map(|c| c.value).collect();
+            let returning = returning.as_deref().map(convert_select_items).transpose()?;
+            let on_conflict = on.map(convert_on_conflict).transpose()?;
 
-                if let Some(source) = source {
-                    if let SetExpr::Values(values) = source.body.as_ref() {
+            if let Some(source) = source {
+                match source.body.as_ref() {
+                    SetExpr::Values(values) => {
+                        let mut rows = Vec::new();
                         for row_values in &values.rows {
                             let mut fields = Vec::new();
                             for expr in row_values {
                                 fields.push(convert_expr_to_field(expr)?);
                             }
-                            commands.push(Command::Insert {
-                                table_name: table.clone(),
-                                row: Row { fields },
-                            });
+                            rows.push(Row { fields });
                         }
-                    } else {
-                        return Err("Unsupported INSERT format".to_string());
+                        commands.push(Command::Insert {
+                            table_name: table,
+                            columns,
+                            rows,
+                            on_conflict,
+                            returning,
+                        });
+                    }
+                    // INSERT INTO t SELECT ...
+                    SetExpr::Select(select) => commands.push(Command::InsertSelect {
+                        table_name: table,
+                        columns,
+                        query: Box::new(convert_select(select)?),
+                        on_conflict,
+                        returning,
+                    }),
+                    _ => {
+                        return Err(Error::Unsupported("Unsupported INSERT format".to_string()));
                     }
-                } else {
-                    return Err("INSERT statement missing values".to_string());
                 }
+            } else {
+                return Err(Error::parse("INSERT statement missing values"));
             }
-            Statement::Query(query) => {
-                if let SetExpr::Select(select) = *query.body {
-                    // 1. Get the Primary (Left) Table
-                    let first_from = select.from.first().ok_or("Missing FROM clause")?;
-                    let left_table = match &first_from.relation {
-                        TableFactor::Table { name, .. } => name.to_string(),
-                        _ => return Err("Unsupported table reference".to_string()),
-                    };
-
-                    // 2. Check for JOINs
-                    let mut join_info = None;
-                    if let Some(join) = first_from.joins.first() {
-                        let right_table = match &join.relation {
-                            TableFactor::Table { name, .. } => name.to_string(),
-                            _ => return Err("Unsupported JOIN table".to_string()),
-                        };
-
-                        // 3. Extract the ON condition (e.g., tableA.id = tableB.user_id)
-                        if let JoinOperator::Inner(JoinConstraint::On(Expr::BinaryOp {
-                            left,
-                            op,
-                            right,
-                        })) = &join.join_operator
-                        {
-                            if let BinaryOperator::Eq = op {
-                                // Extract column names from expressions like 'users.id'
-                                let left_column = extract_column_name(left)?;
-                                let right_column = extract_column_name(right)?;
-
-                                join_info = Some(JoinClause {
-                                    right_table,
-                                    left_column,
-                                    right_column,
-                                });
-                            }
-                        }
-                    }
+        }
+        Statement::Query(query) => {
+            if let SetExpr::Select(select) = *query.body {
+                commands.push(convert_select(&select)?);
+            }
+        }
 
-                    let filter: Option<Filter> = match &select.selection {
-                        Some(sel) => parse_selection(sel)?,
-                        None => None,
-                    };
+        Statement::ShowTables {
+            db_name: None,
+            filter: None,
+            ..
+        } => commands.push(Command::ShowTables),
 
-                    commands.push(Command::Select {
-                        table_name: left_table,
-                        join: join_info,
-                        filter,
-                    });
-                }
-            }
+        Statement::ShowColumns {
+            table_name,
+            filter: None,
+            ..
+        }
+        | Statement::ExplainTable { table_name, .. } => commands.push(Command::Describe {
+            table_name: table_name.to_string(),
+        }),
+
+        Statement::ShowCreate {
+            obj_type: ShowCreateObject::Table,
+            obj_name,
+        } => commands.push(Command::ShowCreateTable {
+            table_name: obj_name.to_string(),
+        }),
 
-            _ => return Err("Unsupported SQL statement".to_string()),
+        statement @ (Statement::ShowTables { .. }
+        | Statement::ShowColumns { .. }
+        | Statement::ShowCreate { .. }) => {
+            return Err(Error::Unsupported(format!(
+                "Unsupported SHOW statement: {}",
+                statement
+            )));
         }
+
+        _ => return Err(Error::Unsupported("Unsupported SQL statement".to_string())),
     }
+    Ok(())
+}
 
-    Ok(commands)
+fn convert_on_conflict(on: SQLOnInsert) -> Result<OnConflict> {
+    let SQLOnInsert::OnConflict(on_conflict) = on else {
+        return Err(Error::Unsupported(
+            "Only ON CONFLICT is supported".to_string(),
+        ));
+    };
+    let target = match on_conflict.conflict_target {
+        None => Vec::new(),
+        Some(ConflictTarget::Columns(columns)) => idents_to_names(&columns),
+        Some(ConflictTarget::OnConstraint(name)) => {
+            return Err(Error::Unsupported(format!(
+                "ON CONFLICT ON CONSTRAINT {} is not supported",
+                name
+            )));
+        }
+    };
+
+    let action = match on_conflict.action {
+        OnConflictAction::DoNothing => ConflictAction::DoNothing,
+        OnConflictAction::DoUpdate(update) => {
+            if target.is_empty() {
+                return Err(Error::parse(
+                    "ON CONFLICT DO UPDATE needs a conflict target",
+                ));
+            }
+            let mut assignments = Vec::new();
+            for assignment in &update.assignments {
+                let col_name = assignment
+                    .id
+                    .last()
+                    .ok_or_else(|| Error::parse("Missing column"))?
+                    .value
+                    .clone();
+                assignments.push((col_name, convert_expr(&assignment.value)?));
+            }
+            ConflictAction::DoUpdate {
+                assignments,
+                filter: update.selection.as_ref().map(convert_expr).transpose()?,
+            }
+        }
+    };
+
+    Ok(OnConflict { target, action })
 }
 
-fn parse_selection(selection: &Expr) -> Result<Option<Filter>, String> {
-    let filter = {
-        if let Expr::BinaryOp { left, op, right } = selection {
-            let col_name = extract_column_name(&left)?;
-            let op_type = match op {
-                BinaryOperator::Eq => Operator::Eq,
-                BinaryOperator::NotEq => Operator::NotEq,
-                BinaryOperator::Gt => Operator::GreaterThan,
-                BinaryOperator::Lt => Operator::LessThan,
-                _ => return Err("Unsupported operator".to_string()),
-            };
-            let val = convert_expr_to_field(&right)?;
+fn convert_select(select: &Select) -> Result<Command> {
+    // 1. Get the Primary (Left) Table
+    let first_from = select
+        .from
+        .first()
+        .ok_or_else(|| Error::parse("Missing FROM clause"))?;
+    let left_table = match &first_from.relation {
+        TableFactor::Table { name, .. } => name.to_string(),
+        _ => {
+            return Err(Error::Unsupported(
+                "Unsupported table reference".to_string(),
+            ));
+        }
+    };
 
-            Some(Filter {
-                column_name: col_name,
-                operator: op_type,
-                value: val,
-            })
-        } else {
-            None
+    // 2. Check for JOINs
+    let mut join_info = None;
+    if let Some(join) = first_from.joins.first() {
+        let right_table = match &join.relation {
+            TableFactor::Table { name, .. } => name.to_string(),
+            _ => return Err(Error::Unsupported("Unsupported JOIN table".to_string())),
+        };
+
+        // 3. Extract the ON condition (e.g., tableA.id = tableB.user_id)
+        if let JoinOperator::Inner(JoinConstraint::On(Expr::BinaryOp { left, op, right })) =
+            &join.join_operator
+            && let BinaryOperator::Eq = op
+        {
+            // Extract column names from expressions like 'users.id'
+            let left_column = extract_column_name(left)?;
+            let right_column = extract_column_name(right)?;
+
+            join_info = Some(JoinClause {
+                right_table,
+                left_column,
+                right_column,
+            });
         }
+    }
+
+    let filter = select.selection.as_ref().map(convert_expr).transpose()?;
+
+    Ok(Command::Select {
+        table_name: left_table,
+        projection: convert_select_items(&select.projection)?,
+        join: join_info,
+        filter,
+    })
+}
+
+/// Converts a SELECT list, also used for RETURNING.
+fn convert_select_items(items: &[SQLSelectItem]) -> Result<Vec<SelectItem>> {
+    let mut projection = Vec::new();
+    for item in items {
+        projection.push(match item {
+            SQLSelectItem::Wildcard(_) => SelectItem::Wildcard,
+            SQLSelectItem::UnnamedExpr(expr) => SelectItem::Expr {
+                expr: convert_expr(expr)?,
+                alias: None,
+            },
+            SQLSelectItem::ExprWithAlias { expr, alias } => SelectItem::Expr {
+                expr: convert_expr(expr)?,
+                alias: Some(alias.value.clone()),
+            },
+            other => {
+                return Err(Error::Unsupported(format!(
+                    "Unsupported select item: {}",
+                    other
+                )));
+            }
+        });
+    }
+    Ok(projection)
+}
+
+/// Turns a sqlparser error into `Error::Parse`, recovering the position that
+/// sqlparser only reports inside the message ("... at Line: 1, Column 8").
+fn convert_parser_error(error: ParserError) -> Error {
+    let message = match error {
+        ParserError::TokenizerError(message) | ParserError::ParserError(message) => message,
+        ParserError::RecursionLimitExceeded => "Recursion limit exceeded".to_string(),
     };
 
-    Ok(filter)
+    if let Some((text, location)) = message.rsplit_once(" at Line: ")
+        && let Some((line, column)) = location.split_once(", Column ")
+        && let (Ok(line), Ok(column)) = (line.parse(), column.parse())
+    {
+        return Error::Parse {
+            message: text.to_string(),
+            position: Some(Position { line, column }),
+        };
+    }
+    Error::parse(message)
+}
+
+fn convert_data_type(data_type: &SQLDataType) -> Result<DataType> {
+    match data_type {
+        SQLDataType::Int(_) | SQLDataType::Integer(_) => Ok(DataType::Integer),
+        SQLDataType::Boolean => Ok(DataType::Boolean),
+        SQLDataType::Varchar(Some(CharacterLength::IntegerLength { length, .. })) => {
+            match usize::try_from(*length) {
+                Ok(length) if length > 0 && length < PAGE_SIZE => Ok(DataType::Text(length)),
+                _ => Err(Error::catalog(
+                    CatalogKind::InvalidDefinition,
+                    format!("Invalid VARCHAR length {}", length),
+                )),
+            }
+        }
+        SQLDataType::Text => Ok(DataType::Text(255)),
+        _ => Err(Error::Unsupported(format!(
+            "Unsupported data type: {:?}",
+            data_type
+        ))),
+    }
+}
+
+/// Table-level constraints collected from both column options and constraint clauses.
+#[derive(Default)]
+struct TableConstraints {
+    unique: Vec<Vec<String>>,
+    checks: Vec<expr::Expr>,
+    foreign_keys: Vec<ForeignKey>,
+}
+
+impl TableConstraints {
+    fn is_empty(&self) -> bool {
+        self.unique.is_empty() && self.checks.is_empty() && self.foreign_keys.is_empty()
+    }
+
+    fn add(&mut self, constraint: TableConstraint) -> Result<()> {
+        match constraint {
+            TableConstraint::Unique {
+                columns,
+                is_primary: false,
+                ..
+            } => self.unique.push(idents_to_names(&columns)),
+            TableConstraint::Check { expr, .. } => self.checks.push(convert_expr(&expr)?),
+            TableConstraint::ForeignKey {
+                columns,
+                foreign_table,
+                referred_columns,
+                on_delete,
+                on_update,
+                ..
+            } => self.foreign_keys.push(convert_foreign_key(
+                idents_to_names(&columns),
+                foreign_table.to_string(),
+                &referred_columns,
+                on_delete,
+                on_update,
+            )?),
+            other => {
+                return Err(Error::Unsupported(format!(
+                    "Unsupported table constraint: {}",
+                    other
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn idents_to_names(idents: &[Ident]) -> Vec<String> {
+    idents.iter().map(|i| i.value.clone()).collect()
 }
 
-fn convert_column(col: ColumnDef) -> Result<Column, String> {
-    let data_type = match col.data_type {
-        SQLDataType::Int(_) | SQLDataType::Integer(_) => DataType::Integer,
-        SQLDataType::Boolean => DataType::Boolean,
-        SQLDataType::Varchar(Some(len)) => DataType::Text(len.to_string().parse().unwrap()),
-        SQLDataType::Text => DataType::Text(255),
-        _ => return Err(format!("Unsupported data type: {:?}", col.data_type)),
+fn convert_foreign_key(
+    columns: Vec<String>,
+    foreign_table: String,
+    referred_columns: &[Ident],
+    on_delete: Option<SQLReferentialAction>,
+    on_update: Option<SQLReferentialAction>,
+) -> Result<ForeignKey> {
+    let convert_action = |action: Option<SQLReferentialAction>, event: &str| match action {
+        None | Some(SQLReferentialAction::Restrict) | Some(SQLReferentialAction::NoAction) => {
+            Ok(ReferentialAction::Restrict)
+        }
+        Some(SQLReferentialAction::Cascade) => Ok(ReferentialAction::Cascade),
+        Some(SQLReferentialAction::SetNull) => Ok(ReferentialAction::SetNull),
+        Some(other) => Err(Error::Unsupported(format!(
+            "ON {} {} is not supported",
+            event, other
+        ))),
     };
 
+    Ok(ForeignKey {
+        columns,
+        foreign_table,
+        referred_columns: idents_to_names(referred_columns),
+        on_delete: convert_action(on_delete, "DELETE")?,
+        on_update: convert_action(on_update, "UPDATE")?,
+    })
+}
+
+fn convert_column(col: ColumnDef, constraints: &mut TableConstraints) -> Result<Column> {
+    let data_type = convert_data_type(&col.data_type)?;
+    let name = col.name.value.clone();
+
     // Check if it's a primary key
     let is_primary = col.options.iter().any(|opt| {
         matches!(
@@ -218,36 +679,187 @@ fn convert_column(col: ColumnDef) -> Result<Column, String> {
         )
     });
 
+    let default = match col.options.iter().find_map(|opt| match &opt.option {
+        ColumnOption::Default(expr) => Some(expr),
+        _ => None,
+    }) {
+        Some(expr) => {
+            let value = convert_expr_to_field(expr)?;
+            if value != Field::Null && !data_type.accepts(&value) {
+                return Err(Error::Type(format!(
+                    "Default value {:?} does not match type {:?} of column '{}'",
+                    value, data_type, col.name
+                )));
+            }
+            data_type.check_length(&value, &name)?;
+            Some(value)
+        }
+        None => None,
+    };
+
+    let is_not_null = col
+        .options
+        .iter()
+        .any(|opt| matches!(opt.option, ColumnOption::NotNull));
+
+    for opt in col.options {
+        match opt.option {
+            ColumnOption::Unique {
+                is_primary: false, ..
+            } => constraints.unique.push(vec![name.clone()]),
+            ColumnOption::Check(expr) => constraints.checks.push(convert_expr(&expr)?),
+            ColumnOption::ForeignKey {
+                foreign_table,
+                referred_columns,
+                on_delete,
+                on_update,
+                ..
+            } => constraints.foreign_keys.push(convert_foreign_key(
+                vec![name.clone()],
+                foreign_table.to_string(),
+                &referred_columns,
+                on_delete,
+                on_update,
+            )?),
+            _ => {}
+        }
+    }
+
     Ok(Column {
-        name: col.name.to_string(),
+        name,
         data_type,
         is_primary,
         is_autoincrement,
+        is_nullable: !is_primary && !is_not_null,
+        default,
     })
 }
 
-fn convert_expr_to_field(expr: &Expr) -> Result<Field, String> {
+/// Converts a SQL expression into one the engine can evaluate against a row.
+fn convert_expr(sql_expr: &Expr) -> Result<expr::Expr> {
+    match sql_expr {
+        Expr::Identifier(ident) => Ok(expr::Expr::Column(ident.value.clone())),
+        // EXCLUDED.col is the row proposed for insertion in ON CONFLICT DO UPDATE
+        Expr::CompoundIdentifier(parts)
+            if parts.len() == 2 && parts[0].value.eq_ignore_ascii_case("excluded") =>
+        {
+            Ok(expr::Expr::Column(format!("excluded.{}", parts[1].value)))
+        }
+        // Keep the table so joined tables sharing a column name can be told apart
+        Expr::CompoundIdentifier(parts) if parts.len() >= 2 => {
+            let table = &parts[parts.len() - 2].value;
+            let column = &parts[parts.len() - 1].value;
+            Ok(expr::Expr::Column(format!("{}.{}", table, column)))
+        }
+        Expr::Nested(inner) => convert_expr(inner),
+        Expr::IsNull(inner) => Ok(expr::Expr::IsNull {
+            expr: Box::new(convert_expr(inner)?),
+            negated: false,
+        }),
+        Expr::IsNotNull(inner) => Ok(expr::Expr::IsNull {
+            expr: Box::new(convert_expr(inner)?),
+            negated: true,
+        }),
+        Expr::UnaryOp {
+            op: UnaryOperator::Not,
+            expr: inner,
+        } => Ok(expr::Expr::Not(Box::new(convert_expr(inner)?))),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr: inner,
+        } => match convert_expr(inner)? {
+            expr::Expr::Literal(Field::Integer(i)) => Ok(expr::Expr::Literal(Field::Integer(-i))),
+            other => Ok(expr::Expr::Negate(Box::new(other))),
+        },
+        Expr::UnaryOp {
+            op: UnaryOperator::Plus,
+            expr: inner,
+        } => convert_expr(inner),
+        Expr::Function(function) => {
+            let name = function.name.to_string();
+            let func = expr::Function::from_name(&name)
+                .ok_or_else(|| Error::Unsupported(format!("Unknown function: {}", name)))?;
+            let mut args = Vec::new();
+            for arg in &function.args {
+                match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => {
+                        args.push(convert_expr(arg)?)
+                    }
+                    other => {
+                        return Err(Error::Unsupported(format!(
+                            "Unsupported function argument: {}",
+                            other
+                        )));
+                    }
+                }
+            }
+            func.check_arity(args.len())?;
+            Ok(expr::Expr::Function {
+                function: func,
+                args,
+            })
+        }
+        Expr::BinaryOp { left, op, right } => {
+            let op = match op {
+                BinaryOperator::Eq => expr::BinaryOp::Eq,
+                BinaryOperator::NotEq => expr::BinaryOp::NotEq,
+                BinaryOperator::Lt => expr::BinaryOp::Lt,
+                BinaryOperator::LtEq => expr::BinaryOp::LtEq,
+                BinaryOperator::Gt => expr::BinaryOp::Gt,
+                BinaryOperator::GtEq => expr::BinaryOp::GtEq,
+                BinaryOperator::And => expr::BinaryOp::And,
+                BinaryOperator::Or => expr::BinaryOp::Or,
+                BinaryOperator::Plus => expr::BinaryOp::Plus,
+                BinaryOperator::Minus => expr::BinaryOp::Minus,
+                BinaryOperator::Multiply => expr::BinaryOp::Multiply,
+                BinaryOperator::Divide => expr::BinaryOp::Divide,
+                BinaryOperator::Modulo => expr::BinaryOp::Modulo,
+                BinaryOperator::StringConcat => expr::BinaryOp::Concat,
+                _ => return Err(Error::Unsupported(format!("Unsupported operator: {}", op))),
+            };
+            Ok(expr::Expr::BinaryOp {
+                left: Box::new(convert_expr(left)?),
+                op,
+                right: Box::new(convert_expr(right)?),
+            })
+        }
+        _ => Ok(expr::Expr::Literal(convert_expr_to_field(sql_expr)?)),
+    }
+}
+
+fn convert_expr_to_field(expr: &Expr) -> Result<Field> {
     match expr {
         Expr::Value(sqlparser::ast::Value::Number(n, _)) => {
             if let Ok(i) = n.parse::<i32>() {
                 Ok(Field::Integer(i))
             } else {
-                Err(format!("Invalid integer: {}", n))
+                Err(Error::Data(format!("Invalid integer: {}", n)))
             }
         }
         Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) => Ok(Field::Text(s.clone())),
         Expr::Value(sqlparser::ast::Value::Boolean(b)) => Ok(Field::Boolean(*b)),
-        _ => Err(format!("Unsupported expression type: {:?}", expr)),
+        Expr::Value(sqlparser::ast::Value::Null) => Ok(Field::Null),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => match convert_expr_to_field(expr)? {
+            Field::Integer(i) => Ok(Field::Integer(-i)),
+            other => Err(Error::Type(format!("Cannot negate {:?}", other))),
+        },
+        _ => Err(Error::Unsupported(format!(
+            "Unsupported expression type: {:?}",
+            expr
+        ))),
     }
 }
 
-fn extract_column_name(expr: &Expr) -> Result<String, String> {
-    match expr {
-        Expr::Identifier(ident) => Ok(ident.value.clone()),
-        Expr::CompoundIdentifier(parts) => {
-            // We just take the last part (the column name)
-            Ok(parts.last().unwrap().value.clone())
-        }
-        _ => Err(format!("Expected column name, found {:?}", expr)),
-    }
+fn extract_column_name(expr: &Expr) -> Result<String> {
+    let name = match expr {
+        Expr::Identifier(ident) => Some(&ident.value),
+        // We just take the last part (the column name)
+        Expr::CompoundIdentifier(parts) => parts.last().map(|last| &last.value),
+        _ => None,
+    };
+    name.cloned()
+        .ok_or_else(|| Error::parse(format!("Expected column name, found {:?}", expr)))
 }
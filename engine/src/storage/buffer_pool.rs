@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::{Error, Result};
+use crate::storage::pager::{PAGE_SIZE, Page};
+
+/// Number of pages cached when no capacity is given: 1 MiB.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// A buffer pool shared by every pager of a database.
+pub type SharedPool = Arc<Mutex<BufferPool>>;
+
+/// Identifies a cached page: the path of its file and its index in the file.
+type PageKey = (String, usize);
+
+struct Frame {
+    key: PageKey,
+    // Shared with the `PinnedPage`s reading it; a write gives the frame a new copy
+    page: Arc<Page>,
+    // Pinned frames are in use and can't be evicted
+    pin_count: u32,
+    // Changed since it was read, so it must be written back before eviction
+    dirty: bool,
+    // Second chance bit for the clock
+    referenced: bool,
+}
+
+/// Counters describing how well the pool is doing.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    // Dirty pages written back to their file, on eviction or flush
+    pub writes: u64,
+    // Files synced to disk by a flush
+    pub syncs: u64,
+}
+
+/// A fixed number of page frames in front of the table files, evicted with the
+/// clock algorithm. Writes only mark a frame dirty; dirty pages reach the file
+/// when they are evicted or flushed.
+pub struct BufferPool {
+    frames: Vec<Frame>,
+    page_table: HashMap<PageKey, usize>,
+    capacity: usize,
+    hand: usize,
+    stats: PoolStats,
+}
+
+impl BufferPool {
+    /// A pool of `capacity` frames; at least one, so every page can be loaded.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            frames: Vec::with_capacity(capacity),
+            page_table: HashMap::new(),
+            capacity,
+            hand: 0,
+            stats: PoolStats::default(),
+        }
+    }
+
+    pub fn shared(capacity: usize) -> SharedPool {
+        Arc::new(Mutex::new(Self::new(capacity)))
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+
+    /// Pins the frame holding page `page_index` of `path`, calling `load` to read
+    /// the page on a miss. Every fetch must be matched by an `unpin`.
+    pub fn fetch(
+        &mut self,
+        path: &str,
+        page_index: usize,
+        load: impl FnOnce() -> Result<Page>,
+    ) -> Result<usize> {
+        if let Some(&frame_id) = self.page_table.get(&(path.to_string(), page_index)) {
+            self.stats.hits += 1;
+            let frame = &mut self.frames[frame_id];
+            frame.pin_count += 1;
+            frame.referenced = true;
+            return Ok(frame_id);
+        }
+
+        self.stats.misses += 1;
+        let page = load()?;
+        let frame = Frame {
+            key: (path.to_string(), page_index),
+            page: Arc::new(page),
+            pin_count: 1,
+            dirty: false,
+            referenced: true,
+        };
+
+        let frame_id = if self.frames.len() < self.capacity {
+            self.frames.push(frame);
+            self.frames.len() - 1
+        } else {
+            let victim = self.find_victim()?;
+            if self.frames[victim].dirty {
+                self.write_back(victim)?;
+            }
+            self.stats.evictions += 1;
+            self.page_table.remove(&self.frames[victim].key);
+            self.frames[victim] = frame;
+            victim
+        };
+        self.page_table
+            .insert((path.to_string(), page_index), frame_id);
+        Ok(frame_id)
+    }
+
+    pub fn page(&self, frame_id: usize) -> &Page {
+        &self.frames[frame_id].page
+    }
+
+    pub fn page_mut(&mut self, frame_id: usize) -> &mut Page {
+        Arc::make_mut(&mut self.frames[frame_id].page)
+    }
+
+    /// Releases a pin taken by `fetch`, recording whether the page was changed.
+    pub fn unpin(&mut self, frame_id: usize, dirty: bool) {
+        let frame = &mut self.frames[frame_id];
+        frame.pin_count = frame.pin_count.saturating_sub(1);
+        frame.dirty |= dirty;
+    }
+
+    /// The length `path` will have once its dirty pages are written, if any of
+    /// them lie past `on_disk`.
+    pub fn file_length(&self, path: &str, on_disk: u64) -> u64 {
+        self.frames
+            .iter()
+            .filter(|frame| frame.dirty && frame.key.0 == path)
+            .map(|frame| (frame.key.1 as u64 + 1) * PAGE_SIZE as u64)
+            .fold(on_disk, u64::max)
+    }
+
+    /// Writes every dirty page of `path`, then syncs the file if `sync` is set.
+    pub fn flush_file(&mut self, path: &str, sync: bool) -> Result<()> {
+        let dirty: Vec<usize> = (0..self.frames.len())
+            .filter(|&i| self.frames[i].dirty && self.frames[i].key.0 == path)
+            .collect();
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = open_for_write(path)?;
+        for frame_id in dirty {
+            self.write_frame(&mut file, frame_id)?;
+        }
+        if sync {
+            file.sync_all()?;
+            self.stats.syncs += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes every dirty page in the pool, syncing each file written to if `sync` is set.
+    pub fn flush_all(&mut self, sync: bool) -> Result<()> {
+        let mut paths: Vec<String> = self
+            .frames
+            .iter()
+            .filter(|frame| frame.dirty)
+            .map(|frame| frame.key.0.clone())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        for path in paths {
+            self.flush_file(&path, sync)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets every page of `path`, dirty or not. Used when the file is removed
+    /// or replaced, so the next read goes to the new file.
+    pub fn discard_file(&mut self, path: &str) {
+        for frame in &mut self.frames {
+            if frame.key.0 == path {
+                // An empty key never matches a lookup, leaving the frame free for reuse
+                self.page_table.remove(&frame.key);
+                frame.key = (String::new(), 0);
+                frame.dirty = false;
+                frame.referenced = false;
+            }
+        }
+    }
+
+    /// Forgets one page of `path`, e.g. when it is cut off the end of the file.
+    pub fn discard_page(&mut self, path: &str, page_index: usize) {
+        if let Some(frame_id) = self.page_table.remove(&(path.to_string(), page_index)) {
+            let frame = &mut self.frames[frame_id];
+            frame.key = (String::new(), 0);
+            frame.dirty = false;
+            frame.referenced = false;
+        }
+    }
+
+    /// Sweeps the clock for an unpinned frame, giving referenced frames a second chance.
+    fn find_victim(&mut self) -> Result<usize> {
+        for _ in 0..2 * self.frames.len() {
+            let frame_id = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            let frame = &mut self.frames[frame_id];
+            if frame.pin_count > 0 {
+                continue;
+            }
+            if frame.referenced {
+                frame.referenced = false;
+                continue;
+            }
+            return Ok(frame_id);
+        }
+        Err(Error::Io(io::Error::other(
+            "Buffer pool exhausted: every page is pinned",
+        )))
+    }
+
+    fn write_back(&mut self, frame_id: usize) -> Result<()> {
+        let mut file = open_for_write(&self.frames[frame_id].key.0)?;
+        self.write_frame(&mut file, frame_id)
+    }
+
+    fn write_frame(&mut self, file: &mut File, frame_id: usize) -> Result<()> {
+        let frame = &mut self.frames[frame_id];
+        file.seek(SeekFrom::Start(frame.key.1 as u64 * PAGE_SIZE as u64))?;
+        file.write_all(&frame.page.data)?;
+        frame.dirty = false;
+        self.stats.writes += 1;
+        Ok(())
+    }
+}
+
+/// A page fetched from a shared pool. Its frame stays pinned, so it isn't evicted,
+/// until the guard is dropped. Reading the page doesn't lock the pool; a write to it
+/// in the meantime goes to a new copy, and the guard keeps the version it fetched.
+pub struct PinnedPage {
+    page: Arc<Page>,
+    // None for a page that isn't stored anywhere yet and reads as zeros
+    pin: Option<(SharedPool, usize)>,
+}
+
+impl PinnedPage {
+    /// An unstored page of zeros, which has no frame to pin.
+    pub fn empty() -> Self {
+        Self {
+            page: Arc::new(Page::new()),
+            pin: None,
+        }
+    }
+
+    /// A copy of the page, to change and write back.
+    pub fn to_page(&self) -> Page {
+        Page::clone(&self.page)
+    }
+}
+
+impl Deref for PinnedPage {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        &self.page
+    }
+}
+
+impl Drop for PinnedPage {
+    fn drop(&mut self) {
+        if let Some((pool, frame_id)) = &self.pin {
+            lock(pool).unpin(*frame_id, false);
+        }
+    }
+}
+
+/// Fetches page `page_index` of `path` through `pool`, like `BufferPool::fetch`,
+/// and keeps it pinned until the returned guard is dropped.
+pub fn pin(
+    pool: &SharedPool,
+    path: &str,
+    page_index: usize,
+    load: impl FnOnce() -> Result<Page>,
+) -> Result<PinnedPage> {
+    let mut locked = lock(pool);
+    let frame_id = locked.fetch(path, page_index, load)?;
+    Ok(PinnedPage {
+        page: locked.frames[frame_id].page.clone(),
+        pin: Some((pool.clone(), frame_id)),
+    })
+}
+
+impl Drop for BufferPool {
+    /// Like `BufWriter`, writes what's left on a best-effort basis; call
+    /// `flush_all` first to see errors.
+    fn drop(&mut self) {
+        let _ = self.flush_all(true);
+    }
+}
+
+fn open_for_write(path: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+}
+
+/// Locks the pool. A panic while it was held can't leave a frame half written,
+/// so a poisoned lock is still safe to use.
+pub fn lock(pool: &SharedPool) -> MutexGuard<'_, BufferPool> {
+    pool.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,31 @@
+/// Lookup table for the reflected CRC-32 polynomial, one entry per byte value.
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE 802.3, as used by zlib and PNG) of `chunks` one after the other.
+pub fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for chunk in chunks {
+        for &byte in *chunk {
+            crc = TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+    !crc
+}
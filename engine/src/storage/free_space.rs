@@ -0,0 +1,63 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::pager::Page;
+
+/// The pages of a table that have at least one empty slot, so an insert can go
+/// straight to one instead of reading every page. The `Pager` stores it with the
+/// table, as a bitmap in the header page of a table file or in the directory entry
+/// of a single-file segment, and keeps it up to date on every page write.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreeSpaceMap {
+    // Slots in use per page, as given by `Page::max_slots`; 0 until the map is built
+    slots_per_page: usize,
+    pages: BTreeSet<usize>,
+}
+
+impl FreeSpaceMap {
+    pub fn new(slots_per_page: usize) -> Self {
+        Self {
+            slots_per_page,
+            pages: BTreeSet::new(),
+        }
+    }
+
+    pub fn slots_per_page(&self) -> usize {
+        self.slots_per_page
+    }
+
+    /// Records whether `page` has room after it was read or written.
+    pub fn update(&mut self, page_index: usize, page: &Page) {
+        self.set(
+            page_index,
+            page.first_free_slot(self.slots_per_page).is_some(),
+        );
+    }
+
+    /// Records whether page `page_index` has room. Returns true if that changed the map.
+    pub fn set(&mut self, page_index: usize, has_room: bool) -> bool {
+        if has_room {
+            self.pages.insert(page_index)
+        } else {
+            self.pages.remove(&page_index)
+        }
+    }
+
+    /// The lowest page with room, so tables keep filling from the front.
+    pub fn first(&self) -> Option<usize> {
+        self.pages.first().copied()
+    }
+
+    pub fn contains(&self, page_index: usize) -> bool {
+        self.pages.contains(&page_index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+}
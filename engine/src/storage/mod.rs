@@ -1,109 +1,236 @@
+pub mod buffer_pool;
+pub mod checksum;
+pub mod free_space;
 pub mod pager;
 pub mod record;
+pub mod single_file;
+
+use std::collections::{BTreeMap, HashSet};
 
 use crate::catalog::schema::Schema;
-use crate::index::PrimaryIndex;
-use crate::storage::pager::{HEADER_SIZE, PAGE_SIZE, Page, Pager};
+use crate::error::{CatalogKind, ConstraintKind, Error, Result};
+use crate::index::{KEY_SEPARATOR, PrimaryIndex, UniqueIndex, encode_key};
+use crate::storage::pager::{Page, Pager};
 use crate::storage::record::{Field, Row};
 
 pub struct Table {
     pub pager: Pager,
     pub schema: Schema,
     pub index: PrimaryIndex,
+    pub unique_indexes: Vec<UniqueIndex>,
 }
 
 impl Table {
-    pub fn insert_row(&mut self, row: Row) -> std::io::Result<()> {
-        let serialized_row = row.serialize(&self.schema);
-        let mut target_page_index = None;
-        let mut target_slot_index = None;
-        let mut page = Page::new();
-        let pk_col_idx = self.schema.columns.iter().position(|c| c.is_primary);
-        let max_slots = (PAGE_SIZE - HEADER_SIZE) / self.schema.row_size();
-
-        let pk_value = if let Some(col_idx) = pk_col_idx {
-            // Convert the field value to a string to use as the index key
-            Some(match &row.fields[col_idx] {
-                Field::Integer(v) => v.to_string(),
-                Field::Text(v) => v.clone(),
-                Field::Boolean(v) => v.to_string(),
-            })
-        } else {
-            None
-        };
-
-        if let Some(pk_value) = &pk_value {
-            if self.index.map.contains_key(pk_value) {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::AlreadyExists,
-                    "Primary Key Violation",
-                ));
-            }
-        }
-
-        // 1. Find a page and a slot.
-        // Iterate through existing pages.
-        'page_loop: for p_idx in 0..self.pager.num_pages() {
-            let p = self.pager.read_page(p_idx)?;
+    /// Wraps a pager with empty indexes for every key of `schema`; call `load_index` to fill them.
+    pub fn new(pager: Pager, schema: Schema) -> Self {
+        let unique_indexes = schema
+            .unique
+            .iter()
+            .filter_map(|columns| schema.column_indices(columns).ok())
+            .map(UniqueIndex::new)
+            .collect();
 
-            for slot_index in 0..max_slots {
-                if !p.is_slot_full(slot_index) {
-                    target_page_index = Some(p_idx);
-                    target_slot_index = Some(slot_index);
-                    page = p;
+        Self {
+            pager,
+            schema,
+            index: PrimaryIndex::new(),
+            unique_indexes,
+        }
+    }
 
-                    break 'page_loop;
-                }
-            }
+    pub fn insert_row(&mut self, row: Row) -> Result<()> {
+        let serialized_row = row.serialize(&self.schema);
+        let max_slots = Page::max_slots(self.schema.row_size());
+        if max_slots == 0 {
+            return Err(Error::catalog(
+                CatalogKind::InvalidDefinition,
+                format!(
+                    "Rows of table {} don't fit in a page",
+                    self.schema.table_name
+                ),
+            ));
         }
 
-        // 2. If no empty slot found in existing pages, create a new page
-        if target_page_index.is_none() {
-            let new_idx = self.pager.num_pages();
-            target_page_index = Some(new_idx);
-            target_slot_index = Some(0);
-            page = Page::new();
+        let pk_value = self.primary_key_for(&row);
+
+        if let Some(pk_value) = &pk_value
+            && self.index.map.contains_key(pk_value)
+        {
+            return Err(self.primary_key_violation());
         }
+        let unique_keys = self.unique_keys_for(&row, None)?;
 
-        let p_idx = target_page_index.unwrap();
-        let s_idx = target_slot_index.unwrap();
+        // Take a free slot in an existing page, or start a new page if they are all full
+        let (p_idx, s_idx, mut page) = self.pager.find_free_slot(max_slots)?;
 
         page.set_slot(s_idx, true);
         let offset = page.get_row_offset(s_idx, self.schema.row_size());
         page.data[offset..offset + self.schema.row_size()].copy_from_slice(&serialized_row);
 
-        self.pager.write_page(p_idx, &page).unwrap();
+        self.pager.write_page(p_idx, &page)?;
         if let Some(pk_value) = pk_value {
             let _ = self.index.insert(pk_value, p_idx, s_idx);
         }
+        for (unique, key) in self.unique_indexes.iter_mut().zip(unique_keys) {
+            if let Some(key) = key {
+                unique.map.insert(key, (p_idx, s_idx));
+            }
+        }
 
         Ok(())
     }
 
-    pub fn update_row(
-        &mut self,
-        page_idx: usize,
-        slot_idx: usize,
-        row: Row,
-    ) -> std::io::Result<()> {
-        let mut page = self.pager.read_page(page_idx)?;
-        let serialized_row = row.serialize(&self.schema);
+    /// The primary index key of `row`, covering every primary key column,
+    /// or None if the table has no primary key.
+    pub fn primary_key_for(&self, row: &Row) -> Option<String> {
+        let pk_columns = self.schema.primary_key_indices();
+        if pk_columns.is_empty() {
+            return None;
+        }
+        let fields: Vec<&Field> = pk_columns.iter().map(|&i| &row.fields[i]).collect();
+        Some(encode_key(&fields))
+    }
 
-        let offset = page.get_row_offset(slot_idx, self.schema.row_size());
-        page.data[offset..offset + self.schema.row_size()].copy_from_slice(&serialized_row);
+    fn primary_key_violation(&self) -> Error {
+        Error::constraint(
+            ConstraintKind::PrimaryKey,
+            format!(
+                "Primary key violation on ({})",
+                self.schema.primary_key_columns().join(", ")
+            ),
+        )
+    }
 
-        self.pager.write_page(page_idx, &page)
+    /// Computes the key of `row` in every unique index, failing if one is taken by
+    /// a row other than the one stored at `location`.
+    fn unique_keys_for(
+        &self,
+        row: &Row,
+        location: Option<(usize, usize)>,
+    ) -> Result<Vec<Option<String>>> {
+        let mut keys = Vec::new();
+
+        for unique in &self.unique_indexes {
+            let key = unique.key_for(row);
+            if let Some(key) = &key
+                && let Some(existing) = unique.map.get(key)
+                && Some(*existing) != location
+            {
+                return Err(self.unique_violation(unique));
+            }
+            keys.push(key);
+        }
+
+        Ok(keys)
+    }
+
+    fn unique_violation(&self, unique: &UniqueIndex) -> Error {
+        let columns: Vec<&str> = unique
+            .columns
+            .iter()
+            .map(|&i| self.schema.columns[i].name.as_str())
+            .collect();
+        Error::constraint(
+            ConstraintKind::Unique,
+            format!("Unique constraint violation on ({})", columns.join(", ")),
+        )
+    }
+
+    /// Fails if writing `updates`, keyed by (page_idx, slot_idx), would leave two rows
+    /// with the same primary key or UNIQUE key. Rows that aren't updated keep their
+    /// keys, and the keys the updated rows give up are free to be taken.
+    pub fn check_updates(&self, updates: &BTreeMap<(usize, usize), Row>) -> Result<()> {
+        let mut new_keys = HashSet::new();
+        for row in updates.values() {
+            if let Some(pk_value) = self.primary_key_for(row) {
+                let kept = self
+                    .index
+                    .map
+                    .get(&pk_value)
+                    .is_some_and(|location| !updates.contains_key(location));
+                if kept || !new_keys.insert(pk_value) {
+                    return Err(self.primary_key_violation());
+                }
+            }
+        }
+
+        for unique in &self.unique_indexes {
+            let mut new_keys = HashSet::new();
+            for key in updates.values().filter_map(|row| unique.key_for(row)) {
+                let kept = unique
+                    .map
+                    .get(&key)
+                    .is_some_and(|location| !updates.contains_key(location));
+                if kept || !new_keys.insert(key) {
+                    return Err(self.unique_violation(unique));
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn delete_row(&mut self, page_idx: usize, slot_idx: usize) -> std::io::Result<()> {
-        let mut page = self.pager.read_page(page_idx)?;
+    /// Overwrites the rows at the locations of `updates` and moves their index entries.
+    /// The keys are checked as a whole before anything is written, so rows may trade
+    /// or shift keys, as in `SET id = id + 1`, and a duplicate leaves every row as it was.
+    pub fn update_rows(&mut self, updates: &BTreeMap<(usize, usize), Row>) -> Result<()> {
+        self.check_updates(updates)?;
+        let mut old_rows = Vec::new();
+        for &(page_idx, slot_idx) in updates.keys() {
+            old_rows.push(self.get_row(page_idx, slot_idx)?);
+        }
+
+        // Every old key is released before any new one is taken
+        for old_row in &old_rows {
+            if let Some(old_pk) = self.primary_key_for(old_row) {
+                self.index.map.remove(&old_pk);
+            }
+            for unique in &mut self.unique_indexes {
+                if let Some(old_key) = unique.key_for(old_row) {
+                    unique.map.remove(&old_key);
+                }
+            }
+        }
+
+        for (&(page_idx, slot_idx), row) in updates {
+            let mut page = self.pager.read_page(page_idx)?.to_page();
+            let offset = page.get_row_offset(slot_idx, self.schema.row_size());
+            page.data[offset..offset + self.schema.row_size()]
+                .copy_from_slice(&row.serialize(&self.schema));
+            self.pager.write_page(page_idx, &page)?;
+
+            if let Some(pk_value) = self.primary_key_for(row) {
+                let _ = self.index.insert(pk_value, page_idx, slot_idx);
+            }
+            for unique in &mut self.unique_indexes {
+                if let Some(key) = unique.key_for(row) {
+                    unique.map.insert(key, (page_idx, slot_idx));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete_row(&mut self, page_idx: usize, slot_idx: usize) -> Result<()> {
+        let old_row = self.get_row(page_idx, slot_idx)?;
+
+        let mut page = self.pager.read_page(page_idx)?.to_page();
         page.set_slot(slot_idx, false);
-        self.pager.write_page(page_idx, &page)
+        self.pager.write_page(page_idx, &page)?;
+
+        if let Some(pk_value) = self.primary_key_for(&old_row) {
+            self.index.map.remove(&pk_value);
+        }
+        for unique in &mut self.unique_indexes {
+            if let Some(key) = unique.key_for(&old_row) {
+                unique.map.remove(&key);
+            }
+        }
+        Ok(())
     }
 
-    pub fn scan_rows(&mut self) -> std::io::Result<Vec<Row>> {
+    pub fn scan_rows(&mut self) -> Result<Vec<Row>> {
         let mut rows = Vec::new();
-        let max_slots = (PAGE_SIZE - HEADER_SIZE) / self.schema.row_size();
+        let max_slots = Page::max_slots(self.schema.row_size());
 
         for p_idx in 0..self.pager.num_pages() {
             let page = self.pager.read_page(p_idx)?;
@@ -116,7 +243,7 @@ impl Table {
                     // 2. Extract the slice of bytes representing this row
                     let row_bytes = &page.data[offset..offset + self.schema.row_size()];
 
-                    let row = Row::deserialize(row_bytes, &self.schema);
+                    let row = Row::deserialize(row_bytes, &self.schema)?;
                     rows.push(row);
                 }
             }
@@ -125,19 +252,83 @@ impl Table {
         Ok(rows)
     }
 
-    pub fn get_row(&mut self, page_idx: usize, slot_idx: usize) -> std::io::Result<Row> {
+    /// Like `scan_rows`, but each row comes with its (page_idx, slot_idx).
+    pub fn scan_locations(&mut self) -> Result<Vec<(usize, usize, Row)>> {
+        let mut rows = Vec::new();
+        let max_slots = Page::max_slots(self.schema.row_size());
+
+        for p_idx in 0..self.pager.num_pages() {
+            let page = self.pager.read_page(p_idx)?;
+            for s_idx in 0..max_slots {
+                if page.is_slot_full(s_idx) {
+                    let offset = page.get_row_offset(s_idx, self.schema.row_size());
+                    let row_bytes = &page.data[offset..offset + self.schema.row_size()];
+                    rows.push((p_idx, s_idx, Row::deserialize(row_bytes, &self.schema)?));
+                }
+            }
+        }
+
+        Ok(rows)
+    }
+
+    pub fn get_row(&mut self, page_idx: usize, slot_idx: usize) -> Result<Row> {
         let page = self.pager.read_page(page_idx)?;
         let offset = page.get_row_offset(slot_idx, self.schema.row_size());
         let row_bytes = &page.data[offset..offset + self.schema.row_size()];
 
-        Ok(Row::deserialize(row_bytes, &self.schema))
+        Row::deserialize(row_bytes, &self.schema)
     }
 
-    pub fn load_index(&mut self) -> std::io::Result<()> {
-        let pk_col_idx = self.schema.columns.iter().position(|c| c.is_primary);
+    /// Locations of every row whose primary key starts with `values`, in key order.
+    /// With a value for each key column this is at most one row.
+    pub fn find_by_key_prefix(&self, values: &[&Field]) -> Vec<(usize, usize)> {
+        let prefix = encode_key(values);
+        if values.len() == self.schema.primary_key_indices().len() {
+            return self.index.map.get(&prefix).copied().into_iter().collect();
+        }
+        // Keys of the matching rows continue with a separator after the prefix
+        let prefix = format!("{}{}", prefix, KEY_SEPARATOR);
+        self.index
+            .map
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, location)| *location)
+            .collect()
+    }
+
+    /// The location of a stored row that `row` would collide with on the primary key
+    /// or any UNIQUE constraint.
+    pub fn find_conflict(&self, row: &Row) -> Option<(usize, usize)> {
+        if let Some(pk_value) = self.primary_key_for(row)
+            && let Some(location) = self.index.map.get(&pk_value)
+        {
+            return Some(*location);
+        }
+        self.unique_indexes.iter().find_map(|unique| {
+            unique
+                .key_for(row)
+                .and_then(|key| unique.map.get(&key).copied())
+        })
+    }
 
-        if let Some(col_idx) = pk_col_idx {
-            let max_slots = (PAGE_SIZE - HEADER_SIZE) / self.schema.row_size();
+    /// Looks `values` up in the primary or unique index covering exactly `columns`.
+    /// Returns None if there is no such index or no matching row.
+    pub fn find_by_key(&self, columns: &[usize], values: &[&Field]) -> Option<(usize, usize)> {
+        let key = encode_key(values);
+        if self.schema.primary_key_indices() == columns {
+            return self.index.map.get(&key).copied();
+        }
+        self.unique_indexes
+            .iter()
+            .find(|u| u.columns == columns)
+            .and_then(|u| u.map.get(&key).copied())
+    }
+
+    pub fn load_index(&mut self) -> Result<()> {
+        let has_pk = self.schema.columns.iter().any(|c| c.is_primary);
+
+        if has_pk || !self.unique_indexes.is_empty() {
+            let max_slots = Page::max_slots(self.schema.row_size());
 
             for p_idx in 0..self.pager.num_pages() {
                 let page = self.pager.read_page(p_idx)?;
@@ -145,16 +336,16 @@ impl Table {
                     if page.is_slot_full(s_idx) {
                         let offset = page.get_row_offset(s_idx, self.schema.row_size());
                         let row_bytes = &page.data[offset..offset + self.schema.row_size()];
-                        let row = Row::deserialize(row_bytes, &self.schema);
-
-                        // Convert the field value to a string to use as the index key
-                        let pk_value = match &row.fields[col_idx] {
-                            Field::Integer(v) => v.to_string(),
-                            Field::Text(v) => v.clone(),
-                            Field::Boolean(v) => v.to_string(),
-                        };
+                        let row = Row::deserialize(row_bytes, &self.schema)?;
 
-                        let _ = self.index.insert(pk_value, p_idx, s_idx);
+                        if let Some(pk_value) = self.primary_key_for(&row) {
+                            let _ = self.index.insert(pk_value, p_idx, s_idx);
+                        }
+                        for unique in &mut self.unique_indexes {
+                            if let Some(key) = unique.key_for(&row) {
+                                unique.map.insert(key, (p_idx, s_idx));
+                            }
+                        }
                     }
                 }
             }
@@ -1,19 +1,155 @@
 pub const PAGE_SIZE: usize = 4096;
 pub const HEADER_SIZE: usize = 64;
+// The slot bitmap fills the page header up to the checksum in its last 4 bytes
+pub const BITMAP_SIZE: usize = 60;
+pub const CHECKSUM_OFFSET: usize = BITMAP_SIZE;
+
+/// First bytes of every table file.
+pub const MAGIC: [u8; 8] = *b"ScarDB\0\0";
+/// Bumped whenever the layout of table files changes.
+pub const FORMAT_VERSION: u32 = 1;
+/// Data pages covered by the free-space bitmap in the rest of a table file's header
+/// page, one bit each. Pages past them are read to find room.
+pub const FREE_SPACE_PAGES: usize = (PAGE_SIZE - HEADER_SIZE) * 8;
+// Where the header page records the slots per page its free-space bitmap was built
+// for; 0, as in files written before the bitmap, means it hasn't been built
+const FREE_SPACE_SLOTS_OFFSET: usize = 24;
 
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::error::{Error, Result};
+use crate::storage::buffer_pool::{
+    self, BufferPool, DEFAULT_CAPACITY, PinnedPage, SharedPool, lock,
+};
+use crate::storage::checksum::crc32;
+use crate::storage::free_space::FreeSpaceMap;
+use crate::storage::single_file::{self, SharedFile};
 
+/// Reads and writes the pages of one table through a buffer pool.
+///
+/// The table either has a file of its own, whose page 0 holds a `FileHeader` and
+/// whose data pages, seen by callers as 0, 1, 2..., are stored after it, or it is a
+/// segment of a single-file database, whose pages may lie anywhere in that file.
+/// Every page carries a CRC-32 of its contents that is checked when it is read from disk.
+/// The table's free-space map is stored with it, so it survives reopening and every
+/// pager of the table sees the same one.
 pub struct Pager {
     file: File,
+    path: String,
+    pool: SharedPool,
     pub file_length: u64,
+    layout: Layout,
+}
+
+enum Layout {
+    // A file of its own, with a header page in front
+    File { header: FileHeader },
+    // The segment called `name` in a single-file database
+    Segment { file: SharedFile, name: String },
+}
+
+/// What page 0 of a table file says about the rest of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileHeader {
+    pub format_version: u32,
+    pub page_size: u32,
+    // `Schema::fingerprint` of the table the rows were written for, 0 if not set yet
+    pub schema_fingerprint: u64,
+}
+
+impl FileHeader {
+    /// Writes the header fields to the start of `page`, leaving the free-space map alone.
+    fn encode_into(&self, page: &mut Page) {
+        page.data[0..8].copy_from_slice(&MAGIC);
+        page.data[8..12].copy_from_slice(&self.format_version.to_le_bytes());
+        page.data[12..16].copy_from_slice(&self.page_size.to_le_bytes());
+        page.data[16..24].copy_from_slice(&self.schema_fingerprint.to_le_bytes());
+    }
+
+    fn decode(page: &Page, path: &str) -> Result<Self> {
+        if page.data[0..8] != MAGIC {
+            return Err(Error::Corruption(format!(
+                "{} is not a ScarDB table file, or was written before file format {}",
+                path, FORMAT_VERSION
+            )));
+        }
+        let u32_at = |at: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&page.data[at..at + 4]);
+            u32::from_le_bytes(bytes)
+        };
+        let mut fingerprint = [0; 8];
+        fingerprint.copy_from_slice(&page.data[16..24]);
+        let header = Self {
+            format_version: u32_at(8),
+            page_size: u32_at(12),
+            schema_fingerprint: u64::from_le_bytes(fingerprint),
+        };
+
+        if header.format_version != FORMAT_VERSION {
+            return Err(Error::Corruption(format!(
+                "{} uses file format {}, but this version of ScarDB reads format {}",
+                path, header.format_version, FORMAT_VERSION
+            )));
+        }
+        if header.page_size as usize != PAGE_SIZE {
+            return Err(Error::Corruption(format!(
+                "{} has {}-byte pages, expected {}",
+                path, header.page_size, PAGE_SIZE
+            )));
+        }
+        Ok(header)
+    }
 }
 
+#[derive(Clone)]
 pub struct Page {
     pub data: [u8; PAGE_SIZE],
 }
 
+impl Default for Page {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Page {
+    /// Number of rows of `row_size` bytes a page can hold. This is also capped by
+    /// the number of bits in the header bitmap. Zero-sized rows get no slots.
+    pub fn max_slots(row_size: usize) -> usize {
+        (PAGE_SIZE - HEADER_SIZE)
+            .checked_div(row_size)
+            .unwrap_or(0)
+            .min(BITMAP_SIZE * 8)
+    }
+
+    /// CRC-32 of everything in the page but the checksum itself.
+    pub fn compute_checksum(&self) -> u32 {
+        crc32(&[
+            &self.data[..CHECKSUM_OFFSET],
+            &self.data[CHECKSUM_OFFSET + 4..],
+        ])
+    }
+
+    pub fn stored_checksum(&self) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.data[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn seal(&mut self) {
+        let checksum = self.compute_checksum();
+        self.data[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// True if the stored checksum matches the contents. An all-zero page was
+    /// never written, e.g. a gap left when a later page was evicted first, and
+    /// counts as a valid empty page.
+    pub fn verify_checksum(&self) -> bool {
+        self.stored_checksum() == self.compute_checksum() || self.data.iter().all(|&b| b == 0)
+    }
+
     pub fn new() -> Self {
         Self {
             data: [0; PAGE_SIZE],
@@ -31,6 +167,11 @@ impl Page {
         self.data[byte_idx] & (1 << bit_idx) != 0
     }
 
+    /// The first empty slot among the first `max_slots`, if there is one.
+    pub fn first_free_slot(&self, max_slots: usize) -> Option<usize> {
+        (0..max_slots).find(|&s| !self.is_slot_full(s))
+    }
+
     pub fn set_slot(&mut self, slot_index: usize, occupied: bool) {
         let byte_idx = slot_index / 8;
         let bit_idx = slot_index % 8;
@@ -44,43 +185,459 @@ impl Page {
 }
 
 impl Pager {
-    pub fn open(path: &str) -> std::io::Result<Self> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(path)?;
+    /// Opens `path` with a buffer pool of its own.
+    pub fn open(path: &str) -> Result<Self> {
+        Self::with_pool(path, BufferPool::shared(DEFAULT_CAPACITY))
+    }
 
-        let file_length = file.metadata()?.len();
-        Ok(Self { file, file_length })
+    /// Opens `path`, caching its pages in `pool`. A new or empty file is given a
+    /// header; an existing one must have a valid header of the current format.
+    pub fn with_pool(path: &str, pool: SharedPool) -> Result<Self> {
+        let mut pager = Self::from_file(open_file(path)?, path, pool)?;
+        if pager.file_length == 0 {
+            let mut page = Page::new();
+            pager.header().encode_into(&mut page);
+            pager.write_physical(0, &page)?;
+        }
+        Ok(pager)
     }
 
-    pub fn read_page(&mut self, page_index: usize) -> std::io::Result<Page> {
-        let mut page = Page::new();
-        let offset = page_index as u64 * PAGE_SIZE as u64;
+    /// Opens the existing file `path` for reading only, with a buffer pool of its own.
+    /// Nothing is created or written; an empty file reads as a table without pages.
+    pub fn open_read_only(path: &str) -> Result<Self> {
+        Self::from_file(
+            File::open(path)?,
+            path,
+            BufferPool::shared(DEFAULT_CAPACITY),
+        )
+    }
+
+    /// Wraps an open table file, reading its header unless it is empty.
+    fn from_file(mut file: File, path: &str, pool: SharedPool) -> Result<Self> {
+        // Its first page would fail the checksum check, so tell the user what it is instead
+        if is_headerless(&mut file)? {
+            return Err(Error::Corruption(format!(
+                "{} was written before table files had a header; run `cli upgrade <data_dir>` to convert it",
+                path
+            )));
+        }
 
-        // Jump to the right spot in the file
-        self.file.seek(SeekFrom::Start(offset))?;
+        // Pages another pager appended may still be waiting in the pool
+        let file_length = lock(&pool).file_length(path, file.metadata()?.len());
+        let header = FileHeader {
+            format_version: FORMAT_VERSION,
+            page_size: PAGE_SIZE as u32,
+            schema_fingerprint: 0,
+        };
+        let mut pager = Self {
+            file,
+            path: path.to_string(),
+            pool,
+            file_length,
+            layout: Layout::File { header },
+        };
 
-        // Try to read exactly 4096 bytes.
-        let _ = self.file.read(&mut page.data)?;
+        if file_length != 0 {
+            let page = pager.read_header_page()?;
+            pager.layout = Layout::File {
+                header: FileHeader::decode(&page, path)?,
+            };
+        }
+        Ok(pager)
+    }
 
-        Ok(page)
+    /// Opens the segment `name` of a single-file database, creating it if it's missing.
+    pub fn segment(file: &SharedFile, name: &str) -> Result<Self> {
+        let (path, pool) = {
+            let mut single = single_file::lock(file);
+            single.ensure_segment(name);
+            (single.path().to_string(), single.pool())
+        };
+        Ok(Self {
+            file: open_file(&path)?,
+            path,
+            pool,
+            file_length: 0,
+            layout: Layout::Segment {
+                file: file.clone(),
+                name: name.to_string(),
+            },
+        })
     }
 
-    pub fn write_page(&mut self, page_index: usize, page: &Page) -> std::io::Result<()> {
-        let offset = page_index as u64 * PAGE_SIZE as u64;
+    pub fn header(&self) -> FileHeader {
+        match &self.layout {
+            Layout::File { header } => *header,
+            Layout::Segment { file, name } => FileHeader {
+                format_version: FORMAT_VERSION,
+                page_size: PAGE_SIZE as u32,
+                schema_fingerprint: single_file::lock(file).fingerprint(name),
+            },
+        }
+    }
 
-        self.file.seek(SeekFrom::Start(offset))?;
-        self.file.write_all(&page.data)?;
-        self.file.sync_all()?;
+    /// Records which schema the rows in this file are laid out for.
+    pub fn set_schema_fingerprint(&mut self, fingerprint: u64) -> Result<()> {
+        match &mut self.layout {
+            Layout::File { header } => {
+                header.schema_fingerprint = fingerprint;
+                let header = *header;
+                let mut page = self.read_header_page()?;
+                header.encode_into(&mut page);
+                self.write_physical(0, &page)
+            }
+            Layout::Segment { file, name } => {
+                single_file::lock(file).set_fingerprint(name, fingerprint);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads a page, which stays pinned in the buffer pool while the guard is held.
+    /// To change it, write a copy back with `write_page`.
+    pub fn read_page(&mut self, page_index: usize) -> Result<PinnedPage> {
+        let index = match &self.layout {
+            Layout::File { .. } => page_index + 1,
+            Layout::Segment { file, name } => {
+                match single_file::lock(file).page(name, page_index) {
+                    Some(index) => index,
+                    // Not allocated yet, like a page past the end of a file
+                    None => return Ok(PinnedPage::empty()),
+                }
+            }
+        };
+        let Self {
+            file,
+            path,
+            pool,
+            layout,
+            ..
+        } = self;
+        let label = || match &*layout {
+            Layout::File { .. } => format!("page {}", page_index),
+            Layout::Segment { name, .. } => format!("page {} of {}", page_index, name),
+        };
+        buffer_pool::pin(pool, path, index, || load_page(file, path, index, label))
+    }
+
+    /// Replaces a page in the pool. It reaches the file on `flush` or when evicted.
+    pub fn write_page(&mut self, page_index: usize, page: &Page) -> Result<()> {
+        let index = match &self.layout {
+            Layout::File { .. } => page_index + 1,
+            Layout::Segment { file, name } => {
+                single_file::lock(file).page_for_write(name, page_index)
+            }
+        };
+        self.write_physical(index, page)?;
+
+        let slots_per_page = self.free_space_slots()?;
+        if slots_per_page != 0 {
+            let has_room = page.first_free_slot(slots_per_page).is_some();
+            self.mark_free_space(page_index, has_room)?;
+        }
+        Ok(())
+    }
+
+    fn read_header_page(&mut self) -> Result<Page> {
+        self.read_physical(0, || "the header page".to_string())
+    }
+
+    /// Reads page `index` of the file, checking its checksum if it comes from disk.
+    /// `label` names the page in errors.
+    fn read_physical(&mut self, index: usize, label: impl Fn() -> String) -> Result<Page> {
+        fetch_page(
+            &mut lock(&self.pool),
+            &mut self.file,
+            &self.path,
+            index,
+            label,
+        )
+    }
+
+    fn write_physical(&mut self, index: usize, page: &Page) -> Result<()> {
+        store_page(&mut lock(&self.pool), &self.path, index, page)?;
 
         // Update our knowledge of the file length
-        self.file_length = self.file.metadata()?.len();
+        let end = (index as u64 + 1) * PAGE_SIZE as u64;
+        self.file_length = self.file_length.max(end);
         Ok(())
     }
 
+    /// Writes this file's dirty pages and syncs it.
+    pub fn flush(&mut self) -> Result<()> {
+        lock(&self.pool).flush_file(&self.path, true)
+    }
+
+    /// Number of data pages, not counting the header. A torn page at the end counts,
+    /// so reading it reports the damage instead of silently dropping its rows.
     pub fn num_pages(&self) -> usize {
-        (self.file_length as usize) / PAGE_SIZE
+        match &self.layout {
+            Layout::File { .. } => (self.file_length as usize)
+                .div_ceil(PAGE_SIZE)
+                .saturating_sub(1),
+            Layout::Segment { file, name } => single_file::lock(file).segment_len(name),
+        }
+    }
+
+    /// Finds an empty slot for a row on pages of `max_slots` slots. Returns the page
+    /// to write the row to, a new one past the end of the file if every page is full.
+    /// The free-space map is built first if it is missing or was built for other pages.
+    pub fn find_free_slot(&mut self, max_slots: usize) -> Result<(usize, usize, Page)> {
+        if self.free_space_slots()? != max_slots {
+            self.rebuild_free_space(max_slots)?;
+        }
+
+        while let Some(page_index) = self.first_free_page(max_slots)? {
+            let page = self.read_page(page_index)?;
+            if let Some(slot_index) = page.first_free_slot(max_slots) {
+                return Ok((page_index, slot_index, page.to_page()));
+            }
+            // The map is out of date, e.g. after a crash; fix it and try the next page
+            self.mark_free_space(page_index, false)?;
+        }
+        Ok((self.num_pages(), 0, Page::new()))
+    }
+
+    /// The stored free-space map, or None if it hasn't been built yet.
+    pub fn free_space(&mut self) -> Result<Option<FreeSpaceMap>> {
+        let map = match &self.layout {
+            Layout::File { .. } => {
+                let header = self.read_header_page()?;
+                let mut map = FreeSpaceMap::new(free_space_slots(&header));
+                for page_index in 0..FREE_SPACE_PAGES.min(self.num_pages()) {
+                    map.set(page_index, free_space_bit(&header, page_index));
+                }
+                map
+            }
+            Layout::Segment { file, name } => single_file::lock(file)
+                .free_space(name)
+                .cloned()
+                .unwrap_or_default(),
+        };
+        Ok(Some(map).filter(|map| map.slots_per_page() != 0))
+    }
+
+    /// Slots per page the stored free-space map was built for, 0 if there is none.
+    fn free_space_slots(&mut self) -> Result<usize> {
+        match &self.layout {
+            Layout::File { .. } => Ok(free_space_slots(&*self.pin_header_page()?)),
+            Layout::Segment { file, name } => Ok(single_file::lock(file)
+                .free_space(name)
+                .map_or(0, FreeSpaceMap::slots_per_page)),
+        }
+    }
+
+    /// The lowest page the free-space map lists as having room. Pages of a table
+    /// file past the bitmap are read instead.
+    fn first_free_page(&mut self, max_slots: usize) -> Result<Option<usize>> {
+        let header = match &self.layout {
+            Layout::File { .. } => self.pin_header_page()?,
+            Layout::Segment { file, name } => {
+                return Ok(single_file::lock(file)
+                    .free_space(name)
+                    .and_then(FreeSpaceMap::first));
+            }
+        };
+        let bitmap = &header.data[HEADER_SIZE..];
+        if let Some(byte) = bitmap.iter().position(|&b| b != 0) {
+            return Ok(Some(byte * 8 + bitmap[byte].trailing_zeros() as usize));
+        }
+        for page_index in FREE_SPACE_PAGES..self.num_pages() {
+            if self
+                .read_page(page_index)?
+                .first_free_slot(max_slots)
+                .is_some()
+            {
+                return Ok(Some(page_index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Records in the stored free-space map whether page `page_index` has room.
+    /// The header page is only rewritten if that changes its bit.
+    fn mark_free_space(&mut self, page_index: usize, has_room: bool) -> Result<()> {
+        match &self.layout {
+            Layout::File { .. } => {
+                if page_index >= FREE_SPACE_PAGES
+                    || free_space_bit(&*self.pin_header_page()?, page_index) == has_room
+                {
+                    return Ok(());
+                }
+                let mut header = self.read_header_page()?;
+                set_free_space_bit(&mut header, page_index, has_room);
+                self.write_physical(0, &header)
+            }
+            Layout::Segment { file, name } => {
+                single_file::lock(file).mark_free_space(name, page_index, has_room);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads the bitmap of every page to find the ones with room, and stores the
+    /// result as the map for pages of `max_slots` slots.
+    fn rebuild_free_space(&mut self, max_slots: usize) -> Result<()> {
+        let mut free_space = FreeSpaceMap::new(max_slots);
+        for page_index in 0..self.num_pages() {
+            free_space.update(page_index, &*self.read_page(page_index)?);
+        }
+
+        match &self.layout {
+            Layout::File { .. } => {
+                let mut header = self.read_header_page()?;
+                header.data[HEADER_SIZE..].fill(0);
+                for page_index in 0..FREE_SPACE_PAGES.min(self.num_pages()) {
+                    set_free_space_bit(&mut header, page_index, free_space.contains(page_index));
+                }
+                header.data[FREE_SPACE_SLOTS_OFFSET..FREE_SPACE_SLOTS_OFFSET + 4]
+                    .copy_from_slice(&(max_slots as u32).to_le_bytes());
+                self.write_physical(0, &header)
+            }
+            Layout::Segment { file, name } => {
+                single_file::lock(file).set_free_space(name, free_space);
+                Ok(())
+            }
+        }
     }
+
+    /// The header page of a table file, pinned for a look at its free-space map.
+    fn pin_header_page(&mut self) -> Result<PinnedPage> {
+        let Self {
+            file, path, pool, ..
+        } = self;
+        buffer_pool::pin(pool, path, 0, || {
+            load_page(file, path, 0, || "the header page".to_string())
+        })
+    }
+}
+
+fn free_space_slots(header: &Page) -> usize {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&header.data[FREE_SPACE_SLOTS_OFFSET..FREE_SPACE_SLOTS_OFFSET + 4]);
+    u32::from_le_bytes(bytes) as usize
+}
+
+fn free_space_bit(header: &Page, page_index: usize) -> bool {
+    header.data[HEADER_SIZE + page_index / 8] & (1 << (page_index % 8)) != 0
+}
+
+fn set_free_space_bit(header: &mut Page, page_index: usize, has_room: bool) {
+    let byte = &mut header.data[HEADER_SIZE + page_index / 8];
+    if has_room {
+        *byte |= 1 << (page_index % 8);
+    } else {
+        *byte &= !(1 << (page_index % 8));
+    }
+}
+
+/// True if `file` is a table file from before file format 1, which has no header page
+/// and no checksums: its first page holds rows instead of `MAGIC`. An all-zero first
+/// page is a header that was never written, not such a file.
+fn is_headerless(file: &mut File) -> io::Result<bool> {
+    let mut page = Page::new();
+    file.seek(SeekFrom::Start(0))?;
+    read_full(file, &mut page.data)?;
+    Ok(page.data[0..8] != MAGIC && page.data.iter().any(|&b| b != 0))
+}
+
+/// The pages of the table file at `path` if it has no header page, None otherwise.
+/// A torn last page is filled up with zeros, as such files were read.
+pub fn read_headerless(path: &str) -> Result<Option<Vec<Page>>> {
+    let mut file = File::open(path)?;
+    if !is_headerless(&mut file)? {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(0))?;
+    let mut pages = Vec::new();
+    loop {
+        let mut page = Page::new();
+        if read_full(&mut file, &mut page.data)? == 0 {
+            break;
+        }
+        pages.push(page);
+    }
+    Ok(Some(pages))
+}
+
+/// Reads until `buf` is full or the file ends, returning the number of bytes read.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn open_file(path: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+}
+
+/// Reads a copy of page `index` of the file at `path` through `pool`, loading it
+/// with `file` on a miss. `label` names the page in errors.
+pub(crate) fn fetch_page(
+    pool: &mut BufferPool,
+    file: &mut File,
+    path: &str,
+    index: usize,
+    label: impl Fn() -> String,
+) -> Result<Page> {
+    let frame_id = pool.fetch(path, index, || load_page(file, path, index, label))?;
+    let page = pool.page(frame_id).clone();
+    pool.unpin(frame_id, false);
+    Ok(page)
+}
+
+/// Reads page `index` from disk. It must be whole and match its checksum; a page
+/// past the end of the file reads as zeros.
+fn load_page(
+    file: &mut File,
+    path: &str,
+    index: usize,
+    label: impl Fn() -> String,
+) -> Result<Page> {
+    let mut page = Page::new();
+    // Jump to the right spot in the file
+    file.seek(SeekFrom::Start(index as u64 * PAGE_SIZE as u64))?;
+    let read = read_full(file, &mut page.data)?;
+    if read != 0 && read != PAGE_SIZE {
+        return Err(Error::Corruption(format!(
+            "{}: {} is truncated to {} bytes",
+            path,
+            label(),
+            read
+        )));
+    }
+    if !page.verify_checksum() {
+        return Err(Error::Corruption(format!(
+            "{}: checksum mismatch on {}",
+            path,
+            label()
+        )));
+    }
+    Ok(page)
+}
+
+/// Replaces page `index` of the file at `path` in `pool`, stamping its checksum.
+pub(crate) fn store_page(
+    pool: &mut BufferPool,
+    path: &str,
+    index: usize,
+    page: &Page,
+) -> Result<()> {
+    // The whole page is overwritten, so there's no need to read the old one
+    let frame_id = pool.fetch(path, index, || Ok(Page::new()))?;
+    let frame = pool.page_mut(frame_id);
+    frame.data = page.data;
+    frame.seal();
+    pool.unpin(frame_id, true);
+    Ok(())
 }
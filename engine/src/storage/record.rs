@@ -1,15 +1,14 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{
-    catalog::schema::{DataType, Schema},
-    sql::{Filter, Operator},
-};
+use crate::catalog::schema::{DataType, Schema};
+use crate::error::{Error, Result};
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Field {
     Integer(i32),
     Boolean(bool),
     Text(String),
+    Null,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -17,12 +16,34 @@ pub struct Row {
     pub fields: Vec<Field>,
 }
 
+impl Field {
+    /// Converts the value to `target`, which must be a widening of its current type.
+    pub fn widen_to(self, target: &DataType) -> Field {
+        match (self, target) {
+            (Field::Boolean(v), DataType::Integer) => Field::Integer(v as i32),
+            (Field::Integer(v), DataType::Text(_)) => Field::Text(v.to_string()),
+            (Field::Boolean(v), DataType::Text(_)) => Field::Text(v.to_string()),
+            (field, _) => field,
+        }
+    }
+}
+
 impl Row {
     pub fn serialize(&self, schema: &Schema) -> Vec<u8> {
         let mut bytes = Vec::new();
 
         for (i, column) in schema.columns.iter().enumerate() {
             let field = &self.fields[i];
+
+            // Nullable columns are prefixed by a flag byte, followed by zeroed value bytes
+            if column.is_nullable {
+                bytes.push(u8::from(*field == Field::Null));
+                if *field == Field::Null {
+                    bytes.extend(vec![0u8; column.data_type.byte_size()]);
+                    continue;
+                }
+            }
+
             match &column.data_type {
                 DataType::Integer => {
                     if let Field::Integer(val) = field {
@@ -51,14 +72,60 @@ impl Row {
         bytes
     }
 
-    pub fn deserialize(bytes: &[u8], schema: &Schema) -> Self {
+    /// The first text column of a row written by `serialize` whose bytes aren't
+    /// valid UTF-8. `deserialize` replaces such bytes, this finds them.
+    pub fn invalid_text_column(bytes: &[u8], schema: &Schema) -> Option<usize> {
+        let mut cursor = 0;
+        for (i, column) in schema.columns.iter().enumerate() {
+            let is_null = column.is_nullable && bytes.get(cursor).is_some_and(|&b| b != 0);
+            if column.is_nullable {
+                cursor += 1;
+            }
+            let size = column.data_type.byte_size();
+            if let DataType::Text(_) = column.data_type
+                && !is_null
+            {
+                let text = bytes.get(cursor..cursor + size)?;
+                let len = text.iter().position(|&b| b == 0).unwrap_or(size);
+                if std::str::from_utf8(&text[..len]).is_err() {
+                    return Some(i);
+                }
+            }
+            cursor += size;
+        }
+        None
+    }
+
+    /// Decodes a row written by `serialize`. Fails if `bytes` is shorter than
+    /// `schema.row_size()`, as with a truncated or corrupt file.
+    pub fn deserialize(bytes: &[u8], schema: &Schema) -> Result<Self> {
+        if bytes.len() < schema.row_size() {
+            return Err(Error::Corruption(format!(
+                "Row of table {} is {} bytes, expected {}",
+                schema.table_name,
+                bytes.len(),
+                schema.row_size()
+            )));
+        }
         let mut fields = Vec::new();
         let mut cursor = 0;
 
         for column in &schema.columns {
+            if column.is_nullable {
+                let is_null = bytes[cursor] != 0;
+                cursor += 1;
+                if is_null {
+                    fields.push(Field::Null);
+                    cursor += column.data_type.byte_size();
+                    continue;
+                }
+            }
+
             match column.data_type {
                 DataType::Integer => {
-                    let val = i32::from_le_bytes(bytes[cursor..cursor + 4].try_into().unwrap());
+                    let mut val = [0u8; 4];
+                    val.copy_from_slice(&bytes[cursor..cursor + 4]);
+                    let val = i32::from_le_bytes(val);
                     fields.push(Field::Integer(val));
                     cursor += 4;
                 }
@@ -81,41 +148,6 @@ impl Row {
             }
         }
 
-        Row { fields }
-    }
-
-    pub fn row_matches_filter(row: &Row, filter: &Filter, schema: &Schema) -> bool {
-        // 1. Find the index of the column being filtered
-        let col_idx = match schema
-            .columns
-            .iter()
-            .position(|c| c.name == filter.column_name)
-        {
-            Some(idx) => idx,
-            None => return false,
-        };
-
-        let actual_value = &row.fields[col_idx];
-
-        // 2. Compare actual_value vs filter.value based on the operator
-        match filter.operator {
-            Operator::Eq => actual_value == &filter.value,
-            Operator::NotEq => actual_value != &filter.value,
-            // For GreaterThan/LessThan, we will handle only Integers
-            Operator::GreaterThan => {
-                if let (Field::Integer(a), Field::Integer(b)) = (actual_value, &filter.value) {
-                    a > b
-                } else {
-                    false
-                }
-            }
-            Operator::LessThan => {
-                if let (Field::Integer(a), Field::Integer(b)) = (actual_value, &filter.value) {
-                    a < b
-                } else {
-                    false
-                }
-            }
-        }
+        Ok(Row { fields })
     }
 }
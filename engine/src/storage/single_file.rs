@@ -0,0 +1,436 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::CatalogData;
+use crate::error::{Error, Result};
+use crate::storage::buffer_pool::{self, SharedPool};
+use crate::storage::free_space::FreeSpaceMap;
+use crate::storage::pager::{FORMAT_VERSION, HEADER_SIZE, PAGE_SIZE, Page, fetch_page, store_page};
+
+/// First bytes of a single-file database.
+pub const SINGLE_FILE_MAGIC: [u8; 8] = *b"ScarDBsf";
+
+/// Bytes of the directory stored in each of its pages, after the page header.
+const CHUNK_SIZE: usize = PAGE_SIZE - HEADER_SIZE;
+
+/// A single-file database shared by the pagers of its tables.
+pub type SharedFile = Arc<Mutex<SingleFile>>;
+
+/// Everything needed to find the data in the file. It is saved as JSON in a chain
+/// of pages, which page 0 points at.
+#[derive(Serialize, Deserialize, Default)]
+struct Directory {
+    // Where older versions kept the catalog. It is moved into the `scardb_master`
+    // segment when the file is opened, and stays empty after that.
+    catalog: CatalogData,
+    segments: BTreeMap<String, Segment>,
+    free_pages: BTreeSet<usize>,
+}
+
+/// The pages of one table, in order.
+#[derive(Serialize, Deserialize, Default)]
+struct Segment {
+    // `Schema::fingerprint` of the table the rows were written for, 0 if not set yet
+    fingerprint: u64,
+    pages: Vec<usize>,
+    #[serde(default)]
+    free_space: FreeSpaceMap,
+}
+
+/// A database kept in one file: a header page, the directory, and the pages of
+/// every table, handed out by a page allocator with a free list.
+///
+/// Changes to the directory stay in memory until `commit`, which writes a new copy
+/// of it to free pages before pointing the header at it. A crash before the header
+/// is written leaves the previous directory in place, and pages freed since it was
+/// saved aren't reused until then, so it never points at pages that were reused.
+pub struct SingleFile {
+    path: String,
+    file: File,
+    pool: SharedPool,
+    directory: Directory,
+    // Pages holding the saved directory, and pages freed since it was saved.
+    // Both only join the free list once a newer directory is saved.
+    directory_pages: Vec<usize>,
+    pending_free: Vec<usize>,
+    // Pages in the file, the header included
+    page_count: usize,
+    dirty: bool,
+}
+
+impl SingleFile {
+    /// Opens the database in `path`, creating it if the file is missing or empty.
+    pub fn open(path: &str, pool: SharedPool) -> Result<SharedFile> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let length = buffer_pool::lock(&pool).file_length(path, file.metadata()?.len());
+
+        let mut single = Self {
+            path: path.to_string(),
+            file,
+            pool,
+            directory: Directory::default(),
+            directory_pages: Vec::new(),
+            pending_free: Vec::new(),
+            page_count: (length as usize).div_ceil(PAGE_SIZE),
+            dirty: true,
+        };
+        if length == 0 {
+            single.page_count = 1;
+            single.commit(true)?;
+        } else {
+            single.load()?;
+        }
+        Ok(Arc::new(Mutex::new(single)))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn pool(&self) -> SharedPool {
+        self.pool.clone()
+    }
+
+    pub fn catalog(&self) -> &CatalogData {
+        &self.directory.catalog
+    }
+
+    /// Replaces the catalog saved in the directory. It reaches the file on the next `commit`.
+    pub fn set_catalog(&mut self, catalog: CatalogData) {
+        self.directory.catalog = catalog;
+        self.dirty = true;
+    }
+
+    pub fn segment_names(&self) -> Vec<String> {
+        self.directory.segments.keys().cloned().collect()
+    }
+
+    pub fn has_segment(&self, name: &str) -> bool {
+        self.directory.segments.contains_key(name)
+    }
+
+    pub fn ensure_segment(&mut self, name: &str) {
+        if !self.has_segment(name) {
+            self.directory
+                .segments
+                .insert(name.to_string(), Segment::default());
+            self.dirty = true;
+        }
+    }
+
+    pub fn fingerprint(&self, name: &str) -> u64 {
+        self.directory
+            .segments
+            .get(name)
+            .map_or(0, |segment| segment.fingerprint)
+    }
+
+    pub fn set_fingerprint(&mut self, name: &str, fingerprint: u64) {
+        self.ensure_segment(name);
+        if let Some(segment) = self.directory.segments.get_mut(name) {
+            segment.fingerprint = fingerprint;
+        }
+    }
+
+    /// The free-space map of a segment, empty and for 0 slots per page until it is built.
+    pub fn free_space(&self, name: &str) -> Option<&FreeSpaceMap> {
+        self.directory
+            .segments
+            .get(name)
+            .map(|segment| &segment.free_space)
+    }
+
+    pub fn set_free_space(&mut self, name: &str, free_space: FreeSpaceMap) {
+        self.ensure_segment(name);
+        if let Some(segment) = self.directory.segments.get_mut(name) {
+            segment.free_space = free_space;
+            self.dirty = true;
+        }
+    }
+
+    /// Records whether page `page_index` of a segment has room. It is saved with
+    /// the directory on the next `commit`.
+    pub fn mark_free_space(&mut self, name: &str, page_index: usize, has_room: bool) {
+        if let Some(segment) = self.directory.segments.get_mut(name)
+            && segment.free_space.set(page_index, has_room)
+        {
+            self.dirty = true;
+        }
+    }
+
+    pub fn segment_len(&self, name: &str) -> usize {
+        self.directory
+            .segments
+            .get(name)
+            .map_or(0, |segment| segment.pages.len())
+    }
+
+    /// The file page holding page `page_index` of a segment, if it has been allocated.
+    pub fn page(&self, name: &str, page_index: usize) -> Option<usize> {
+        self.directory
+            .segments
+            .get(name)?
+            .pages
+            .get(page_index)
+            .copied()
+    }
+
+    /// Like `page`, but allocates the page, and any before it, if the segment is shorter.
+    pub fn page_for_write(&mut self, name: &str, page_index: usize) -> usize {
+        self.ensure_segment(name);
+        while self.segment_len(name) <= page_index {
+            let page = self.allocate();
+            if let Some(segment) = self.directory.segments.get_mut(name) {
+                segment.pages.push(page);
+            }
+            self.dirty = true;
+        }
+        self.page(name, page_index).unwrap_or_default()
+    }
+
+    /// Frees the pages of a segment and forgets it, along with any of them in the pool.
+    pub fn remove_segment(&mut self, name: &str) {
+        if let Some(segment) = self.directory.segments.remove(name) {
+            let mut pool = buffer_pool::lock(&self.pool);
+            for &page in &segment.pages {
+                pool.discard_page(&self.path, page);
+            }
+            self.pending_free.extend(segment.pages);
+            self.dirty = true;
+        }
+    }
+
+    /// Renames a segment, replacing any segment already called `to`.
+    pub fn rename_segment(&mut self, from: &str, to: &str) {
+        if let Some(segment) = self.directory.segments.remove(from) {
+            self.remove_segment(to);
+            self.directory.segments.insert(to.to_string(), segment);
+            self.dirty = true;
+        }
+    }
+
+    /// Writes every dirty page, then saves the directory if it changed, syncing
+    /// between the steps if `sync` is set. Trailing free pages are cut off the file.
+    pub fn commit(&mut self, sync: bool) -> Result<()> {
+        // Table pages first, so the new directory never points at pages not yet written
+        buffer_pool::lock(&self.pool).flush_file(&self.path, sync)?;
+        if !self.dirty {
+            return Ok(());
+        }
+
+        // Pages freed now are listed as free in the new directory, but can't hold it
+        let mut released = std::mem::take(&mut self.directory_pages);
+        released.append(&mut self.pending_free);
+        let chunks = self.directory_json(&released)?.len().div_ceil(CHUNK_SIZE);
+        let pages: Vec<usize> = (0..chunks.max(1)).map(|_| self.allocate()).collect();
+        // Taking pages off the free list only makes the JSON shorter
+        let json = self.directory_json(&released)?;
+
+        let mut pool = buffer_pool::lock(&self.pool);
+        let mut chunks = json.chunks(CHUNK_SIZE);
+        for (i, &index) in pages.iter().enumerate() {
+            let chunk = chunks.next().unwrap_or_default();
+            let mut page = Page::new();
+            let next = pages.get(i + 1).copied().unwrap_or(0) as u32;
+            page.data[0..4].copy_from_slice(&next.to_le_bytes());
+            page.data[4..8].copy_from_slice(&(chunk.len() as u32).to_le_bytes());
+            page.data[HEADER_SIZE..HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+            store_page(&mut pool, &self.path, index, &page)?;
+        }
+        pool.flush_file(&self.path, sync)?;
+
+        let mut header = Page::new();
+        header.data[0..8].copy_from_slice(&SINGLE_FILE_MAGIC);
+        header.data[8..12].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header.data[12..16].copy_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+        header.data[16..20].copy_from_slice(&(pages[0] as u32).to_le_bytes());
+        header.data[20..24].copy_from_slice(&(json.len() as u32).to_le_bytes());
+        store_page(&mut pool, &self.path, 0, &header)?;
+        pool.flush_file(&self.path, sync)?;
+
+        self.directory.free_pages.extend(released);
+        self.directory_pages = pages;
+        self.dirty = false;
+
+        // Give trailing free pages back to the file system
+        let old_count = self.page_count;
+        while self.page_count > 1 && self.directory.free_pages.remove(&(self.page_count - 1)) {
+            self.page_count -= 1;
+            pool.discard_page(&self.path, self.page_count);
+        }
+        if self.page_count < old_count {
+            self.file.set_len((self.page_count * PAGE_SIZE) as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Describes pages claimed twice or by nothing, and segments for which `owned`
+    /// is false.
+    pub fn check(&self, owned: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut owners: BTreeMap<usize, String> = BTreeMap::new();
+        let mut claim = |page: usize, owner: String, problems: &mut Vec<String>| {
+            if page == 0 || page >= self.page_count {
+                problems.push(format!(
+                    "{} points at page {}, outside the file",
+                    owner, page
+                ));
+            } else if let Some(previous) = owners.insert(page, owner.clone()) {
+                problems.push(format!(
+                    "Page {} belongs to both {} and {}",
+                    page, previous, owner
+                ));
+            }
+        };
+
+        for (name, segment) in &self.directory.segments {
+            if !owned(name) {
+                problems.push(format!(
+                    "Segment {} doesn't belong to any table in the catalog",
+                    name
+                ));
+            }
+            for &page in &segment.pages {
+                claim(page, format!("table {}", name), &mut problems);
+            }
+        }
+        for &page in &self.directory_pages {
+            claim(page, "the directory".to_string(), &mut problems);
+        }
+        for &page in self.directory.free_pages.iter().chain(&self.pending_free) {
+            claim(page, "the free list".to_string(), &mut problems);
+        }
+
+        let leaked = (1..self.page_count)
+            .filter(|page| !owners.contains_key(page))
+            .count();
+        if leaked > 0 {
+            problems.push(format!(
+                "{} pages of {} aren't used by anything and aren't free",
+                leaked, self.path
+            ));
+        }
+        problems
+    }
+
+    /// Takes the lowest free page, or grows the file by one.
+    fn allocate(&mut self) -> usize {
+        match self.directory.free_pages.pop_first() {
+            Some(page) => page,
+            None => {
+                self.page_count += 1;
+                self.page_count - 1
+            }
+        }
+    }
+
+    /// The directory as it will be saved, with `released` pages listed as free.
+    fn directory_json(&self, released: &[usize]) -> Result<Vec<u8>> {
+        #[derive(Serialize)]
+        struct Saved<'a> {
+            catalog: &'a CatalogData,
+            segments: &'a BTreeMap<String, Segment>,
+            free_pages: BTreeSet<usize>,
+        }
+        let saved = Saved {
+            catalog: &self.directory.catalog,
+            segments: &self.directory.segments,
+            free_pages: self
+                .directory
+                .free_pages
+                .iter()
+                .chain(released)
+                .copied()
+                .collect(),
+        };
+        serde_json::to_vec(&saved).map_err(|e| Error::Io(std::io::Error::other(e)))
+    }
+
+    /// Reads the header and the directory it points at.
+    fn load(&mut self) -> Result<()> {
+        let path = self.path.clone();
+        let mut pool = buffer_pool::lock(&self.pool);
+        let header = fetch_page(&mut pool, &mut self.file, &path, 0, || {
+            "the header page".to_string()
+        })?;
+        if header.data[0..8] != SINGLE_FILE_MAGIC {
+            return Err(Error::Corruption(format!(
+                "{} is not a single-file ScarDB database",
+                path
+            )));
+        }
+        let u32_at = |at: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&header.data[at..at + 4]);
+            u32::from_le_bytes(bytes) as usize
+        };
+        if u32_at(8) != FORMAT_VERSION as usize || u32_at(12) != PAGE_SIZE {
+            return Err(Error::Corruption(format!(
+                "{} uses file format {} with {}-byte pages, expected format {} with {}-byte pages",
+                path,
+                u32_at(8),
+                u32_at(12),
+                FORMAT_VERSION,
+                PAGE_SIZE
+            )));
+        }
+
+        let (mut next, length) = (u32_at(16), u32_at(20));
+        let mut json = Vec::with_capacity(length);
+        while next != 0 {
+            if next >= self.page_count || self.directory_pages.contains(&next) {
+                return Err(Error::Corruption(format!(
+                    "{}: the directory chain is broken at page {}",
+                    path, next
+                )));
+            }
+            let page = fetch_page(&mut pool, &mut self.file, &path, next, || {
+                format!("directory page {}", next)
+            })?;
+            self.directory_pages.push(next);
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&page.data[4..8]);
+            let used = (u32::from_le_bytes(bytes) as usize).min(CHUNK_SIZE);
+            json.extend_from_slice(&page.data[HEADER_SIZE..HEADER_SIZE + used]);
+            bytes.copy_from_slice(&page.data[0..4]);
+            next = u32::from_le_bytes(bytes) as usize;
+        }
+        if json.len() != length {
+            return Err(Error::Corruption(format!(
+                "{}: the directory is {} bytes, the header says {}",
+                path,
+                json.len(),
+                length
+            )));
+        }
+
+        self.directory = serde_json::from_slice(&json).map_err(|e| {
+            Error::Corruption(format!("{}: the directory can't be read: {}", path, e))
+        })?;
+        // Pages cut off the end of the file may still be listed as free
+        let page_count = self.page_count;
+        self.directory.free_pages.retain(|&page| page < page_count);
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for SingleFile {
+    /// Saves what's left on a best-effort basis; call `commit` first to see errors.
+    fn drop(&mut self) {
+        let _ = self.commit(true);
+    }
+}
+
+/// Locks the file. As with the buffer pool, a poisoned lock is still safe to use.
+pub fn lock(file: &SharedFile) -> MutexGuard<'_, SingleFile> {
+    file.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
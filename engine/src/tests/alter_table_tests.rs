@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::Database;
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_add_and_drop_column_rewrites_rows() {
+        let data_dir = "/tmp/test_alter_add_drop";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(10))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO users VALUES (1, 'Alice')").unwrap();
+        run(&mut db, "INSERT INTO users VALUES (2, 'Bob')").unwrap();
+
+        // A NOT NULL column without a default can't be back-filled
+        assert!(
+            run(
+                &mut db,
+                "ALTER TABLE users ADD COLUMN active BOOLEAN NOT NULL"
+            )
+            .is_err()
+        );
+
+        run(
+            &mut db,
+            "ALTER TABLE users ADD COLUMN active BOOLEAN DEFAULT true",
+        )
+        .unwrap();
+        let (columns, rows) = query(&mut db, "SELECT * FROM users");
+        assert_eq!(columns, vec!["id", "name", "active"]);
+        assert!(rows.contains(&vec![
+            Field::Integer(1),
+            Field::Text("Alice".to_string()),
+            Field::Boolean(true)
+        ]));
+
+        // Omitted columns take their default
+        run(&mut db, "INSERT INTO users (id, name) VALUES (3, 'Carol')").unwrap();
+        run(&mut db, "ALTER TABLE users DROP COLUMN name").unwrap();
+        let (columns, rows) = query(&mut db, "SELECT * FROM users");
+        assert_eq!(columns, vec!["id", "active"]);
+        assert_eq!(rows.len(), 3);
+        assert!(rows.contains(&vec![Field::Integer(3), Field::Boolean(true)]));
+
+        assert!(run(&mut db, "ALTER TABLE users DROP COLUMN id").is_err());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_rename_and_widen() {
+        let data_dir = "/tmp/test_alter_rename_widen";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE items (id INT PRIMARY KEY, label VARCHAR(4))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO items VALUES (1, 'abcd')").unwrap();
+
+        run(&mut db, "ALTER TABLE items RENAME COLUMN label TO title").unwrap();
+        run(&mut db, "ALTER TABLE items RENAME TO products").unwrap();
+        assert!(run(&mut db, "SELECT * FROM items").is_err());
+
+        // Narrowing is rejected, widening rewrites the rows
+        assert!(
+            run(
+                &mut db,
+                "ALTER TABLE products ALTER COLUMN title SET DATA TYPE VARCHAR(2)"
+            )
+            .is_err()
+        );
+        run(
+            &mut db,
+            "ALTER TABLE products ALTER COLUMN title SET DATA TYPE VARCHAR(12)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO products VALUES (2, 'longer title')").unwrap();
+
+        let (columns, rows) = query(&mut db, "SELECT * FROM products");
+        assert_eq!(columns, vec!["id", "title"]);
+        assert!(rows.contains(&vec![Field::Integer(1), Field::Text("abcd".to_string())]));
+        assert!(rows.contains(&vec![
+            Field::Integer(2),
+            Field::Text("longer title".to_string())
+        ]));
+
+        // The new layout survives a reopen
+        drop(db);
+        let mut db = Database::open(data_dir).unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM products");
+        assert_eq!(rows.len(), 2);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_rewrite_spares_table_named_like_its_copy() {
+        let data_dir = "/tmp/test_alter_tmp_name";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE t.tmp (id INT PRIMARY KEY, name TEXT)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1)").unwrap();
+        run(&mut db, "INSERT INTO t.tmp VALUES (7, 'kept')").unwrap();
+
+        // The rewritten copy of t must not land on t.tmp
+        run(
+            &mut db,
+            "ALTER TABLE t ADD COLUMN flag BOOLEAN DEFAULT true",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM t");
+        assert_eq!(rows, vec![vec![Field::Integer(1), Field::Boolean(true)]]);
+        let (_, rows) = query(&mut db, "SELECT * FROM t.tmp");
+        assert_eq!(
+            rows,
+            vec![vec![Field::Integer(7), Field::Text("kept".to_string())]]
+        );
+        assert!(db.integrity_check().unwrap().is_empty());
+        drop(db);
+
+        let mut db = Database::open(data_dir).unwrap();
+        assert_eq!(query(&mut db, "SELECT * FROM t.tmp").1.len(), 1);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
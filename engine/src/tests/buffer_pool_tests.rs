@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::Database;
+    use crate::error::Result;
+    use crate::storage::buffer_pool::{self, BufferPool};
+    use crate::storage::pager::{PAGE_SIZE, Page, Pager};
+    use crate::tests::test_utils::{query, run};
+    use std::fs;
+
+    fn page_with(byte: u8) -> Result<Page> {
+        let mut page = Page::new();
+        page.data[100] = byte;
+        Ok(page)
+    }
+
+    #[test]
+    fn test_clock_eviction_and_write_back() {
+        let path = "/tmp/test_buffer_pool.db";
+        let _ = fs::remove_file(path);
+        let mut pool = BufferPool::new(2);
+
+        let a = pool.fetch(path, 0, || page_with(1)).unwrap();
+        pool.page_mut(a).data[100] = 9;
+        pool.unpin(a, true);
+        let b = pool.fetch(path, 1, || page_with(2)).unwrap();
+        pool.unpin(b, false);
+
+        // A hit doesn't call the loader
+        let a = pool.fetch(path, 0, || panic!("page 0 is cached")).unwrap();
+        assert_eq!(pool.page(a).data[100], 9);
+        pool.unpin(a, false);
+
+        // The pool is full: page 0 gets evicted and written back, as it is dirty
+        let c = pool.fetch(path, 2, || page_with(3)).unwrap();
+        pool.unpin(c, false);
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 3));
+        assert_eq!((stats.evictions, stats.writes), (1, 1));
+        let bytes = fs::read(path).unwrap();
+        assert_eq!(bytes.len(), PAGE_SIZE);
+        assert_eq!(bytes[100], 9);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_pinned_pages_are_not_evicted() {
+        let path = "/tmp/test_buffer_pool_pins.db";
+        let mut pool = BufferPool::new(2);
+
+        let a = pool.fetch(path, 0, || page_with(1)).unwrap();
+        let b = pool.fetch(path, 1, || page_with(2)).unwrap();
+        assert!(pool.fetch(path, 2, || page_with(3)).is_err());
+
+        // Once page 1 is released it is the only candidate
+        pool.unpin(b, false);
+        let c = pool.fetch(path, 2, || page_with(3)).unwrap();
+        assert_eq!(pool.page(a).data[100], 1);
+        assert_eq!(pool.page(c).data[100], 3);
+        pool.unpin(a, false);
+        pool.unpin(c, false);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_pages_stay_pinned_while_read() {
+        let path = "/tmp/test_buffer_pool_guards.db";
+        let _ = fs::remove_file(path);
+        let pool = BufferPool::shared(2);
+        let mut pager = Pager::with_pool(path, pool.clone()).unwrap();
+        for i in 0..4 {
+            let mut page = Page::new();
+            page.data[100] = i as u8;
+            pager.write_page(i, &page).unwrap();
+        }
+
+        // While page 0 is held, the other pages take turns in the one frame left
+        let held = pager.read_page(0).unwrap();
+        for i in 1..4 {
+            assert_eq!(pager.read_page(i).unwrap().data[100], i as u8);
+        }
+        let hits = buffer_pool::lock(&pool).stats().hits;
+        assert_eq!(pager.read_page(0).unwrap().data[100], 0);
+        assert_eq!(buffer_pool::lock(&pool).stats().hits, hits + 1);
+
+        // A write goes to a new copy; the guard keeps the page as it was read
+        let mut page = held.to_page();
+        page.data[100] = 7;
+        pager.write_page(0, &page).unwrap();
+        assert_eq!(held.data[100], 0);
+        assert_eq!(pager.read_page(0).unwrap().data[100], 7);
+
+        // With both frames held nothing can be evicted, until a guard is dropped
+        let other = pager.read_page(1).unwrap();
+        assert!(pager.read_page(2).is_err());
+        drop(held);
+        assert_eq!(pager.read_page(2).unwrap().data[100], 2);
+        drop(other);
+
+        drop(pager);
+        drop(pool);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_database_with_a_small_pool() {
+        let data_dir = "/tmp/test_database_small_pool";
+        let _ = fs::remove_dir_all(data_dir);
+        let mut db = Database::open_with_pool_size(data_dir, 2).unwrap();
+
+        // Rows of about 1 KiB put 3 rows on a page, so this spans many more pages than fit
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY, a VARCHAR(500), b VARCHAR(500))",
+        )
+        .unwrap();
+        for i in 0..30 {
+            run(&mut db, &format!("INSERT INTO t VALUES ({}, 'x', 'y')", i)).unwrap();
+        }
+        run(&mut db, "UPDATE t SET a = 'z' WHERE id > 14").unwrap();
+        run(&mut db, "DELETE FROM t WHERE id < 5").unwrap();
+        assert!(db.buffer_pool_stats().evictions > 0);
+
+        let (_, rows) = query(&mut db, "SELECT * FROM t WHERE a = 'z'");
+        assert_eq!(rows.len(), 15);
+
+        // Everything reached the disk
+        drop(db);
+        let mut db = Database::open_with_pool_size(data_dir, 2).unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM t");
+        assert_eq!(rows.len(), 25);
+
+        // Repeated reads of a table that fits are served from the pool
+        let mut db = Database::open(data_dir).unwrap();
+        query(&mut db, "SELECT * FROM t");
+        let before = db.buffer_pool_stats();
+        query(&mut db, "SELECT * FROM t");
+        let after = db.buffer_pool_stats();
+        assert_eq!(after.misses, before.misses);
+        assert!(after.hits > before.hits);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -0,0 +1,175 @@
+#[cfg(test)]
+mod tests {
+    use crate::catalog::{Catalog, SEQUENCE_BATCH};
+    use crate::engine::Database;
+    use crate::storage::pager::PAGE_SIZE;
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    /// The sequence of `table` as saved in `scardb_master`.
+    fn saved_sequence(data_dir: &str, table: &str) -> i64 {
+        Catalog::read_directory(data_dir).unwrap().sequences[table] as i64
+    }
+
+    #[test]
+    fn test_corrupt_catalog_is_an_error() {
+        let data_dir = "/tmp/test_corrupt_catalog";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+        drop(db);
+
+        // A torn write: the tables must not silently disappear
+        let path = format!("{}/scardb_master.db", data_dir);
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..PAGE_SIZE + 100]).unwrap();
+        let err = Database::open(data_dir).err().unwrap();
+        assert_eq!(err.sqlstate(), "XX001");
+        assert!(err.to_string().contains("scardb_master.db"));
+
+        // A temporary file left by a crash mid-save is ignored
+        fs::write(&path, &bytes).unwrap();
+        fs::write(format!("{}.tmp", path), b"{ half").unwrap();
+        let mut db = Database::open(data_dir).unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1)").unwrap();
+        run(&mut db, "CREATE TABLE u (id INT)").unwrap();
+        assert!(!fs::exists(format!("{}.tmp", path)).unwrap());
+        drop(db);
+        assert!(Database::open(data_dir).is_ok());
+
+        // The catalog.json of an older version that can't be parsed is an error too
+        fs::remove_file(&path).unwrap();
+        let json = format!("{}/catalog.json", data_dir);
+        fs::write(&json, b"{ half").unwrap();
+        let err = Database::open(data_dir).err().unwrap();
+        assert_eq!(err.sqlstate(), "XX001");
+        assert!(err.to_string().contains("catalog.json"));
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_catalog_is_stored_in_a_table() {
+        let data_dir = "/tmp/test_catalog_table";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY AUTOINCREMENT, name TEXT)",
+        )
+        .unwrap();
+        // A definition too long for one row is split into parts
+        let columns: Vec<String> = (0..40)
+            .map(|i| format!("a_rather_long_column_name_{} INT", i))
+            .collect();
+        run(
+            &mut db,
+            &format!("CREATE TABLE wide ({})", columns.join(", ")),
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('a')").unwrap();
+
+        let path = format!("{}/scardb_master.db", data_dir);
+        assert!(fs::exists(&path).unwrap());
+        assert!(!fs::exists(format!("{}/catalog.json", data_dir)).unwrap());
+        let (columns, rows) = query(
+            &mut db,
+            "SELECT table_name, part, sequence FROM scardb_master",
+        );
+        assert_eq!(columns, vec!["table_name", "part", "sequence"]);
+        assert_eq!(
+            rows[0],
+            vec![
+                Field::Text("t".to_string()),
+                Field::Integer(0),
+                Field::Integer(SEQUENCE_BATCH)
+            ]
+        );
+        assert!(rows.len() > 3);
+        assert_eq!(
+            run(&mut db, "DELETE FROM scardb_master")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("42809")
+        );
+        drop(db);
+
+        // Both tables come back from the table
+        let mut db = Database::open(data_dir).unwrap();
+        assert_eq!(db.catalog.tables["wide"].columns.len(), 40);
+        run(&mut db, "INSERT INTO t (name) VALUES ('b')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t WHERE name = 'b'");
+        assert_eq!(rows, vec![vec![Field::Integer(2)]]);
+        drop(db);
+
+        // A catalog.json left by an older version is moved into the table
+        let data = Catalog::read_directory(data_dir).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::write(
+            format!("{}/catalog.json", data_dir),
+            serde_json::to_string(&data).unwrap(),
+        )
+        .unwrap();
+        let mut db = Database::open(data_dir).unwrap();
+        assert!(fs::exists(&path).unwrap());
+        assert!(!fs::exists(format!("{}/catalog.json", data_dir)).unwrap());
+        let (_, rows) = query(&mut db, "SELECT name FROM t");
+        assert_eq!(rows.len(), 2);
+        assert!(db.integrity_check().unwrap().is_empty());
+        drop(db);
+
+        // Unless the old catalog has a table of that name, whose rows it would replace
+        let mut data = Catalog::read_directory(data_dir).unwrap();
+        let mut schema = data.tables["t"].clone();
+        schema.table_name = "scardb_master".to_string();
+        data.tables.insert(schema.table_name.clone(), schema);
+        fs::remove_file(&path).unwrap();
+        fs::write(
+            format!("{}/catalog.json", data_dir),
+            serde_json::to_string(&data).unwrap(),
+        )
+        .unwrap();
+        let err = Database::open(data_dir).err().unwrap();
+        assert_eq!(err.sqlstate(), "42P07");
+        assert!(!fs::exists(&path).unwrap());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_sequences_are_saved_in_batches() {
+        let data_dir = "/tmp/test_sequence_batches";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY AUTOINCREMENT, name TEXT)",
+        )
+        .unwrap();
+        for _ in 0..5 {
+            run(&mut db, "INSERT INTO t (name) VALUES ('a')").unwrap();
+        }
+        // The catalog was saved once, reserving a batch of ids
+        assert_eq!(saved_sequence(data_dir, "t"), SEQUENCE_BATCH as i64);
+
+        // A crash loses the unused ids, but never hands out one twice
+        std::mem::forget(db);
+        let mut db = Database::open(data_dir).unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('b')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t WHERE name = 'b'");
+        assert_eq!(rows, vec![vec![Field::Integer(SEQUENCE_BATCH + 1)]]);
+
+        // A clean shutdown gives the unused ids back
+        drop(db);
+        assert_eq!(saved_sequence(data_dir, "t"), SEQUENCE_BATCH as i64 + 1);
+        let mut db = Database::open(data_dir).unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('c')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t WHERE name = 'c'");
+        assert_eq!(rows, vec![vec![Field::Integer(SEQUENCE_BATCH + 2)]]);
+
+        // An id set by hand past the reserved ones is saved right away
+        run(&mut db, "UPDATE t SET id = 1000 WHERE name = 'c'").unwrap();
+        assert_eq!(saved_sequence(data_dir, "t"), 1000);
+        drop(db);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -0,0 +1,160 @@
+#[cfg(test)]
+mod tests {
+    use crate::query::AccessPath;
+    use crate::sql::Command;
+    use crate::sql::parser::parse_sql;
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_composite_primary_key() {
+        let data_dir = "/tmp/test_composite_primary_key";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE enrollments (student INT, course VARCHAR(10), grade INT, \
+             PRIMARY KEY (student, course))",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "INSERT INTO enrollments VALUES (1, 'math', 80), (1, 'art', 70), (2, 'math', 90)",
+        )
+        .unwrap();
+
+        // Only the full key has to be unique
+        assert!(run(&mut db, "INSERT INTO enrollments VALUES (1, 'math', 50)").is_err());
+        run(&mut db, "INSERT INTO enrollments VALUES (2, 'art', 60)").unwrap();
+
+        let (_, rows) = query(
+            &mut db,
+            "SELECT * FROM enrollments WHERE course = 'math' AND student = 2",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                Field::Integer(2),
+                Field::Text("math".to_string()),
+                Field::Integer(90)
+            ]]
+        );
+
+        // A prefix of the key narrows the scan, the rest of the filter still applies
+        let (_, rows) = query(&mut db, "SELECT * FROM enrollments WHERE student = 1");
+        assert_eq!(rows.len(), 2);
+        let (_, rows) = query(
+            &mut db,
+            "SELECT * FROM enrollments WHERE student = 1 AND grade > 75",
+        );
+        assert_eq!(rows.len(), 1);
+
+        // The key survives a reopen, where the index is rebuilt from disk
+        let mut db = crate::engine::Database::open(data_dir).unwrap();
+        assert!(run(&mut db, "INSERT INTO enrollments VALUES (2, 'art', 10)").is_err());
+        run(
+            &mut db,
+            "DELETE FROM enrollments WHERE student = 2 AND course = 'art'",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO enrollments VALUES (2, 'art', 10)").unwrap();
+
+        run(
+            &mut db,
+            "UPDATE enrollments SET grade = 0 WHERE student = 1",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM enrollments WHERE grade = 0");
+        assert_eq!(rows.len(), 2);
+
+        let (columns, rows) = query(&mut db, "SELECT * FROM enrollments WHERE student = 3");
+        assert_eq!(columns, vec!["student", "course", "grade"]);
+        assert!(rows.is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_one_primary_key_per_table() {
+        // Each form declares two keys, which must not be merged into one on (a, b)
+        for sql in [
+            "CREATE TABLE t (a INT PRIMARY KEY, b INT PRIMARY KEY)",
+            "CREATE TABLE t (a INT PRIMARY KEY, b INT, PRIMARY KEY (b))",
+        ] {
+            let err = parse_sql(sql).err();
+            assert_eq!(err.map(|e| e.sqlstate()), Some("42P16"), "{}", sql);
+        }
+        assert!(parse_sql("CREATE TABLE t (a INT, b INT, PRIMARY KEY (a, b))").is_ok());
+    }
+
+    #[test]
+    fn test_access_path_planning() {
+        let data_dir = "/tmp/test_access_path_planning";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (a INT, b INT, c INT, PRIMARY KEY (a, b))",
+        )
+        .unwrap();
+        let schema = db.catalog.tables.get("t").unwrap().clone();
+
+        let plan = |sql: &str| {
+            let Command::Select { filter, .. } = parse_sql(sql).unwrap().remove(0) else {
+                panic!("Expected a SELECT");
+            };
+            AccessPath::plan(&schema, filter.as_ref())
+        };
+
+        assert_eq!(
+            plan("SELECT * FROM t WHERE b = 2 AND a = 1"),
+            AccessPath::PrimaryKeyLookup(vec![Field::Integer(1), Field::Integer(2)])
+        );
+        assert_eq!(
+            plan("SELECT * FROM t WHERE a = 1 AND c = 3"),
+            AccessPath::PrimaryKeyPrefix(vec![Field::Integer(1)])
+        );
+        // b alone is not a prefix of the key, and OR can't use the index
+        assert_eq!(plan("SELECT * FROM t WHERE b = 2"), AccessPath::FullScan);
+        assert_eq!(
+            plan("SELECT * FROM t WHERE a = 1 OR b = 2"),
+            AccessPath::FullScan
+        );
+
+        assert!(
+            run(
+                &mut db,
+                "CREATE TABLE u (a INT PRIMARY KEY, b INT, PRIMARY KEY (b))"
+            )
+            .is_err()
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_key_values_containing_separators() {
+        let data_dir = "/tmp/test_key_values_containing_separators";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (a VARCHAR(10), b VARCHAR(10), PRIMARY KEY (a, b))",
+        )
+        .unwrap();
+
+        // ('a<sep>b', 'c') and ('a', 'b<sep>c') are different keys
+        let sep = '\u{1f}';
+        run(&mut db, &format!("INSERT INTO t VALUES ('a{}b', 'c')", sep)).unwrap();
+        run(&mut db, &format!("INSERT INTO t VALUES ('a', 'b{}c')", sep)).unwrap();
+        run(&mut db, "INSERT INTO t VALUES ('a\\', 'x'), ('a', '\\x')").unwrap();
+
+        // A prefix scan only finds the rows whose first value matches exactly
+        let (_, rows) = query(&mut db, "SELECT b FROM t WHERE a = 'a'");
+        assert_eq!(rows.len(), 2);
+        let (_, rows) = query(&mut db, &format!("SELECT b FROM t WHERE a = 'a{}b'", sep));
+        assert_eq!(rows, vec![vec![Field::Text("c".to_string())]]);
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -0,0 +1,234 @@
+#[cfg(test)]
+mod tests {
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_unique_and_check() {
+        let data_dir = "/tmp/test_constraints_unique_check";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE accounts (id INT PRIMARY KEY, email VARCHAR(20) UNIQUE, \
+             balance INT NOT NULL CHECK (balance >= 0))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO accounts VALUES (1, 'a@x.io', 10)").unwrap();
+
+        assert!(run(&mut db, "INSERT INTO accounts VALUES (2, 'a@x.io', 5)").is_err());
+        assert!(run(&mut db, "INSERT INTO accounts VALUES (2, 'b@x.io', -5)").is_err());
+        assert!(run(&mut db, "INSERT INTO accounts VALUES (2, 'b@x.io', NULL)").is_err());
+
+        // NULLs never collide in a unique index
+        run(&mut db, "INSERT INTO accounts VALUES (2, NULL, 5)").unwrap();
+        run(&mut db, "INSERT INTO accounts VALUES (3, NULL, 5)").unwrap();
+
+        // Updates are held to the same rules
+        assert!(run(&mut db, "UPDATE accounts SET email = 'a@x.io' WHERE id = 2").is_err());
+        assert!(run(&mut db, "UPDATE accounts SET balance = -1 WHERE id = 1").is_err());
+        run(&mut db, "UPDATE accounts SET email = 'c@x.io' WHERE id = 2").unwrap();
+
+        // The old value is free again once it's been replaced
+        run(&mut db, "UPDATE accounts SET email = 'd@x.io' WHERE id = 1").unwrap();
+        run(&mut db, "INSERT INTO accounts VALUES (4, 'a@x.io', 0)").unwrap();
+
+        let (_, rows) = query(&mut db, "SELECT * FROM accounts");
+        assert_eq!(rows.len(), 4);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let data_dir = "/tmp/test_constraints_foreign_keys";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, name TEXT)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE orders (id INT PRIMARY KEY, \
+             user_id INT REFERENCES users(id) ON DELETE CASCADE)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE notes (id INT PRIMARY KEY, user_id INT, \
+             FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE invoices (id INT PRIMARY KEY, order_id INT REFERENCES orders(id))",
+        )
+        .unwrap();
+
+        run(&mut db, "INSERT INTO users VALUES (1, 'Alice'), (2, 'Bob')").unwrap();
+        run(&mut db, "INSERT INTO orders VALUES (10, 1), (11, 2)").unwrap();
+        run(&mut db, "INSERT INTO notes VALUES (100, 1), (101, 2)").unwrap();
+        run(&mut db, "INSERT INTO invoices VALUES (1000, 11)").unwrap();
+
+        assert!(run(&mut db, "INSERT INTO orders VALUES (12, 99)").is_err());
+        assert!(run(&mut db, "UPDATE orders SET user_id = 99 WHERE id = 10").is_err());
+        assert!(run(&mut db, "DROP TABLE users").is_err());
+
+        // Cascading to orders 11 would orphan invoice 1000, which restricts
+        assert!(run(&mut db, "DELETE FROM users WHERE id = 2").is_err());
+        let (_, rows) = query(&mut db, "SELECT * FROM orders");
+        assert_eq!(rows.len(), 2);
+
+        run(&mut db, "DELETE FROM users WHERE id = 1").unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM orders");
+        assert_eq!(rows, vec![vec![Field::Integer(11), Field::Integer(2)]]);
+        let (_, rows) = query(&mut db, "SELECT * FROM notes WHERE id = 100");
+        assert_eq!(rows, vec![vec![Field::Integer(100), Field::Null]]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_primary_key_update() {
+        let data_dir = "/tmp/test_constraints_primary_key_update";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY AUTOINCREMENT, name TEXT)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE orders (id INT PRIMARY KEY, \
+             user_id INT REFERENCES users(id) ON UPDATE CASCADE)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE notes (id INT PRIMARY KEY, \
+             user_id INT REFERENCES users(id) ON UPDATE SET NULL)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE invoices (id INT PRIMARY KEY, order_id INT REFERENCES orders(id))",
+        )
+        .unwrap();
+
+        run(
+            &mut db,
+            "INSERT INTO users (name) VALUES ('Alice'), ('Bob')",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO orders VALUES (10, 1), (11, 2)").unwrap();
+        run(&mut db, "INSERT INTO notes VALUES (100, 1)").unwrap();
+        run(&mut db, "INSERT INTO invoices VALUES (1000, 11)").unwrap();
+
+        // The new key must be free
+        assert!(run(&mut db, "UPDATE users SET id = 2 WHERE id = 1").is_err());
+
+        run(&mut db, "UPDATE users SET id = 5 WHERE id = 1").unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM users WHERE id = 5");
+        assert_eq!(rows.len(), 1);
+        let (_, rows) = query(&mut db, "SELECT * FROM orders WHERE id = 10");
+        assert_eq!(rows, vec![vec![Field::Integer(10), Field::Integer(5)]]);
+        let (_, rows) = query(&mut db, "SELECT * FROM notes");
+        assert_eq!(rows, vec![vec![Field::Integer(100), Field::Null]]);
+
+        // The old key is gone from the index, so it can be reused
+        run(&mut db, "INSERT INTO orders VALUES (1, 5)").unwrap();
+        assert!(run(&mut db, "INSERT INTO orders VALUES (12, 1)").is_err());
+
+        // Invoice 1000 restricts changes to order 11, and nothing is written
+        assert!(run(&mut db, "UPDATE orders SET id = 13 WHERE id = 11").is_err());
+        let (_, rows) = query(&mut db, "SELECT * FROM orders WHERE id = 11");
+        assert_eq!(rows.len(), 1);
+
+        // The sequence skips past ids that were set by hand
+        run(&mut db, "INSERT INTO users (name) VALUES ('Carol')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM users WHERE name = 'Carol'");
+        assert_eq!(rows[0][0], Field::Integer(6));
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_text_longer_than_column() {
+        let data_dir = "/tmp/test_text_longer_than_column";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(3) UNIQUE)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1, 'abc')").unwrap();
+
+        // Nothing is cut short: 'abcd' would be stored as 'abc' and clash with row 1,
+        // and 'abé' is four bytes, so cutting it would split the 'é'
+        for sql in [
+            "INSERT INTO t VALUES (2, 'abcd')",
+            "INSERT INTO t VALUES (2, 'abé')",
+            "UPDATE t SET name = name || 'x'",
+            "INSERT INTO t VALUES (1, 'x') ON CONFLICT (id) DO UPDATE SET name = 'long'",
+        ] {
+            assert_eq!(run(&mut db, sql).err().map(|e| e.sqlstate()), Some("22001"));
+        }
+        let (_, rows) = query(&mut db, "SELECT name FROM t");
+        assert_eq!(rows, vec![vec![Field::Text("abc".to_string())]]);
+        run(&mut db, "INSERT INTO t VALUES (2, 'é')").unwrap();
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        assert_eq!(
+            run(&mut db, "CREATE TABLE d (name VARCHAR(2) DEFAULT 'abc')")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("22001")
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_update_checks_keys_before_writing() {
+        let data_dir = "/tmp/test_update_checks_keys";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY, code INT UNIQUE)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO t VALUES (10, 1), (2, 2), (3, 3)").unwrap();
+
+        // 10 and 3 would both become 4. Row 10 comes first on disk, but it keeps its key
+        assert_eq!(
+            run(&mut db, "UPDATE t SET id = id % 7 + 1")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("23505")
+        );
+        assert_eq!(
+            run(&mut db, "UPDATE t SET code = 7")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("23505")
+        );
+        let (_, rows) = query(&mut db, "SELECT code FROM t WHERE id = 10");
+        assert_eq!(rows, vec![vec![Field::Integer(1)]]);
+        assert!(query(&mut db, "SELECT * FROM t WHERE id = 4").1.is_empty());
+
+        // Keys shifted or swapped as a whole don't collide along the way
+        run(&mut db, "UPDATE t SET id = id + 1").unwrap();
+        run(&mut db, "UPDATE t SET code = 4 - code").unwrap();
+        let (_, rows) = query(&mut db, "SELECT code FROM t WHERE id = 4");
+        assert_eq!(rows, vec![vec![Field::Integer(1)]]);
+        let (_, rows) = query(&mut db, "SELECT code FROM t WHERE id = 11");
+        assert_eq!(rows, vec![vec![Field::Integer(3)]]);
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
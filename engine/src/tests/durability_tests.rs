@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::{Database, Synchronous};
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_synchronous_pragma() {
+        let data_dir = "/tmp/test_synchronous_pragma";
+        let mut db = fresh_db(data_dir);
+
+        let (_, rows) = query(&mut db, "PRAGMA synchronous");
+        assert_eq!(rows, vec![vec![Field::Text("FULL".to_string())]]);
+        for (sql, expected) in [
+            ("PRAGMA synchronous = normal", Synchronous::Normal),
+            ("PRAGMA synchronous = 0", Synchronous::Off),
+            ("PRAGMA synchronous('FULL')", Synchronous::Full),
+        ] {
+            run(&mut db, sql).unwrap();
+            assert_eq!(db.synchronous(), expected);
+        }
+        assert!(run(&mut db, "PRAGMA synchronous = sometimes").is_err());
+        assert!(run(&mut db, "PRAGMA no_such_setting").is_err());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_flushes_follow_the_setting() {
+        let data_dir = "/tmp/test_flushes_follow_the_setting";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)").unwrap();
+
+        // FULL: one sync per statement, however many rows it writes
+        let values: Vec<String> = (0..100).map(|i| format!("({}, 'x')", i)).collect();
+        let before = db.buffer_pool_stats();
+        run(
+            &mut db,
+            &format!("INSERT INTO t VALUES {}", values.join(", ")),
+        )
+        .unwrap();
+        let after = db.buffer_pool_stats();
+        assert_eq!(after.syncs - before.syncs, 1);
+
+        // NORMAL: written, not synced
+        db.set_synchronous(Synchronous::Normal);
+        let before = db.buffer_pool_stats();
+        run(&mut db, "INSERT INTO t VALUES (100, 'y')").unwrap();
+        let after = db.buffer_pool_stats();
+        assert!(after.writes > before.writes);
+        assert_eq!(after.syncs, before.syncs);
+
+        // OFF: left in the pool, yet visible to the next statement
+        db.set_synchronous(Synchronous::Off);
+        let before = db.buffer_pool_stats();
+        run(&mut db, "INSERT INTO t VALUES (101, 'z')").unwrap();
+        assert_eq!(db.buffer_pool_stats().writes, before.writes);
+        let (_, rows) = query(&mut db, "SELECT * FROM t WHERE id = 101");
+        assert_eq!(rows.len(), 1);
+
+        // Closing the database writes what is left
+        drop(db);
+        let mut db = Database::open(data_dir).unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM t");
+        assert_eq!(rows.len(), 102);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
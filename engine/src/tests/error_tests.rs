@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::sql::parser::parse_sql;
+    use crate::tests::test_utils::{fresh_db, run};
+    use std::fs;
+
+    fn sqlstate_of(db: &mut crate::engine::Database, sql: &str) -> &'static str {
+        match run(db, sql) {
+            Err(e) => e.sqlstate(),
+            Ok(_) => panic!("Expected an error from: {}", sql),
+        }
+    }
+
+    #[test]
+    fn test_error_sqlstates() {
+        let data_dir = "/tmp/test_error_sqlstates";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, email TEXT UNIQUE)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE posts (id INT PRIMARY KEY, user_id INT REFERENCES users(id))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO users (id, email) VALUES (1, 'a@x')").unwrap();
+
+        assert_eq!(sqlstate_of(&mut db, "SELECT * FROM missing"), "42P01");
+        assert_eq!(sqlstate_of(&mut db, "SELECT nope FROM users"), "42703");
+        assert_eq!(
+            sqlstate_of(&mut db, "CREATE TABLE users (id INT PRIMARY KEY)"),
+            "42P07"
+        );
+        assert_eq!(
+            sqlstate_of(&mut db, "INSERT INTO users (id, email) VALUES (1, 'b@x')"),
+            "23505"
+        );
+        assert_eq!(
+            sqlstate_of(&mut db, "INSERT INTO users (id, email) VALUES (2, 'a@x')"),
+            "23505"
+        );
+        assert_eq!(
+            sqlstate_of(&mut db, "INSERT INTO posts (id, user_id) VALUES (1, 99)"),
+            "23503"
+        );
+        assert_eq!(sqlstate_of(&mut db, "SELECT id / 0 FROM users"), "22000");
+        assert_eq!(
+            sqlstate_of(&mut db, "INSERT INTO users (id, email) VALUES ('x', 'c@x')"),
+            "42804"
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let e = parse_sql("SELECT * FROM users\nWHERE id = = 1").unwrap_err();
+        assert_eq!(e.sqlstate(), "42601");
+        match e {
+            Error::Parse { position, .. } => assert_eq!(position.map(|p| p.line), Some(2)),
+            _ => panic!("Expected a parse error"),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+#[cfg(test)]
+mod tests {
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    fn text(s: &str) -> Field {
+        Field::Text(s.to_string())
+    }
+
+    #[test]
+    fn test_update_with_expressions() {
+        let data_dir = "/tmp/test_expressions_update";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE accounts (id INT PRIMARY KEY, name VARCHAR(20), \
+             balance INT CHECK (balance >= 0))",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "INSERT INTO accounts VALUES (1, 'alice', 100), (2, 'bob', 5), (3, 'carol', NULL)",
+        )
+        .unwrap();
+
+        run(
+            &mut db,
+            "UPDATE accounts SET balance = balance - 10, name = UPPER(name) WHERE id = 1",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM accounts WHERE id = 1");
+        assert_eq!(
+            rows,
+            vec![vec![Field::Integer(1), text("ALICE"), Field::Integer(90)]]
+        );
+
+        // The CHECK constraint sees the computed value, and a failed row writes nothing
+        assert!(run(&mut db, "UPDATE accounts SET balance = balance - 10").is_err());
+        let (_, rows) = query(&mut db, "SELECT balance FROM accounts WHERE id = 1");
+        assert_eq!(rows, vec![vec![Field::Integer(90)]]);
+
+        // Arithmetic on NULL stays NULL, COALESCE picks the first non-NULL value
+        run(&mut db, "UPDATE accounts SET balance = balance * 2").unwrap();
+        let (_, rows) = query(&mut db, "SELECT balance FROM accounts WHERE id = 3");
+        assert_eq!(rows, vec![vec![Field::Null]]);
+        run(
+            &mut db,
+            "UPDATE accounts SET balance = COALESCE(balance, 0) + 1",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT balance FROM accounts WHERE id = 3");
+        assert_eq!(rows, vec![vec![Field::Integer(1)]]);
+
+        assert!(run(&mut db, "UPDATE accounts SET balance = balance / 0").is_err());
+        assert!(run(&mut db, "UPDATE accounts SET balance = name + 1").is_err());
+        assert!(run(&mut db, "UPDATE accounts SET balance = NOPE(balance)").is_err());
+
+        let (columns, rows) = query(
+            &mut db,
+            "SELECT name || '!' AS shout, LENGTH(name), -balance FROM accounts WHERE id = 2",
+        );
+        assert_eq!(columns, vec!["shout", "LENGTH(name)", "-balance"]);
+        assert_eq!(
+            rows,
+            vec![vec![text("bob!"), Field::Integer(3), Field::Integer(-11)]]
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_insert_select() {
+        let data_dir = "/tmp/test_expressions_insert_select";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR(20), active BOOLEAN)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE archive (id INT PRIMARY KEY AUTOINCREMENT, user_id INT, name VARCHAR(20))",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "INSERT INTO users VALUES (1, 'a', true), (2, 'b', false), (3, 'c', false)",
+        )
+        .unwrap();
+
+        run(
+            &mut db,
+            "INSERT INTO archive (user_id, name) SELECT id, UPPER(name) FROM users \
+             WHERE active = false",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM archive");
+        assert_eq!(
+            rows,
+            vec![
+                vec![Field::Integer(1), Field::Integer(2), text("B")],
+                vec![Field::Integer(2), Field::Integer(3), text("C")],
+            ]
+        );
+
+        // A table can be filled from itself, the query is read before anything is written
+        run(
+            &mut db,
+            "INSERT INTO users SELECT id + 10, name, active FROM users",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM users");
+        assert_eq!(rows.len(), 6);
+
+        // Constraints still apply to every inserted row
+        assert!(run(&mut db, "INSERT INTO users SELECT * FROM users").is_err());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_returning() {
+        let data_dir = "/tmp/test_expressions_returning";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY AUTOINCREMENT, name VARCHAR(20), score INT)",
+        )
+        .unwrap();
+
+        // Generated ids come back without a second query
+        let (columns, rows) = query(
+            &mut db,
+            "INSERT INTO users (name, score) VALUES ('a', 1), ('b', 2) RETURNING id, name",
+        );
+        assert_eq!(columns, vec!["id", "name"]);
+        assert_eq!(
+            rows,
+            vec![
+                vec![Field::Integer(1), text("a")],
+                vec![Field::Integer(2), text("b")],
+            ]
+        );
+
+        // UPDATE returns rows as they are after the change
+        let (_, rows) = query(
+            &mut db,
+            "UPDATE users SET score = score + 10 WHERE id = 2 RETURNING *",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![Field::Integer(2), text("b"), Field::Integer(12)]]
+        );
+
+        // DELETE returns the rows it removed, an empty result still has its columns
+        let (_, rows) = query(&mut db, "DELETE FROM users WHERE id = 1 RETURNING name");
+        assert_eq!(rows, vec![vec![text("a")]]);
+        let (columns, rows) = query(&mut db, "DELETE FROM users WHERE id = 1 RETURNING name");
+        assert_eq!(columns, vec!["name"]);
+        assert!(rows.is_empty());
+
+        // An unknown column is caught before the row is written
+        assert!(
+            run(
+                &mut db,
+                "INSERT INTO users (name, score) VALUES ('c', 3) RETURNING nope"
+            )
+            .is_err()
+        );
+        let (_, rows) = query(&mut db, "SELECT * FROM users");
+        assert_eq!(rows.len(), 1);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::Database;
+    use crate::storage::pager::{FORMAT_VERSION, HEADER_SIZE, MAGIC, PAGE_SIZE, Page, Pager};
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_header_page() {
+        let file_path = "/tmp/test_header_page.db";
+        let _ = fs::remove_file(file_path);
+
+        let mut pager = Pager::open(file_path).expect("Failed to open pager");
+        pager.set_schema_fingerprint(42).unwrap();
+        pager.write_page(0, &Page::new()).unwrap();
+        pager.flush().unwrap();
+        drop(pager);
+
+        let bytes = fs::read(file_path).unwrap();
+        assert_eq!(bytes.len(), 2 * PAGE_SIZE);
+        assert_eq!(bytes[..8], MAGIC);
+
+        let pager = Pager::open(file_path).expect("Failed to reopen pager");
+        let header = pager.header();
+        assert_eq!(header.format_version, FORMAT_VERSION);
+        assert_eq!(header.page_size as usize, PAGE_SIZE);
+        assert_eq!(header.schema_fingerprint, 42);
+        assert_eq!(pager.num_pages(), 1);
+
+        // Files from another program are refused
+        fs::write(file_path, vec![7u8; PAGE_SIZE]).unwrap();
+        assert_eq!(
+            Pager::open(file_path).err().map(|e| e.sqlstate()),
+            Some("XX001")
+        );
+
+        let _ = fs::remove_file(file_path);
+    }
+
+    #[test]
+    fn test_checksum_mismatch_is_reported() {
+        let data_dir = "/tmp/test_checksum_mismatch";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1, 'a'), (2, 'b')").unwrap();
+        drop(db);
+
+        // Flip one bit in the first row
+        let path = format!("{}/t.db", data_dir);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[PAGE_SIZE + 70] ^= 0x10;
+        fs::write(&path, &bytes).unwrap();
+
+        let mut db = Database::open(data_dir).unwrap();
+        let err = run(&mut db, "SELECT * FROM t").err().unwrap();
+        assert_eq!(err.sqlstate(), "XX001");
+        assert!(err.to_string().contains("checksum"));
+
+        // A torn trailing page is corrupt too
+        bytes[PAGE_SIZE + 70] ^= 0x10;
+        bytes.truncate(PAGE_SIZE + 100);
+        fs::write(&path, &bytes).unwrap();
+        let mut db = Database::open(data_dir).unwrap();
+        assert_eq!(
+            run(&mut db, "SELECT * FROM t").err().map(|e| e.sqlstate()),
+            Some("XX001")
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_schema_fingerprint() {
+        let data_dir = "/tmp/test_schema_fingerprint";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE a (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run(&mut db, "CREATE TABLE b (id INT PRIMARY KEY, flag BOOLEAN)").unwrap();
+        run(&mut db, "INSERT INTO a VALUES (1, 'x')").unwrap();
+        run(&mut db, "INSERT INTO b VALUES (1, true)").unwrap();
+        drop(db);
+
+        // The file of a table with another layout isn't read as garbage rows
+        fs::copy(format!("{}/a.db", data_dir), format!("{}/b.db", data_dir)).unwrap();
+        let mut db = Database::open(data_dir).unwrap();
+        assert_eq!(
+            run(&mut db, "SELECT * FROM b").err().map(|e| e.sqlstate()),
+            Some("XX001")
+        );
+
+        // Renaming a column doesn't change the layout
+        run(&mut db, "ALTER TABLE a RENAME COLUMN name TO label").unwrap();
+        let (_, rows) = query(&mut db, "SELECT label FROM a");
+        assert_eq!(rows, vec![vec![Field::Text("x".to_string())]]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_headerless_files_are_upgraded() {
+        let data_dir = "/tmp/test_headerless_upgrade";
+        let _ = fs::remove_dir_all(data_dir);
+        fs::create_dir_all(data_dir).unwrap();
+
+        // A catalog and table file as written before files had a header page
+        fs::write(
+            format!("{}/catalog.json", data_dir),
+            r#"{"tables": {"users": {"table_name": "users", "columns": [
+                {"name": "id", "data_type": "Integer", "is_primary": true, "is_autoincrement": true},
+                {"name": "name", "data_type": {"Text": 20}, "is_primary": false, "is_autoincrement": false}
+            ]}}, "sequences": {"users": 3}}"#,
+        )
+        .unwrap();
+        let mut page = Page::new();
+        for (slot, id, name) in [(0, 1, "ann"), (1, 2, "gone"), (2, 3, "cat")] {
+            let offset = HEADER_SIZE + slot * 24;
+            page.data[offset..offset + 4].copy_from_slice(&i32::to_le_bytes(id));
+            page.data[offset + 4..offset + 4 + name.len()].copy_from_slice(name.as_bytes());
+        }
+        // Row 2 was deleted, which only cleared its bit
+        page.data[0] = 0b101;
+        let path = format!("{}/users.db", data_dir);
+        fs::write(&path, page.data).unwrap();
+
+        // The file is recognised and the error says how to convert it
+        let mut db = Database::open(data_dir).unwrap();
+        let err = run(&mut db, "SELECT * FROM users").err().unwrap();
+        assert_eq!(err.sqlstate(), "XX001");
+        assert!(err.to_string().contains("cli upgrade"));
+        drop(db);
+
+        assert_eq!(Database::upgrade(data_dir).unwrap(), vec!["users"]);
+        assert!(Database::upgrade(data_dir).unwrap().is_empty());
+        assert_eq!(fs::read(&path).unwrap()[..8], MAGIC);
+
+        let mut db = Database::open(data_dir).unwrap();
+        let (_, rows) = query(&mut db, "SELECT id, name FROM users ORDER BY id");
+        assert_eq!(
+            rows,
+            vec![
+                vec![Field::Integer(1), Field::Text("ann".to_string())],
+                vec![Field::Integer(3), Field::Text("cat".to_string())],
+            ]
+        );
+        run(&mut db, "INSERT INTO users (name) VALUES ('dan')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM users WHERE name = 'dan'");
+        assert_eq!(rows, vec![vec![Field::Integer(4)]]);
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_upgrade_refuses_to_lose_rows() {
+        let data_dir = "/tmp/test_headerless_lost_rows";
+        let _ = fs::remove_dir_all(data_dir);
+        fs::create_dir_all(data_dir).unwrap();
+        fs::write(
+            format!("{}/catalog.json", data_dir),
+            r#"{"tables": {
+                "a": {"table_name": "a", "columns": [
+                    {"name": "id", "data_type": "Integer", "is_primary": false, "is_autoincrement": false}
+                ]},
+                "b": {"table_name": "b", "columns": [
+                    {"name": "id", "data_type": "Integer", "is_primary": false, "is_autoincrement": false}
+                ]}
+            }, "sequences": {}}"#,
+        )
+        .unwrap();
+
+        // 4-byte rows give 1008 slots a page, but only the first 512 have a bit
+        let mut fine = Page::new();
+        fine.data[0] = 1;
+        fine.data[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&5i32.to_le_bytes());
+        let mut lost = fine.clone();
+        let offset = HEADER_SIZE + 600 * 4;
+        lost.data[offset..offset + 4].copy_from_slice(&6i32.to_le_bytes());
+        fs::write(format!("{}/a.db", data_dir), fine.data).unwrap();
+        fs::write(format!("{}/b.db", data_dir), lost.data).unwrap();
+
+        // Nothing is converted, not even the table that could be
+        let err = Database::upgrade(data_dir).err().unwrap();
+        assert_eq!(err.sqlstate(), "XX001");
+        assert!(err.to_string().contains("past slot 512"));
+        assert_eq!(fs::read(format!("{}/a.db", data_dir)).unwrap(), fine.data);
+        assert_eq!(fs::read(format!("{}/b.db", data_dir)).unwrap(), lost.data);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod tests {
+    use crate::fuzzing::{check_differential, check_parse, check_row_codec};
+    use std::fs;
+    use std::path::Path;
+
+    /// Replays every input saved under `fuzz/regressions/<target>`.
+    fn replay(target: &str, check: impl Fn(&[u8])) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fuzz/regressions")
+            .join(target);
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return;
+        };
+        for entry in entries {
+            let path = entry.unwrap().path();
+            println!("replaying {}", path.display());
+            check(&fs::read(&path).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fuzz_regressions() {
+        replay("parse_sql", check_parse);
+        replay("row_codec", check_row_codec);
+        replay("differential", |data| {
+            check_differential(data, "/tmp/test_fuzz_regressions_differential")
+        });
+    }
+
+    #[test]
+    fn test_differential_model() {
+        // (key, op, args...) for every statement kind, including a reopen
+        let ops: &[&[u8]] = &[
+            &[5, 0, 1, 7],  // INSERT 5
+            &[6, 0, 0, 13], // INSERT 6 with NULL v and a truncated s
+            &[5, 6, 4],     // UPDATE id 5 -> 6 conflicts
+            &[5, 6, 5],     // UPDATE id 5 -> 7
+            &[7, 1, 6],     // UPDATE v
+            &[0, 2, 0, 3],  // UPDATE s WHERE v > -3
+            &[0, 7],        // reopen
+            &[7, 5],        // SELECT by id
+            &[0, 4, 3],     // DELETE WHERE v IS NULL OR v < 0
+            &[7, 3],        // DELETE by id
+        ];
+        check_differential(&ops.concat(), "/tmp/test_differential_model");
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::Database;
+    use crate::tests::test_utils::{fresh_db, run};
+    use std::fs;
+
+    #[test]
+    fn test_hostile_sql_returns_errors() {
+        let data_dir = "/tmp/test_hostile_sql";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)").unwrap();
+
+        let hostile = [
+            "",
+            ";;;",
+            "SELECT",
+            "SELECT * FROM",
+            "DROP TABLE",
+            "DROP VIEW v",
+            "CREATE TABLE big (a VARCHAR(18446744073709551615))",
+            "CREATE TABLE zero (a VARCHAR(0))",
+            "CREATE TABLE wide (a VARCHAR(5000))",
+            "CREATE TABLE wide2 (a VARCHAR(3000), b VARCHAR(3000))",
+            "CREATE TABLE m (a VARCHAR(MAX))",
+            "INSERT INTO t VALUES (99999999999, 'x')",
+            "INSERT INTO t VALUES (1)",
+            "INSERT INTO t (id, name, name) VALUES (1, 'a', 'b')",
+            "INSERT INTO missing VALUES (1)",
+            "UPDATE t SET nope = 1",
+            "UPDATE t SET a.b.c = 1",
+            "SELECT a.b.c.d FROM t",
+            "SELECT UPPER() FROM t",
+            "SELECT ABS(1, 2) FROM t",
+            "SELECT id / 0 FROM t",
+            "SELECT -(-2147483647 - 1) FROM t",
+            "SELECT * FROM t WHERE id = 'text'",
+            "DELETE FROM t WHERE name + 1",
+            "SELECT * FROM t WHERE ((((((((((((((((((((((((((((((id = 1))))))))))))))))))))))))))))))",
+            "SELECT '\u{0}\u{ffff}' FROM t",
+        ];
+        for sql in hostile {
+            // Any outcome but a panic is fine
+            let _ = run(&mut db, sql);
+        }
+
+        // The database is still usable afterwards
+        run(&mut db, "INSERT INTO t VALUES (1, 'ok')").unwrap();
+        assert!(run(&mut db, "SELECT * FROM t").is_ok());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_invalid_column_sizes_are_rejected() {
+        let data_dir = "/tmp/test_invalid_column_sizes";
+        let mut db = fresh_db(data_dir);
+
+        for sql in [
+            "CREATE TABLE a (x VARCHAR(0))",
+            "CREATE TABLE b (x VARCHAR(5000))",
+            "CREATE TABLE c (x VARCHAR(3000), y VARCHAR(3000))",
+        ] {
+            assert_eq!(run(&mut db, sql).err().map(|e| e.sqlstate()), Some("42P16"));
+        }
+
+        run(&mut db, "CREATE TABLE d (x VARCHAR(3000))").unwrap();
+        let err = run(&mut db, "ALTER TABLE d ADD COLUMN y VARCHAR(3000)").err();
+        assert_eq!(err.map(|e| e.sqlstate()), Some("42P16"));
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_corrupt_files() {
+        let data_dir = "/tmp/test_corrupt_files";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1, 'a')").unwrap();
+
+        // Every slot marked full, every byte garbage, and a torn trailing page
+        let mut garbage: Vec<u8> = (0..3 * 4096 + 100).map(|i| (i * 31 % 251) as u8).collect();
+        garbage[..64].fill(0xff);
+        fs::write(format!("{}/t.db", data_dir), &garbage).unwrap();
+        let _ = run(&mut db, "SELECT * FROM t");
+        let _ = run(&mut db, "SELECT * FROM t WHERE id = 1");
+        let _ = run(&mut db, "INSERT INTO t VALUES (2, 'b')");
+        let _ = run(&mut db, "DELETE FROM t WHERE id > 0");
+
+        // A catalog that can't be read is an error, not a panic or an empty database
+        let master = format!("{}/scardb_master.db", data_dir);
+        let mut bytes = fs::read(&master).unwrap();
+        bytes[4096..].fill(0xff);
+        fs::write(&master, bytes).unwrap();
+        assert_eq!(
+            Database::open(data_dir).err().map(|e| e.sqlstate()),
+            Some("XX001")
+        );
+
+        // A data directory that can't be created is an error, not a panic
+        let blocker = "/tmp/test_corrupt_files_blocker";
+        fs::write(blocker, b"").unwrap();
+        assert!(Database::open(&format!("{}/data", blocker)).is_err());
+
+        let _ = fs::remove_file(blocker);
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
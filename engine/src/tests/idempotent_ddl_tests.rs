@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+    use crate::sql::QueryResult;
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    fn message(result: QueryResult) -> String {
+        match result {
+            QueryResult::DdlOk { message, .. } => message,
+            _ => panic!("Expected DdlOk"),
+        }
+    }
+
+    #[test]
+    fn test_create_if_not_exists() {
+        let data_dir = "/tmp/test_create_if_not_exists";
+        let mut db = fresh_db(data_dir);
+        let migration = "CREATE TABLE IF NOT EXISTS t (id INT PRIMARY KEY, name TEXT)";
+        assert_eq!(
+            message(run(&mut db, migration).unwrap()),
+            "Table t created."
+        );
+        run(&mut db, "INSERT INTO t VALUES (1, 'kept')").unwrap();
+
+        // Running the migration again leaves the table and its rows alone
+        assert_eq!(
+            message(run(&mut db, migration).unwrap()),
+            "Table t already exists, skipped."
+        );
+        assert_eq!(
+            message(run(&mut db, "CREATE TABLE IF NOT EXISTS t (other INT)").unwrap()),
+            "Table t already exists, skipped."
+        );
+        let (_, rows) = query(&mut db, "SELECT * FROM t");
+        assert_eq!(
+            rows,
+            vec![vec![Field::Integer(1), Field::Text("kept".to_string())]]
+        );
+
+        // Without IF NOT EXISTS it is still an error
+        assert_eq!(
+            run(&mut db, "CREATE TABLE t (id INT)")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("42P07")
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_drop_if_exists_and_several_tables() {
+        let data_dir = "/tmp/test_drop_if_exists";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE parent (id INT PRIMARY KEY)").unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE child (id INT PRIMARY KEY, parent_id INT REFERENCES parent(id))",
+        )
+        .unwrap();
+        run(&mut db, "CREATE TABLE other (id INT)").unwrap();
+
+        // A missing table fails the whole statement, and nothing is dropped
+        assert_eq!(
+            run(&mut db, "DROP TABLE other, missing")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("42P01")
+        );
+        assert_eq!(query(&mut db, "SHOW TABLES").1.len(), 3);
+
+        // Parent alone is still referenced, but it may go together with its child
+        assert_eq!(
+            run(&mut db, "DROP TABLE parent")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("2BP01")
+        );
+        assert_eq!(
+            message(run(&mut db, "DROP TABLE parent, child").unwrap()),
+            "Tables parent, child dropped."
+        );
+
+        assert_eq!(
+            message(run(&mut db, "DROP TABLE IF EXISTS other, missing").unwrap()),
+            "Table other dropped. Table missing does not exist, skipped."
+        );
+        assert_eq!(
+            message(run(&mut db, "DROP TABLE IF EXISTS other").unwrap()),
+            "Table other does not exist, skipped."
+        );
+        assert!(query(&mut db, "SHOW TABLES").1.is_empty());
+        assert!(!fs::exists(format!("{}/other.db", data_dir)).unwrap());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -0,0 +1,162 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::Database;
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    fn text(value: &str) -> Field {
+        Field::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_query_columns() {
+        let data_dir = "/tmp/test_information_schema_columns";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY AUTOINCREMENT, email VARCHAR(40) UNIQUE NOT NULL, active BOOLEAN DEFAULT true)",
+        )
+        .unwrap();
+        run(&mut db, "CREATE TABLE notes (body TEXT)").unwrap();
+
+        let (columns, rows) = query(
+            &mut db,
+            "SELECT column_name, data_type, is_nullable, column_default, is_unique \
+             FROM information_schema.columns WHERE table_name = 'users'",
+        );
+        assert_eq!(
+            columns,
+            vec![
+                "column_name",
+                "data_type",
+                "is_nullable",
+                "column_default",
+                "is_unique"
+            ]
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    text("id"),
+                    text("INT"),
+                    text("NO"),
+                    Field::Null,
+                    Field::Boolean(false)
+                ],
+                vec![
+                    text("email"),
+                    text("VARCHAR(40)"),
+                    text("NO"),
+                    Field::Null,
+                    Field::Boolean(true)
+                ],
+                vec![
+                    text("active"),
+                    text("BOOLEAN"),
+                    text("YES"),
+                    text("true"),
+                    Field::Boolean(false)
+                ],
+            ]
+        );
+
+        let (_, rows) = query(
+            &mut db,
+            "SELECT * FROM information_schema.columns WHERE ordinal_position = 1 AND is_primary_key = true",
+        );
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][..3], [text("users"), text("id"), Field::Integer(1)]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_views_follow_the_catalog() {
+        let data_dir = "/tmp/test_information_schema_tables";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE b (id INT PRIMARY KEY AUTOINCREMENT, x INT)",
+        )
+        .unwrap();
+        run(&mut db, "CREATE TABLE a (id INT)").unwrap();
+        run(&mut db, "INSERT INTO b (x) VALUES (1), (2)").unwrap();
+
+        let (_, rows) = query(&mut db, "SELECT * FROM information_schema.tables");
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    text("a"),
+                    text("BASE TABLE"),
+                    Field::Integer(1),
+                    Field::Null
+                ],
+                vec![
+                    text("b"),
+                    text("BASE TABLE"),
+                    Field::Integer(2),
+                    Field::Integer(2)
+                ],
+            ]
+        );
+        // The rows are built for the query, not stored
+        assert!(fs::read_dir(data_dir).unwrap().all(|entry| {
+            !entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with("information_schema")
+        }));
+
+        // DDL shows up in the next query, in this session and after reopening
+        run(&mut db, "DROP TABLE a").unwrap();
+        run(&mut db, "ALTER TABLE b RENAME TO c").unwrap();
+        run(&mut db, "ALTER TABLE c ADD COLUMN y INT DEFAULT 0").unwrap();
+        let (_, rows) = query(
+            &mut db,
+            "SELECT table_name, column_count FROM information_schema.tables",
+        );
+        assert_eq!(rows, vec![vec![text("c"), Field::Integer(3)]]);
+        drop(db);
+
+        let mut db = Database::open(data_dir).unwrap();
+        let (_, rows) = query(
+            &mut db,
+            "SELECT column_name FROM information_schema.columns WHERE table_name = 'c'",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![text("id")], vec![text("x")], vec![text("y")]]
+        );
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_views_are_read_only() {
+        let data_dir = "/tmp/test_information_schema_read_only";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT)").unwrap();
+
+        for sql in [
+            "INSERT INTO information_schema.tables VALUES ('x', 'BASE TABLE', 1, NULL)",
+            "UPDATE information_schema.columns SET column_name = 'y'",
+            "DELETE FROM information_schema.tables WHERE table_name = 't'",
+            "DROP TABLE information_schema.columns",
+            "CREATE TABLE information_schema.tables (id INT)",
+            "ALTER TABLE t RENAME TO information_schema.columns",
+            "VACUUM information_schema.tables",
+        ] {
+            let err = run(&mut db, sql).err();
+            assert_eq!(err.map(|e| e.sqlstate()), Some("42809"), "{}", sql);
+        }
+        let (_, rows) = query(&mut db, "SELECT table_name FROM information_schema.tables");
+        assert_eq!(rows, vec![vec![text("t")]]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::Database;
+    use crate::storage::pager::{HEADER_SIZE, PAGE_SIZE, Page, Pager};
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_sound_database_is_ok() {
+        let data_dir = "/tmp/test_integrity_ok";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY, name TEXT UNIQUE)",
+        )
+        .unwrap();
+        run(&mut db, "CREATE TABLE empty (id INT)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1, 'a'), (2, 'b')").unwrap();
+
+        assert!(db.integrity_check().unwrap().is_empty());
+        let (columns, rows) = query(&mut db, "PRAGMA integrity_check");
+        assert_eq!(columns, vec!["integrity_check"]);
+        assert_eq!(rows, vec![vec![Field::Text("ok".to_string())]]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_problems_are_reported() {
+        let data_dir = "/tmp/test_integrity_problems";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run(&mut db, "CREATE TABLE gone (id INT)").unwrap();
+        run(&mut db, "CREATE TABLE broken (id INT)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1, 'a'), (2, 'b')").unwrap();
+        run(&mut db, "INSERT INTO broken VALUES (1)").unwrap();
+        drop(db);
+
+        // Edit the rows of t behind the engine's back, keeping the checksums valid
+        let path = format!("{}/t.db", data_dir);
+        let mut pager = Pager::open(&path).unwrap();
+        let mut page = pager.read_page(0).unwrap().to_page();
+        // id, then the NULL flag and the bytes of name
+        let row_size = 4 + 1 + 255;
+        let max_slots = Page::max_slots(row_size);
+        // The second row gets the first one's key and a name that isn't UTF-8
+        page.data[HEADER_SIZE + row_size..HEADER_SIZE + row_size + 4]
+            .copy_from_slice(&1i32.to_le_bytes());
+        page.data[HEADER_SIZE + row_size + 5] = 0xff;
+        page.set_slot(max_slots + 1, true);
+        pager.write_page(0, &page).unwrap();
+        pager.flush().unwrap();
+        drop(pager);
+
+        // A flipped bit, a lost file and a stray one
+        let broken = format!("{}/broken.db", data_dir);
+        let mut bytes = fs::read(&broken).unwrap();
+        bytes[PAGE_SIZE + 100] ^= 1;
+        fs::write(&broken, bytes).unwrap();
+        fs::remove_file(format!("{}/gone.db", data_dir)).unwrap();
+        fs::write(format!("{}/stray.db", data_dir), b"").unwrap();
+
+        let mut db = Database::open(data_dir).unwrap();
+        let problems = db.integrity_check().unwrap();
+        let expected = [
+            "Table broken: ",
+            "checksum mismatch on page 0",
+            "Table gone: file /tmp/test_integrity_problems/gone.db is missing",
+            "Table t: page 0 marks 1 slots past the last of its",
+            "Table t: row at page 0, slot 1 has invalid UTF-8 in column name",
+            "Table t: duplicate primary key (1) at page 0, slot 0 and page 0, slot 1",
+            "File stray.db doesn't belong to any table in the catalog",
+        ];
+        for text in expected {
+            assert!(
+                problems.iter().any(|p| p.contains(text)),
+                "{:?} not in {:?}",
+                text,
+                problems
+            );
+        }
+        assert_eq!(problems.len(), 6);
+
+        let (_, rows) = query(&mut db, "PRAGMA integrity_check");
+        assert_eq!(rows.len(), 6);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_check_leaves_files_alone() {
+        let data_dir = "/tmp/test_integrity_read_only";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE empty (id INT)").unwrap();
+        run(&mut db, "CREATE TABLE torn (id INT)").unwrap();
+        drop(db);
+
+        // An empty file and one cut off inside its header page
+        let empty = format!("{}/empty.db", data_dir);
+        let torn = format!("{}/torn.db", data_dir);
+        fs::write(&empty, b"").unwrap();
+        let mut bytes = fs::read(&torn).unwrap();
+        bytes.truncate(100);
+        fs::write(&torn, &bytes).unwrap();
+
+        let mut db = Database::open(data_dir).unwrap();
+        let problems = db.integrity_check().unwrap();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("has no header page"));
+        assert!(problems[1].contains("truncated"));
+        drop(db);
+
+        // Neither file was given a new header
+        assert_eq!(fs::read(&empty).unwrap(), b"");
+        assert_eq!(fs::read(&torn).unwrap(), bytes);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
@@ -1,3 +1,26 @@
+mod alter_table_tests;
+mod buffer_pool_tests;
+mod catalog_tests;
+mod composite_key_tests;
+mod constraint_tests;
+mod durability_tests;
+mod error_tests;
+mod expression_tests;
+mod file_format_tests;
+mod fuzz_regression_tests;
+mod hostile_input_tests;
+mod idempotent_ddl_tests;
+mod information_schema_tests;
+mod integrity_tests;
 mod pager_tests;
+mod result_tests;
 mod row_serialization_tests;
+mod show_tests;
+mod single_file_tests;
+mod sqllogic_tests;
+mod table_cache_tests;
 mod table_operations_tests;
+mod test_utils;
+mod truncate_tests;
+mod upsert_tests;
+mod vacuum_tests;
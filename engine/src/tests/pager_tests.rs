@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod tests {
+    use crate::storage::buffer_pool::BufferPool;
     use crate::storage::pager::{HEADER_SIZE, PAGE_SIZE, Page, Pager};
     use std::fs;
 
@@ -60,4 +61,72 @@ mod tests {
 
         let _ = fs::remove_file(file_path);
     }
+
+    #[test]
+    fn test_free_space_map() {
+        let file_path = "/tmp/test_pager_free_space.db";
+        let _ = fs::remove_file(file_path);
+        let max_slots = 4;
+
+        // Pages 0 and 2 are full, page 1 has room in slot 3
+        let mut pager = Pager::open(file_path).expect("Failed to open pager");
+        let mut full = Page::new();
+        (0..max_slots).for_each(|s| full.set_slot(s, true));
+        let mut partial = full.clone();
+        partial.set_slot(3, false);
+        for (i, page) in [&full, &partial, &full].into_iter().enumerate() {
+            pager.write_page(i, page).expect("Failed to write page");
+        }
+        pager.flush().expect("Failed to flush");
+        drop(pager);
+
+        // The first lookup builds the map from the bitmaps and stores it in the header
+        let mut pager = Pager::open(file_path).expect("Failed to open pager");
+        assert!(pager.free_space().unwrap().is_none());
+        let (p_idx, s_idx, page) = pager.find_free_slot(max_slots).unwrap();
+        assert_eq!((p_idx, s_idx), (1, 3));
+        let free_space = pager.free_space().unwrap().expect("map is built");
+        assert_eq!(free_space.len(), 1);
+
+        // Filling the slot takes the page out of the map, so the next row starts page 3
+        let mut page = page;
+        page.set_slot(3, true);
+        pager.write_page(1, &page).expect("Failed to write page");
+        assert!(
+            pager
+                .free_space()
+                .unwrap()
+                .is_some_and(|map| map.is_empty())
+        );
+        let (p_idx, s_idx, _) = pager.find_free_slot(max_slots).unwrap();
+        assert_eq!((p_idx, s_idx), (3, 0));
+        pager.flush().expect("Failed to flush");
+        drop(pager);
+
+        // The map was saved with the file
+        let pool = BufferPool::shared(16);
+        let mut pager = Pager::with_pool(file_path, pool.clone()).expect("Failed to open pager");
+        assert!(
+            pager
+                .free_space()
+                .unwrap()
+                .is_some_and(|map| map.is_empty())
+        );
+
+        // A slot freed through another pager of the file puts the page back
+        let mut other = Pager::with_pool(file_path, pool).expect("Failed to open pager");
+        page.set_slot(0, false);
+        other.write_page(1, &page).expect("Failed to write page");
+        assert!(
+            pager
+                .free_space()
+                .unwrap()
+                .is_some_and(|map| map.contains(1))
+        );
+        let (p_idx, s_idx, _) = pager.find_free_slot(max_slots).unwrap();
+        assert_eq!((p_idx, s_idx), (1, 0));
+        drop((pager, other));
+
+        let _ = fs::remove_file(file_path);
+    }
 }
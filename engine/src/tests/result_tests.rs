@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use crate::sql::QueryResult;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_structured_results() {
+        let data_dir = "/tmp/test_structured_results";
+        let mut db = fresh_db(data_dir);
+
+        match run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY AUTOINCREMENT, name TEXT)",
+        ) {
+            Ok(QueryResult::DdlOk { object, .. }) => assert_eq!(object, "users"),
+            _ => panic!("Expected DdlOk"),
+        }
+        match run(&mut db, "INSERT INTO users (name) VALUES ('a'), ('b')") {
+            Ok(QueryResult::RowsAffected {
+                count,
+                last_insert_id,
+            }) => assert_eq!((count, last_insert_id), (2, Some(2))),
+            _ => panic!("Expected RowsAffected"),
+        }
+        match run(&mut db, "UPDATE users SET name = 'c' WHERE id > 5") {
+            Ok(QueryResult::RowsAffected {
+                count,
+                last_insert_id,
+            }) => assert_eq!((count, last_insert_id), (0, None)),
+            _ => panic!("Expected RowsAffected"),
+        }
+        match run(&mut db, "DELETE FROM users WHERE id = 1") {
+            Ok(QueryResult::RowsAffected { count, .. }) => assert_eq!(count, 1),
+            _ => panic!("Expected RowsAffected"),
+        }
+
+        // Zero rows is still a result set, with its column names
+        let (columns, rows) = query(&mut db, "SELECT * FROM users WHERE id = 1");
+        assert_eq!(columns, vec!["id", "name"]);
+        assert!(rows.is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_join_column_names() {
+        let data_dir = "/tmp/test_join_column_names";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, name TEXT)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE orders (id INT PRIMARY KEY, user_id INT, total INT)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO users VALUES (1, 'ann')").unwrap();
+        run(&mut db, "INSERT INTO orders VALUES (10, 1, 50)").unwrap();
+
+        // Only a name both tables have is qualified with its table
+        let (columns, rows) = query(
+            &mut db,
+            "SELECT * FROM users JOIN orders ON users.id = orders.user_id WHERE total > 20",
+        );
+        assert_eq!(
+            columns,
+            vec!["users.id", "name", "orders.id", "user_id", "total"]
+        );
+        assert_eq!(rows.len(), 1);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
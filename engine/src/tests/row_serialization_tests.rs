@@ -15,20 +15,29 @@ mod tests {
                     data_type: DataType::Integer,
                     is_primary: true,
                     is_autoincrement: true,
+                    is_nullable: false,
+                    default: None,
                 },
                 Column {
                     name: "active".to_string(),
                     data_type: DataType::Boolean,
                     is_primary: false,
                     is_autoincrement: false,
+                    is_nullable: false,
+                    default: None,
                 },
                 Column {
                     name: "name".to_string(),
                     data_type: DataType::Text(20),
                     is_primary: false,
                     is_autoincrement: false,
+                    is_nullable: false,
+                    default: None,
                 },
             ],
+            unique: Vec::new(),
+            checks: Vec::new(),
+            foreign_keys: Vec::new(),
         };
 
         let row = Row {
@@ -40,7 +49,7 @@ mod tests {
         };
 
         let bytes = row.serialize(&schema);
-        let deserialized = Row::deserialize(&bytes, &schema);
+        let deserialized = Row::deserialize(&bytes, &schema).unwrap();
 
         assert_eq!(row, deserialized);
     }
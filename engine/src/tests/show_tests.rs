@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    fn text(value: &str) -> Field {
+        Field::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_show_tables_and_describe() {
+        let data_dir = "/tmp/test_show_tables";
+        let mut db = fresh_db(data_dir);
+        let (columns, rows) = query(&mut db, "SHOW TABLES");
+        assert_eq!(columns, vec!["table_name"]);
+        assert!(rows.is_empty());
+
+        run(&mut db, "CREATE TABLE users (id INT PRIMARY KEY AUTOINCREMENT, name VARCHAR(20) UNIQUE, active BOOLEAN NOT NULL DEFAULT false)").unwrap();
+        run(&mut db, "CREATE TABLE orders (id INT PRIMARY KEY)").unwrap();
+        let (_, rows) = query(&mut db, "SHOW TABLES");
+        assert_eq!(rows, vec![vec![text("orders")], vec![text("users")]]);
+
+        let (columns, rows) = query(&mut db, "DESCRIBE users");
+        assert_eq!(
+            columns,
+            vec![
+                "column_name",
+                "data_type",
+                "is_nullable",
+                "column_default",
+                "is_primary_key",
+                "is_unique",
+                "is_autoincrement"
+            ]
+        );
+        assert_eq!(
+            rows[0],
+            vec![
+                text("id"),
+                text("INT"),
+                text("NO"),
+                Field::Null,
+                Field::Boolean(true),
+                Field::Boolean(false),
+                Field::Boolean(true)
+            ]
+        );
+        assert_eq!(
+            rows[2][..4],
+            [text("active"), text("BOOLEAN"), text("NO"), text("false")]
+        );
+        assert_eq!(query(&mut db, "SHOW COLUMNS FROM users").1, rows);
+
+        assert_eq!(
+            run(&mut db, "DESCRIBE missing").err().map(|e| e.sqlstate()),
+            Some("42P01")
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_show_create_table_round_trips() {
+        let data_dir = "/tmp/test_show_create_table";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY AUTOINCREMENT, name VARCHAR(20))",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE lines (
+                order_id INT,
+                line INT,
+                code VARCHAR(12) UNIQUE NOT NULL,
+                note TEXT DEFAULT 'it''s',
+                qty INT DEFAULT -1 CHECK (qty <> 0 AND qty < 100),
+                user_id INT REFERENCES users(id) ON DELETE SET NULL ON UPDATE CASCADE,
+                PRIMARY KEY (order_id, line),
+                UNIQUE (user_id, line)
+            )",
+        )
+        .unwrap();
+
+        let (columns, rows) = query(&mut db, "SHOW CREATE TABLE lines");
+        assert_eq!(columns, vec!["table_name", "create_statement"]);
+        let Field::Text(ddl) = &rows[0][1] else {
+            panic!("Expected the statement as text");
+        };
+        assert!(ddl.starts_with("CREATE TABLE lines (\n    order_id INT,\n"));
+        assert!(ddl.contains("PRIMARY KEY (order_id, line)"));
+
+        // Running the statement again gives the same schema
+        let (_, rows) = query(&mut db, "SHOW CREATE TABLE users");
+        let Field::Text(users_ddl) = &rows[0][1] else {
+            panic!("Expected the statement as text");
+        };
+        let original = serde_json::to_value(&db.catalog.tables["lines"]).unwrap();
+        let users = serde_json::to_value(&db.catalog.tables["users"]).unwrap();
+        let copy_dir = "/tmp/test_show_create_table_copy";
+        let mut copy = fresh_db(copy_dir);
+        run(&mut copy, users_ddl).unwrap();
+        run(&mut copy, ddl).unwrap();
+        assert_eq!(
+            serde_json::to_value(&copy.catalog.tables["lines"]).unwrap(),
+            original
+        );
+        assert_eq!(
+            serde_json::to_value(&copy.catalog.tables["users"]).unwrap(),
+            users
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+        let _ = fs::remove_dir_all(copy_dir);
+    }
+}
@@ -0,0 +1,265 @@
+#[cfg(test)]
+mod tests {
+    use crate::catalog::CatalogData;
+    use crate::engine::Database;
+    use crate::storage::buffer_pool::{self, BufferPool};
+    use crate::storage::pager::{HEADER_SIZE, PAGE_SIZE, Page};
+    use crate::storage::record::Field;
+    use crate::storage::single_file::{self, SINGLE_FILE_MAGIC, SingleFile};
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn fresh_single_file(path: &str) -> Database {
+        let _ = fs::remove_file(path);
+        Database::open_single_file(path).expect("Failed to open database")
+    }
+
+    #[test]
+    fn test_tables_live_in_one_file() {
+        let path = "/tmp/test_single_file_basic.sdb";
+        let mut db = fresh_single_file(path);
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, name TEXT)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE tags (id INT PRIMARY KEY AUTOINCREMENT, label TEXT)",
+        )
+        .unwrap();
+        for i in 1..=100 {
+            run(
+                &mut db,
+                &format!("INSERT INTO users VALUES ({}, 'user {}')", i, i),
+            )
+            .unwrap();
+        }
+        run(&mut db, "INSERT INTO tags (label) VALUES ('a'), ('b')").unwrap();
+        run(&mut db, "DELETE FROM users WHERE id > 50").unwrap();
+        drop(db);
+
+        let bytes = fs::read(path).unwrap();
+        assert_eq!(bytes[..8], SINGLE_FILE_MAGIC);
+        assert_eq!(bytes.len() % PAGE_SIZE, 0);
+
+        // `open` recognizes the file, and the catalog and sequences came back with it
+        let mut db = Database::open(path).unwrap();
+        let (_, rows) = query(&mut db, "SELECT name FROM users WHERE id = 50");
+        assert_eq!(rows, vec![vec![Field::Text("user 50".to_string())]]);
+        let (_, rows) = query(&mut db, "SELECT id FROM users");
+        assert_eq!(rows.len(), 50);
+        run(&mut db, "INSERT INTO tags (label) VALUES ('c')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM tags WHERE label = 'c'");
+        assert_eq!(rows, vec![vec![Field::Integer(3)]]);
+        let (_, rows) = query(&mut db, "SELECT table_name FROM information_schema.tables");
+        assert_eq!(rows.len(), 2);
+        let (_, rows) = query(&mut db, "SELECT table_name FROM scardb_master");
+        assert_eq!(rows.len(), 2);
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        // A file from before scardb_master keeps the catalog in its directory,
+        // and it moves into the table when the file is opened
+        let tables = db.catalog.tables.clone();
+        drop(db);
+        {
+            let file = SingleFile::open(path, BufferPool::shared(16)).unwrap();
+            let mut single = single_file::lock(&file);
+            single.remove_segment("scardb_master");
+            single.set_catalog(CatalogData {
+                tables,
+                sequences: HashMap::from([("tags".to_string(), 3)]),
+            });
+            single.commit(true).unwrap();
+        }
+        let mut db = Database::open(path).unwrap();
+        run(&mut db, "INSERT INTO tags (label) VALUES ('d')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM tags WHERE label = 'd'");
+        assert_eq!(rows, vec![vec![Field::Integer(4)]]);
+        assert!(db.integrity_check().unwrap().is_empty());
+        let file = db.single_file().unwrap();
+        assert!(single_file::lock(file).catalog().tables.is_empty());
+        drop(db);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_freed_pages_are_reused() {
+        let path = "/tmp/test_single_file_reuse.sdb";
+        let mut db = fresh_single_file(path);
+        run(&mut db, "CREATE TABLE keep (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE scratch (id INT PRIMARY KEY, name TEXT)",
+        )
+        .unwrap();
+        for i in 1..=60 {
+            run(&mut db, &format!("INSERT INTO scratch VALUES ({}, 'x')", i)).unwrap();
+        }
+        run(&mut db, "INSERT INTO keep VALUES (1, 'last')").unwrap();
+        let grown = fs::metadata(path).unwrap().len();
+
+        // The pages of the dropped table hold the next one's rows, so the file doesn't grow
+        run(&mut db, "DROP TABLE scratch").unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE other (id INT PRIMARY KEY, name TEXT)",
+        )
+        .unwrap();
+        for i in 1..=60 {
+            run(&mut db, &format!("INSERT INTO other VALUES ({}, 'y')", i)).unwrap();
+        }
+        assert!(fs::metadata(path).unwrap().len() <= grown);
+
+        // ALTER TABLE and VACUUM rewrite into free pages too
+        run(
+            &mut db,
+            "ALTER TABLE other ADD COLUMN flag BOOLEAN DEFAULT true",
+        )
+        .unwrap();
+        run(&mut db, "ALTER TABLE other RENAME TO renamed").unwrap();
+        run(&mut db, "VACUUM").unwrap();
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        // Dropping the table at the end of the file gives its pages back
+        run(&mut db, "DROP TABLE renamed").unwrap();
+        assert!(fs::metadata(path).unwrap().len() < grown);
+        drop(db);
+
+        let mut db = Database::open(path).unwrap();
+        let (_, rows) = query(&mut db, "SELECT name FROM keep");
+        assert_eq!(rows, vec![vec![Field::Text("last".to_string())]]);
+        assert!(run(&mut db, "SELECT * FROM renamed").is_err());
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_convert_directory() {
+        let data_dir = "/tmp/test_single_file_convert";
+        let path = "/tmp/test_single_file_convert.sdb";
+        let _ = fs::remove_file(path);
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE posts (id INT PRIMARY KEY, user_id INT REFERENCES users(id))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO users (name) VALUES ('ann'), ('bob')").unwrap();
+        run(&mut db, "INSERT INTO posts VALUES (1, 2)").unwrap();
+        drop(db);
+
+        Database::convert_to_single_file(data_dir, path).unwrap();
+        // An existing file is never overwritten
+        assert!(Database::convert_to_single_file(data_dir, path).is_err());
+
+        let mut db = Database::open(path).unwrap();
+        assert!(db.single_file().is_some());
+        let (_, rows) = query(
+            &mut db,
+            "SELECT users.name FROM posts JOIN users ON posts.user_id = users.id",
+        );
+        assert_eq!(rows, vec![vec![Field::Text("bob".to_string())]]);
+        run(&mut db, "INSERT INTO users (name) VALUES ('cy')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM users WHERE name = 'cy'");
+        assert_eq!(rows, vec![vec![Field::Integer(3)]]);
+        assert!(run(&mut db, "INSERT INTO users (name) VALUES ('ann')").is_err());
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_convert_only_reads_the_directory() {
+        let data_dir = "/tmp/test_single_file_convert_source";
+        let path = "/tmp/test_single_file_convert_source.sdb";
+        let _ = fs::remove_dir_all(data_dir);
+        let _ = fs::remove_file(path);
+        fs::create_dir_all(data_dir).unwrap();
+
+        // `old` has a file from before the file header, `empty` has no file at all
+        fs::write(
+            format!("{}/catalog.json", data_dir),
+            r#"{"tables": {
+                "old": {"table_name": "old", "columns": [
+                    {"name": "id", "data_type": "Integer", "is_primary": true, "is_autoincrement": false}
+                ]},
+                "empty": {"table_name": "empty", "columns": [
+                    {"name": "id", "data_type": "Integer", "is_primary": true, "is_autoincrement": false}
+                ]}
+            }, "sequences": {}}"#,
+        )
+        .unwrap();
+        let mut page = Page::new();
+        page.data[0] = 0b11;
+        page.data[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&7i32.to_le_bytes());
+        page.data[HEADER_SIZE + 4..HEADER_SIZE + 8].copy_from_slice(&8i32.to_le_bytes());
+        fs::write(format!("{}/old.db", data_dir), page.data).unwrap();
+        let snapshot = || {
+            let mut files: Vec<(String, Vec<u8>)> = fs::read_dir(data_dir)
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.unwrap().path();
+                    (path.display().to_string(), fs::read(&path).unwrap())
+                })
+                .collect();
+            files.sort();
+            files
+        };
+        let before = snapshot();
+
+        Database::convert_to_single_file(data_dir, path).unwrap();
+        assert!(before == snapshot());
+
+        let mut db = Database::open(path).unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM old ORDER BY id");
+        assert_eq!(rows, vec![vec![Field::Integer(7)], vec![Field::Integer(8)]]);
+        assert!(query(&mut db, "SELECT * FROM empty").1.is_empty());
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_uncommitted_directory_is_not_seen() {
+        let path = "/tmp/test_single_file_uncommitted.sdb";
+        let mut db = fresh_single_file(path);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1)").unwrap();
+        let committed = fs::read(path).unwrap();
+
+        // A crash after the table pages were written but before the header was: the
+        // file still opens, with the last directory the header pointed at
+        run(&mut db, "PRAGMA synchronous = OFF").unwrap();
+        run(&mut db, "CREATE TABLE later (id INT)").unwrap();
+        let file = db.single_file().unwrap().clone();
+        {
+            let single = single_file::lock(&file);
+            buffer_pool::lock(&single.pool())
+                .flush_file(single.path(), false)
+                .unwrap();
+        }
+        let mut bytes = fs::read(path).unwrap();
+        bytes[..PAGE_SIZE].copy_from_slice(&committed[..PAGE_SIZE]);
+        let copy = "/tmp/test_single_file_uncommitted_copy.sdb";
+        fs::write(copy, bytes).unwrap();
+
+        let mut db = Database::open(copy).unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t");
+        assert_eq!(rows, vec![vec![Field::Integer(1)]]);
+        assert!(run(&mut db, "SELECT * FROM later").is_err());
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(copy);
+    }
+}
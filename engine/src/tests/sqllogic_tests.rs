@@ -0,0 +1,271 @@
+#[cfg(test)]
+mod tests {
+    //! Runs the `.test` files in `engine/sqllogictest` in the sqllogictest format:
+    //!
+    //! ```text
+    //! statement ok
+    //! CREATE TABLE t (id INT PRIMARY KEY, name TEXT)
+    //!
+    //! statement count 2
+    //! INSERT INTO t VALUES (1, 'a'), (2, 'b')
+    //!
+    //! statement error 23505
+    //! INSERT INTO t VALUES (1, 'c')
+    //!
+    //! query IT rowsort
+    //! SELECT * FROM t
+    //! ----
+    //! 1 a
+    //! 2 b
+    //! ```
+    //!
+    //! Each result row is one line of space-separated values. `rowsort` sorts the
+    //! rows and `valuesort` every value before comparing; `nosort` (the default)
+    //! keeps the engine's order. `error` takes an optional SQLSTATE code or a
+    //! piece of the message. `skipif scardb` / `onlyif <engine>` and `halt` work
+    //! as in the original format; `hash-threshold` is accepted and ignored.
+
+    use crate::engine::Database;
+    use crate::error::Result;
+    use crate::sql::QueryResult;
+    use crate::sql::parser::parse_sql;
+    use crate::storage::record::Field;
+    use std::fs;
+    use std::path::Path;
+
+    const ENGINE_NAME: &str = "scardb";
+
+    enum Expected {
+        Ok,
+        Count(usize),
+        Error(Option<String>),
+        Rows {
+            types: String,
+            sort: Sort,
+            lines: Vec<String>,
+        },
+    }
+
+    #[derive(Clone, Copy)]
+    enum Sort {
+        None,
+        Rows,
+        Values,
+    }
+
+    struct Record {
+        line: usize,
+        sql: String,
+        expected: Expected,
+    }
+
+    /// Splits a test file into records, dropping the ones `skipif` / `onlyif`
+    /// exclude and everything after `halt`.
+    fn parse_file(text: &str) -> std::result::Result<Vec<Record>, String> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut records = Vec::new();
+        let mut i = 0;
+        let mut skip = false;
+
+        while i < lines.len() {
+            let line = lines[i].trim();
+            let line_no = i + 1;
+            i += 1;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let mut expected = match words.as_slice() {
+                ["halt"] => break,
+                ["hash-threshold", ..] => continue,
+                ["skipif", engine] => {
+                    skip |= engine.eq_ignore_ascii_case(ENGINE_NAME);
+                    continue;
+                }
+                ["onlyif", engine] => {
+                    skip |= !engine.eq_ignore_ascii_case(ENGINE_NAME);
+                    continue;
+                }
+                ["statement", "ok"] => Expected::Ok,
+                ["statement", "count", n] => Expected::Count(
+                    n.parse()
+                        .map_err(|_| format!("line {}: bad count {}", line_no, n))?,
+                ),
+                ["statement" | "query", "error", rest @ ..] => {
+                    Expected::Error((!rest.is_empty()).then(|| rest.join(" ")))
+                }
+                ["query", types, rest @ ..] => {
+                    let sort = match rest.first() {
+                        None | Some(&"nosort") => Sort::None,
+                        Some(&"rowsort") => Sort::Rows,
+                        Some(&"valuesort") => Sort::Values,
+                        Some(other) => {
+                            return Err(format!("line {}: unknown sort mode {}", line_no, other));
+                        }
+                    };
+                    Expected::Rows {
+                        types: types.to_string(),
+                        sort,
+                        lines: Vec::new(),
+                    }
+                }
+                _ => return Err(format!("line {}: unknown record {}", line_no, line)),
+            };
+
+            // The SQL runs up to a blank line, or to "----" before query results
+            let mut sql = Vec::new();
+            while i < lines.len() && !lines[i].trim().is_empty() && lines[i].trim() != "----" {
+                sql.push(lines[i]);
+                i += 1;
+            }
+            if i < lines.len() && lines[i].trim() == "----" {
+                i += 1;
+                let Expected::Rows { lines: out, .. } = &mut expected else {
+                    return Err(format!("line {}: results after a statement", line_no));
+                };
+                while i < lines.len() && !lines[i].trim().is_empty() {
+                    out.push(lines[i].trim().to_string());
+                    i += 1;
+                }
+            }
+
+            if !std::mem::take(&mut skip) {
+                records.push(Record {
+                    line: line_no,
+                    sql: sql.join("\n"),
+                    expected,
+                });
+            }
+        }
+        Ok(records)
+    }
+
+    fn execute(db: &mut Database, sql: &str) -> Result<QueryResult> {
+        let mut result = Ok(QueryResult::Empty);
+        for command in parse_sql(sql)? {
+            result = Ok(db.execute(command)?);
+        }
+        result
+    }
+
+    fn format_value(field: &Field) -> String {
+        match field {
+            Field::Integer(i) => i.to_string(),
+            Field::Boolean(b) => b.to_string(),
+            Field::Text(s) if s.is_empty() => "(empty)".to_string(),
+            Field::Text(s) => s.clone(),
+            Field::Null => "NULL".to_string(),
+        }
+    }
+
+    /// Checks one record, returning a description of the mismatch if it fails.
+    fn check(db: &mut Database, record: &Record) -> std::result::Result<(), String> {
+        let result = execute(db, &record.sql);
+        match (&record.expected, result) {
+            (Expected::Error(None), Err(_)) => Ok(()),
+            (Expected::Error(Some(pattern)), Err(e)) => {
+                if e.sqlstate() == pattern || e.to_string().contains(pattern.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "expected error matching {}, got [{}] {}",
+                        pattern,
+                        e.sqlstate(),
+                        e
+                    ))
+                }
+            }
+            (Expected::Error(_), Ok(_)) => Err("expected an error, but it succeeded".to_string()),
+            (_, Err(e)) => Err(format!("unexpected error [{}] {}", e.sqlstate(), e)),
+            (Expected::Ok, Ok(_)) => Ok(()),
+            (Expected::Count(n), Ok(QueryResult::RowsAffected { count, .. })) if count == *n => {
+                Ok(())
+            }
+            (Expected::Count(n), Ok(QueryResult::RowsAffected { count, .. })) => {
+                Err(format!("expected {} rows affected, got {}", n, count))
+            }
+            (Expected::Count(_), Ok(_)) => Err("expected a row count".to_string()),
+            (Expected::Rows { types, sort, lines }, Ok(QueryResult::Data(response))) => {
+                if response.columns.len() != types.len() {
+                    return Err(format!(
+                        "expected {} columns, got {}",
+                        types.len(),
+                        response.columns.len()
+                    ));
+                }
+                let mut actual: Vec<String> = match sort {
+                    Sort::Values => response.rows.iter().flatten().map(format_value).collect(),
+                    _ => response
+                        .rows
+                        .iter()
+                        .map(|row| row.iter().map(format_value).collect::<Vec<_>>().join(" "))
+                        .collect(),
+                };
+                let mut expected = match sort {
+                    Sort::Values => lines
+                        .iter()
+                        .flat_map(|line| line.split_whitespace().map(str::to_string))
+                        .collect(),
+                    _ => lines.clone(),
+                };
+                if !matches!(sort, Sort::None) {
+                    actual.sort();
+                    expected.sort();
+                }
+                if actual == expected {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "expected:\n{}\ngot:\n{}",
+                        expected.join("\n"),
+                        actual.join("\n")
+                    ))
+                }
+            }
+            (Expected::Rows { .. }, Ok(_)) => Err("expected rows".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_sqllogictest_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("sqllogictest");
+        let mut paths: Vec<_> = fs::read_dir(&corpus)
+            .expect("sqllogictest directory")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "test"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        let mut failures = Vec::new();
+        for path in &paths {
+            let name = path.file_stem().unwrap().to_string_lossy();
+            let records = match parse_file(&fs::read_to_string(path).unwrap()) {
+                Ok(records) => records,
+                Err(e) => {
+                    failures.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+
+            let data_dir = format!("/tmp/sqllogictest_{}", name);
+            let _ = fs::remove_dir_all(&data_dir);
+            let mut db = Database::open(&data_dir).unwrap();
+            for record in &records {
+                if let Err(e) = check(&mut db, record) {
+                    failures.push(format!(
+                        "{}:{}: {}\n{}",
+                        path.display(),
+                        record.line,
+                        record.sql,
+                        e
+                    ));
+                }
+            }
+            let _ = fs::remove_dir_all(&data_dir);
+        }
+
+        assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_indexes_stay_warm_between_statements() {
+        let data_dir = "/tmp/test_indexes_stay_warm";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(200))",
+        )
+        .unwrap();
+        for i in 0..100 {
+            run(&mut db, &format!("INSERT INTO t VALUES ({}, 'row')", i)).unwrap();
+        }
+        assert_eq!(db.open_table_count(), 1);
+
+        // A key lookup reads one page instead of reloading the index from every page
+        let before = db.buffer_pool_stats();
+        let (_, rows) = query(&mut db, "SELECT * FROM t WHERE id = 42");
+        let after = db.buffer_pool_stats();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            (after.hits + after.misses) - (before.hits + before.misses),
+            1
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_ddl_invalidates_cached_tables() {
+        let data_dir = "/tmp/test_ddl_invalidates_cached_tables";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1, 'a'), (2, 'b')").unwrap();
+
+        // A table created again under the same name starts with an empty index
+        run(&mut db, "DROP TABLE t").unwrap();
+        assert_eq!(db.open_table_count(), 0);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1, 'c')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM t WHERE id = 1");
+        assert_eq!(
+            rows,
+            vec![vec![Field::Integer(1), Field::Text("c".to_string())]]
+        );
+
+        // The rewritten file has a new layout
+        run(
+            &mut db,
+            "ALTER TABLE t ADD COLUMN active BOOLEAN DEFAULT true",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO t VALUES (2, 'd', false)").unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM t WHERE id = 2");
+        assert_eq!(
+            rows,
+            vec![vec![
+                Field::Integer(2),
+                Field::Text("d".to_string()),
+                Field::Boolean(false)
+            ]]
+        );
+
+        // Renamed tables are found under their new name only
+        run(&mut db, "ALTER TABLE t RENAME TO u").unwrap();
+        assert!(run(&mut db, "INSERT INTO u VALUES (1, 'e', true)").is_err());
+        assert!(run(&mut db, "SELECT * FROM t").is_err());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
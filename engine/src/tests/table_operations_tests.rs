@@ -21,14 +21,21 @@ mod tests {
                     data_type: DataType::Integer,
                     is_primary: true,
                     is_autoincrement: true,
+                    is_nullable: false,
+                    default: None,
                 },
                 Column {
                     name: "name".to_string(),
                     data_type: DataType::Text(32),
                     is_primary: false,
                     is_autoincrement: false,
+                    is_nullable: false,
+                    default: None,
                 },
             ],
+            unique: Vec::new(),
+            checks: Vec::new(),
+            foreign_keys: Vec::new(),
         };
 
         let pager = Pager::open(file_path).expect("Failed to open pager");
@@ -38,6 +45,7 @@ mod tests {
             pager,
             schema: schema.clone(),
             index,
+            unique_indexes: Vec::new(),
         };
 
         let row1 = Row {
@@ -73,6 +81,7 @@ mod tests {
             pager,
             schema: schema.clone(),
             index,
+            unique_indexes: Vec::new(),
         };
         table.load_index().expect("Failed to load index");
 
@@ -0,0 +1,31 @@
+#![cfg(test)]
+
+use crate::engine::Database;
+use crate::error::{Error, Result};
+use crate::sql::QueryResult;
+use crate::sql::parser::parse_sql;
+use crate::storage::record::Field;
+use std::fs;
+
+/// Opens a database in an empty `data_dir`, removing whatever a previous run left there.
+pub fn fresh_db(data_dir: &str) -> Database {
+    let _ = fs::remove_dir_all(data_dir);
+    Database::open(data_dir).expect("Failed to open database")
+}
+
+/// Parses and executes `sql`, returning the result of the last statement.
+pub fn run(db: &mut Database, sql: &str) -> Result<QueryResult> {
+    let mut result = Err(Error::parse("No statement"));
+    for command in parse_sql(sql)? {
+        result = Ok(db.execute(command)?);
+    }
+    result
+}
+
+/// Runs a query and returns its column names and rows.
+pub fn query(db: &mut Database, sql: &str) -> (Vec<String>, Vec<Vec<Field>>) {
+    match run(db, sql).expect("Query failed") {
+        QueryResult::Data(resp) => (resp.columns, resp.rows),
+        _ => panic!("Expected rows from: {}", sql),
+    }
+}
@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod tests {
+    use crate::engine::Database;
+    use crate::sql::QueryResult;
+    use crate::storage::pager::PAGE_SIZE;
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    fn count(result: QueryResult) -> usize {
+        match result {
+            QueryResult::RowsAffected { count, .. } => count,
+            _ => panic!("Expected RowsAffected"),
+        }
+    }
+
+    #[test]
+    fn test_delete_without_where() {
+        let data_dir = "/tmp/test_delete_without_where";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, name TEXT)",
+        )
+        .unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE posts (id INT PRIMARY KEY, user_id INT REFERENCES users(id) ON DELETE CASCADE)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO users VALUES (1, 'ann'), (2, 'bob')").unwrap();
+        run(&mut db, "INSERT INTO posts VALUES (10, 1), (20, 2)").unwrap();
+
+        // Every row goes, and the foreign keys act on each of them
+        assert_eq!(count(run(&mut db, "DELETE FROM users").unwrap()), 2);
+        assert!(query(&mut db, "SELECT * FROM users").1.is_empty());
+        assert!(query(&mut db, "SELECT * FROM posts").1.is_empty());
+
+        run(&mut db, "INSERT INTO users VALUES (1, 'again')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT name FROM users WHERE id = 1");
+        assert_eq!(rows, vec![vec![Field::Text("again".to_string())]]);
+        assert_eq!(count(run(&mut db, "DELETE FROM posts").unwrap()), 0);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_truncate_resets_file_and_indexes() {
+        let data_dir = "/tmp/test_truncate_resets";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(200) UNIQUE)",
+        )
+        .unwrap();
+        let values: Vec<String> = (0..200).map(|i| format!("({}, 'row {}')", i, i)).collect();
+        run(
+            &mut db,
+            &format!("INSERT INTO t VALUES {}", values.join(", ")),
+        )
+        .unwrap();
+
+        match run(&mut db, "TRUNCATE TABLE t").unwrap() {
+            QueryResult::DdlOk { message, .. } => assert_eq!(message, "Table t truncated."),
+            _ => panic!("Expected DdlOk"),
+        }
+        // Only the header page is left
+        let path = format!("{}/t.db", data_dir);
+        assert_eq!(fs::metadata(&path).unwrap().len(), PAGE_SIZE as u64);
+        assert!(query(&mut db, "SELECT * FROM t").1.is_empty());
+
+        // The primary key and UNIQUE indexes forgot the old rows
+        run(&mut db, "INSERT INTO t VALUES (5, 'row 5')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT name FROM t WHERE id = 5");
+        assert_eq!(rows, vec![vec![Field::Text("row 5".to_string())]]);
+        assert!(db.integrity_check().unwrap().is_empty());
+        drop(db);
+
+        let mut db = Database::open(data_dir).unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t");
+        assert_eq!(rows, vec![vec![Field::Integer(5)]]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_truncate_restart_identity() {
+        let data_dir = "/tmp/test_truncate_restart_identity";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY AUTOINCREMENT, name TEXT)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('a'), ('b')").unwrap();
+
+        // By default the ids carry on where they were
+        run(&mut db, "TRUNCATE t").unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('c')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t");
+        assert_eq!(rows, vec![vec![Field::Integer(3)]]);
+
+        run(&mut db, "TRUNCATE TABLE t CONTINUE IDENTITY").unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('d')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t");
+        assert_eq!(rows, vec![vec![Field::Integer(4)]]);
+
+        run(&mut db, "TRUNCATE TABLE t RESTART IDENTITY").unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('e')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t");
+        assert_eq!(rows, vec![vec![Field::Integer(1)]]);
+        drop(db);
+
+        // The restarted sequence was saved
+        let mut db = Database::open(data_dir).unwrap();
+        run(&mut db, "INSERT INTO t (name) VALUES ('f')").unwrap();
+        let (_, rows) = query(&mut db, "SELECT id FROM t WHERE name = 'f'");
+        assert_eq!(rows, vec![vec![Field::Integer(2)]]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_truncate_referenced_and_system_tables() {
+        let data_dir = "/tmp/test_truncate_referenced";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE parent (id INT PRIMARY KEY)").unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE child (id INT PRIMARY KEY, parent_id INT REFERENCES parent(id))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO parent VALUES (1)").unwrap();
+        run(&mut db, "INSERT INTO child VALUES (1, 1)").unwrap();
+
+        // Parent alone would leave the child pointing nowhere
+        assert_eq!(
+            run(&mut db, "TRUNCATE parent").err().map(|e| e.sqlstate()),
+            Some("2BP01")
+        );
+        assert_eq!(query(&mut db, "SELECT * FROM parent").1.len(), 1);
+        assert_eq!(
+            run(&mut db, "TRUNCATE missing").err().map(|e| e.sqlstate()),
+            Some("42P01")
+        );
+        assert_eq!(
+            run(&mut db, "TRUNCATE information_schema.tables")
+                .err()
+                .map(|e| e.sqlstate()),
+            Some("42809")
+        );
+
+        match run(&mut db, "TRUNCATE TABLE parent, child").unwrap() {
+            QueryResult::DdlOk { message, .. } => {
+                assert_eq!(message, "Tables parent, child truncated.")
+            }
+            _ => panic!("Expected DdlOk"),
+        }
+        assert!(query(&mut db, "SELECT * FROM parent").1.is_empty());
+        assert!(query(&mut db, "SELECT * FROM child").1.is_empty());
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
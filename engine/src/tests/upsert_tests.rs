@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    fn text(s: &str) -> Field {
+        Field::Text(s.to_string())
+    }
+
+    #[test]
+    fn test_on_conflict_do_nothing() {
+        let data_dir = "/tmp/test_upsert_do_nothing";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE users (id INT PRIMARY KEY, email VARCHAR(20) UNIQUE)",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO users VALUES (1, 'a@x.io')").unwrap();
+
+        // Conflicts on either key are skipped without an error
+        run(
+            &mut db,
+            "INSERT INTO users VALUES (1, 'b@x.io'), (2, 'a@x.io'), (3, 'c@x.io') \
+             ON CONFLICT DO NOTHING",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT * FROM users");
+        assert_eq!(
+            rows,
+            vec![
+                vec![Field::Integer(1), text("a@x.io")],
+                vec![Field::Integer(3), text("c@x.io")],
+            ]
+        );
+
+        // With a target, only that key is resolved; the other still raises
+        run(
+            &mut db,
+            "INSERT INTO users VALUES (1, 'z@x.io') ON CONFLICT (id) DO NOTHING",
+        )
+        .unwrap();
+        assert!(
+            run(
+                &mut db,
+                "INSERT INTO users VALUES (4, 'a@x.io') ON CONFLICT (id) DO NOTHING"
+            )
+            .is_err()
+        );
+        assert!(
+            run(
+                &mut db,
+                "INSERT INTO users VALUES (4, 'd@x.io') ON CONFLICT (nope) DO NOTHING"
+            )
+            .is_err()
+        );
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_on_conflict_do_update() {
+        let data_dir = "/tmp/test_upsert_do_update";
+        let mut db = fresh_db(data_dir);
+
+        run(
+            &mut db,
+            "CREATE TABLE counters (name VARCHAR(10) PRIMARY KEY, hits INT, label VARCHAR(10))",
+        )
+        .unwrap();
+        run(&mut db, "INSERT INTO counters VALUES ('home', 1, 'old')").unwrap();
+
+        let (_, rows) = query(
+            &mut db,
+            "INSERT INTO counters VALUES ('home', 5, 'new'), ('about', 1, 'new') \
+             ON CONFLICT (name) DO UPDATE SET hits = hits + EXCLUDED.hits, label = excluded.label \
+             RETURNING *",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![text("home"), Field::Integer(6), text("new")],
+                vec![text("about"), Field::Integer(1), text("new")],
+            ]
+        );
+
+        // The WHERE clause of DO UPDATE can leave the existing row alone
+        run(
+            &mut db,
+            "INSERT INTO counters VALUES ('home', 100, 'x') \
+             ON CONFLICT (name) DO UPDATE SET hits = excluded.hits WHERE hits > 10",
+        )
+        .unwrap();
+        let (_, rows) = query(&mut db, "SELECT hits FROM counters WHERE name = 'home'");
+        assert_eq!(rows, vec![vec![Field::Integer(6)]]);
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
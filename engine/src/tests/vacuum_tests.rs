@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use crate::storage::pager::PAGE_SIZE;
+    use crate::storage::record::Field;
+    use crate::tests::test_utils::{fresh_db, query, run};
+    use std::fs;
+
+    #[test]
+    fn test_vacuum_compacts_and_shrinks_the_file() {
+        let data_dir = "/tmp/test_vacuum_compacts";
+        let mut db = fresh_db(data_dir);
+        run(
+            &mut db,
+            "CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(200))",
+        )
+        .unwrap();
+        let values: Vec<String> = (0..200).map(|i| format!("({}, 'row {}')", i, i)).collect();
+        run(
+            &mut db,
+            &format!("INSERT INTO t VALUES {}", values.join(", ")),
+        )
+        .unwrap();
+        run(&mut db, "DELETE FROM t WHERE id % 20 <> 0").unwrap();
+
+        let path = format!("{}/t.db", data_dir);
+        let size_before = fs::metadata(&path).unwrap().len();
+        let (columns, rows) = query(&mut db, "VACUUM t");
+        assert_eq!(columns, vec!["table", "pages_before", "pages_after"]);
+        let size_after = fs::metadata(&path).unwrap().len();
+        assert_eq!(
+            rows,
+            vec![vec![
+                Field::Text("t".to_string()),
+                // The header page isn't counted
+                Field::Integer((size_before / PAGE_SIZE as u64) as i32 - 1),
+                Field::Integer(1),
+            ]]
+        );
+        assert_eq!(size_after, 2 * PAGE_SIZE as u64);
+
+        // Every row survives, and the rebuilt index finds them at their new slots
+        let (_, rows) = query(&mut db, "SELECT id FROM t");
+        assert_eq!(rows.len(), 10);
+        let (_, rows) = query(&mut db, "SELECT name FROM t WHERE id = 180");
+        assert_eq!(rows, vec![vec![Field::Text("row 180".to_string())]]);
+        assert!(run(&mut db, "INSERT INTO t VALUES (180, 'again')").is_err());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_vacuum_every_table() {
+        let data_dir = "/tmp/test_vacuum_every_table";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE b (id INT PRIMARY KEY)").unwrap();
+        run(&mut db, "CREATE TABLE a (id INT PRIMARY KEY)").unwrap();
+        run(&mut db, "INSERT INTO a VALUES (1)").unwrap();
+
+        let (_, rows) = query(&mut db, "VACUUM");
+        let names: Vec<&Field> = rows.iter().map(|row| &row[0]).collect();
+        assert_eq!(
+            names,
+            vec![&Field::Text("a".to_string()), &Field::Text("b".to_string())]
+        );
+        let (_, rows) = query(&mut db, "SELECT * FROM a");
+        assert_eq!(rows, vec![vec![Field::Integer(1)]]);
+
+        assert!(run(&mut db, "VACUUM missing").is_err());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+
+    #[test]
+    fn test_vacuum_spares_table_named_like_its_copy() {
+        let data_dir = "/tmp/test_vacuum_tmp_name";
+        let mut db = fresh_db(data_dir);
+        run(&mut db, "CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+        run(&mut db, "CREATE TABLE t.tmp (id INT PRIMARY KEY)").unwrap();
+        run(&mut db, "INSERT INTO t VALUES (1), (2)").unwrap();
+        run(&mut db, "INSERT INTO t.tmp VALUES (7)").unwrap();
+
+        // Neither the copy of t nor that of t.tmp may overwrite the other table
+        for sql in ["VACUUM t", "VACUUM t.tmp", "VACUUM"] {
+            run(&mut db, sql).unwrap();
+            let (_, rows) = query(&mut db, "SELECT id FROM t ORDER BY id");
+            assert_eq!(rows, vec![vec![Field::Integer(1)], vec![Field::Integer(2)]]);
+            let (_, rows) = query(&mut db, "SELECT id FROM t.tmp");
+            assert_eq!(rows, vec![vec![Field::Integer(7)]]);
+        }
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(data_dir);
+    }
+}
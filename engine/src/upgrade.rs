@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::Path;
+
+use crate::catalog::Catalog;
+use crate::catalog::schema::Schema;
+use crate::engine::Database;
+use crate::error::{Error, Result};
+use crate::storage::Table;
+use crate::storage::pager::{self, HEADER_SIZE, PAGE_SIZE, Page, Pager};
+use crate::storage::record::Row;
+
+impl Database {
+    /// Rewrites every table file in `data_dir` that was written before table files had
+    /// a header page into the current format, and returns the names of those tables.
+    /// The rows are read as such files laid them out and inserted into a new file,
+    /// which then replaces the old one. Every old file is read before any is replaced,
+    /// so a file whose rows can't all be recovered leaves the directory untouched.
+    /// Files already in the current format are left alone.
+    pub fn upgrade(data_dir: &str) -> Result<Vec<String>> {
+        let catalog = Catalog::read_directory(data_dir)?;
+        let mut names: Vec<&String> = catalog.tables.keys().collect();
+        names.sort();
+
+        let mut old_tables = Vec::new();
+        for name in names {
+            let path = format!("{}/{}.db", data_dir, name);
+            if !Path::new(&path).exists() {
+                continue;
+            }
+            if let Some(pages) = pager::read_headerless(&path)? {
+                let schema = &catalog.tables[name];
+                old_tables.push((name, path, schema, headerless_rows(&pages, schema)?));
+            }
+        }
+
+        let mut upgraded = Vec::new();
+        for (name, path, schema, rows) in old_tables {
+            let new_path = format!("{}.upgrade", path);
+            if Path::new(&new_path).exists() {
+                fs::remove_file(&new_path)?;
+            }
+            let mut pager = Pager::open(&new_path)?;
+            pager.set_schema_fingerprint(schema.fingerprint())?;
+            let mut table = Table::new(pager, schema.clone());
+            for row in rows {
+                table.insert_row(row)?;
+            }
+            table.pager.flush()?;
+            drop(table);
+            fs::rename(&new_path, &path)?;
+            upgraded.push(name.clone());
+        }
+        Ok(upgraded)
+    }
+}
+
+/// The rows on `pages` of a table file written before files had a header page,
+/// where every slot that fit in a page was used. Only the first `HEADER_SIZE * 8`
+/// slots have a bit of their own; bits past them lie in the rows themselves, so a
+/// page with data in those later slots fails instead of losing their rows.
+pub(crate) fn headerless_rows(pages: &[Page], schema: &Schema) -> Result<Vec<Row>> {
+    let row_size = schema.row_size();
+    let slots = (PAGE_SIZE - HEADER_SIZE).checked_div(row_size).unwrap_or(0);
+    let tracked = slots.min(HEADER_SIZE * 8);
+    let mut rows = Vec::new();
+    for (p_idx, page) in pages.iter().enumerate() {
+        let untracked = HEADER_SIZE + tracked * row_size..HEADER_SIZE + slots * row_size;
+        if page.data[untracked].iter().any(|&b| b != 0) {
+            return Err(Error::Corruption(format!(
+                "Table {}: page {} has rows past slot {}, which its file has no bits for; \
+                 they can't be recovered, so the file was left as it is",
+                schema.table_name, p_idx, tracked
+            )));
+        }
+        for s_idx in (0..tracked).filter(|&s| page.is_slot_full(s)) {
+            let offset = page.get_row_offset(s_idx, row_size);
+            rows.push(Row::deserialize(
+                &page.data[offset..offset + row_size],
+                schema,
+            )?);
+        }
+    }
+    Ok(rows)
+}
@@ -13,15 +13,29 @@ struct AppState {
 
 #[tokio::main]
 async fn main() {
-    let db = Database::open("./data");
+    let db = match Database::open("./data") {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
     let shared_state = Arc::new(AppState { db: Mutex::new(db) });
     let app = Router::new()
         .route("/", get(serve_html))
         .route("/query", post(handle_query))
         .with_state(shared_state);
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    let listener = match tokio::net::TcpListener::bind("0.0.0.0:3000").await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to bind 0.0.0.0:3000: {}", e);
+            std::process::exit(1);
+        }
+    };
     println!("Web Demo running on http://0.0.0.0:3000");
-    axum::serve(listener, app).await.unwrap();
+    if let Err(e) = axum::serve(listener, app).await {
+        eprintln!("Server error: {}", e);
+    }
 }
 
 async fn serve_html() -> Html<&'static str> {
@@ -233,9 +247,9 @@ async fn serve_html() -> Html<&'static str> {
                     const tr = document.createElement('tr');
                     row.forEach(cell => {
                         const td = document.createElement('td');
-                        // Extract value from format: {"Integer": 1} or {"Text": "oscar"}
-                        const valueKey = Object.keys(cell)[0];
-                        const value = cell[valueKey];
+                        // Extract value from format: {"Integer": 1}, {"Text": "oscar"} or "Null"
+                        const valueKey = typeof cell === 'string' ? cell : Object.keys(cell)[0];
+                        const value = typeof cell === 'string' ? 'NULL' : cell[valueKey];
 
                         td.innerHTML = `${value}<span class="type-label">(${valueKey})</span>`;
                         tr.appendChild(td);
@@ -244,8 +258,8 @@ async fn serve_html() -> Html<&'static str> {
                 });
 
                 tableContainer.classList.add('show');
-            } else if (firstResult.Message) {
-                // If it's just a message, we only show it in the pre tag (already done)
+            } else {
+                // Counts, DDL and errors are only shown in the pre tag (already done)
                 tableContainer.classList.remove('show');
             }
         }
@@ -266,18 +280,25 @@ async fn handle_query(
     state: axum::extract::State<Arc<AppState>>,
     Json(payload): Json<String>,
 ) -> Json<Vec<QueryResult>> {
-    let mut db = state.db.lock().unwrap();
+    // A poisoned lock only means an earlier request panicked; keep serving the rest
+    let mut db = state
+        .db
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
     match parse_sql(&payload) {
         Ok(commands) => {
             let mut results = Vec::new();
             for cmd in commands {
                 match db.execute(cmd) {
                     Ok(res) => results.push(res),
-                    Err(e) => results.push(QueryResult::Message(format!("Error: {}", e))),
+                    Err(e) => results.push(e.into()),
                 }
             }
+            if results.is_empty() {
+                results.push(QueryResult::Empty);
+            }
             Json(results)
         }
-        Err(e) => Json(vec![QueryResult::Message(format!("SQL Error: {}", e))]),
+        Err(e) => Json(vec![e.into()]),
     }
 }